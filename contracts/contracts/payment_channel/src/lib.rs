@@ -0,0 +1,285 @@
+//! Unidirectional payment channels for high-frequency billing.
+//!
+//! The owner locks a deposit for one merchant. Off-chain, the owner signs
+//! balance updates: a Soroban authorization for `settle(channel_id,
+//! amount)`, where `amount` is the running total owed. The merchant keeps
+//! the latest update and submits it whenever it wants to be paid, so many
+//! small charges cost one transaction. Closing refunds whatever was not
+//! settled; the owner has to wait out a delay first, giving the merchant
+//! time to settle the last update.
+
+#![no_std]
+
+use soroban_sdk::{
+    contract, contractevent, contractimpl, contracttype, token, Address, Env, IntoVal,
+};
+
+/// Shortest close delay a channel may be opened with (~1 hour), so the
+/// merchant always has time to settle the last update.
+pub const MIN_CLOSE_DELAY_LEDGERS: u32 = 720;
+
+/// TTL opening, topping up or settling a channel extends its entry and the
+/// contract instance to (~30 days), so channels in use never archive.
+pub const CHANNEL_TTL_LEDGERS: u32 = 30 * 17_280;
+
+#[contracttype]
+#[derive(Clone)]
+enum DataKey {
+    NextId,
+    Channel(u64),
+}
+
+/// An open channel. `settled` of `deposit` has been paid to the merchant.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Channel {
+    pub owner: Address,
+    pub merchant: Address,
+    pub token: Address,
+    pub deposit: i128,
+    pub settled: i128,
+    /// Ledgers the owner waits between `request_close` and `close`.
+    pub close_delay_ledgers: u32,
+    /// Ledger from which the owner may close, once requested.
+    pub closes_at: Option<u32>,
+}
+
+#[contractevent]
+pub struct ChannelOpened {
+    #[topic]
+    pub channel_id: u64,
+    pub owner: Address,
+    pub merchant: Address,
+    pub token: Address,
+    pub deposit: i128,
+}
+
+#[contractevent]
+pub struct ChannelToppedUp {
+    #[topic]
+    pub channel_id: u64,
+    pub amount: i128,
+    pub deposit: i128,
+}
+
+#[contractevent]
+pub struct ChannelSettled {
+    #[topic]
+    pub channel_id: u64,
+    pub settled: i128,
+    pub paid: i128,
+}
+
+#[contractevent]
+pub struct CloseRequested {
+    #[topic]
+    pub channel_id: u64,
+    pub closes_at: u32,
+}
+
+#[contractevent]
+pub struct ChannelClosed {
+    #[topic]
+    pub channel_id: u64,
+    pub settled: i128,
+    pub refunded: i128,
+}
+
+#[contract]
+pub struct PaymentChannelContract;
+
+#[contractimpl]
+impl PaymentChannelContract {
+    /// Lock `deposit` of `token` for payments to `merchant`. Returns the
+    /// channel id.
+    pub fn open(
+        env: Env,
+        owner: Address,
+        merchant: Address,
+        token: Address,
+        deposit: i128,
+        close_delay_ledgers: u32,
+    ) -> u64 {
+        owner.require_auth();
+        if deposit <= 0 {
+            panic!("Invalid deposit");
+        }
+        if close_delay_ledgers < MIN_CLOSE_DELAY_LEDGERS {
+            panic!("Close delay too short");
+        }
+
+        let channel_id: u64 = env.storage().instance().get(&DataKey::NextId).unwrap_or(1);
+        env.storage()
+            .instance()
+            .set(&DataKey::NextId, &(channel_id + 1));
+
+        token::Client::new(&env, &token).transfer(&owner, env.current_contract_address(), &deposit);
+        let channel = Channel {
+            owner: owner.clone(),
+            merchant: merchant.clone(),
+            token: token.clone(),
+            deposit,
+            settled: 0,
+            close_delay_ledgers,
+            closes_at: None,
+        };
+        Self::save_channel(&env, channel_id, &channel);
+
+        ChannelOpened {
+            channel_id,
+            owner,
+            merchant,
+            token,
+            deposit,
+        }
+        .publish(&env);
+        channel_id
+    }
+
+    /// Add `amount` to the deposit. Owner only; not once closing.
+    pub fn top_up(env: Env, channel_id: u64, amount: i128) {
+        let mut channel = Self::get_channel(env.clone(), channel_id);
+        channel.owner.require_auth();
+        if amount <= 0 {
+            panic!("Invalid amount");
+        }
+        if channel.closes_at.is_some() {
+            panic!("Channel is closing");
+        }
+
+        token::Client::new(&env, &channel.token).transfer(
+            &channel.owner,
+            env.current_contract_address(),
+            &amount,
+        );
+        channel.deposit += amount;
+        Self::save_channel(&env, channel_id, &channel);
+
+        ChannelToppedUp {
+            channel_id,
+            amount,
+            deposit: channel.deposit,
+        }
+        .publish(&env);
+    }
+
+    /// Pay the merchant up to `amount`, the cumulative total in a balance
+    /// update the owner signed. Anyone may submit it. Returns the amount
+    /// paid by this call.
+    pub fn settle(env: Env, channel_id: u64, amount: i128) -> i128 {
+        let mut channel = Self::get_channel(env.clone(), channel_id);
+        channel
+            .owner
+            .require_auth_for_args((channel_id, amount).into_val(&env));
+        if amount <= channel.settled {
+            panic!("Stale balance update");
+        }
+        if amount > channel.deposit {
+            panic!("Balance update exceeds deposit");
+        }
+
+        let paid = amount - channel.settled;
+        channel.settled = amount;
+        Self::save_channel(&env, channel_id, &channel);
+        token::Client::new(&env, &channel.token).transfer(
+            &env.current_contract_address(),
+            &channel.merchant,
+            &paid,
+        );
+
+        ChannelSettled {
+            channel_id,
+            settled: amount,
+            paid,
+        }
+        .publish(&env);
+        paid
+    }
+
+    /// Start the owner's close delay. Owner only.
+    pub fn request_close(env: Env, channel_id: u64) -> u32 {
+        let mut channel = Self::get_channel(env.clone(), channel_id);
+        channel.owner.require_auth();
+        if channel.closes_at.is_some() {
+            panic!("Close already requested");
+        }
+
+        let closes_at = env
+            .ledger()
+            .sequence()
+            .checked_add(channel.close_delay_ledgers)
+            .expect("Close ledger overflow");
+        channel.closes_at = Some(closes_at);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Channel(channel_id), &channel);
+
+        CloseRequested {
+            channel_id,
+            closes_at,
+        }
+        .publish(&env);
+        closes_at
+    }
+
+    /// Close the channel and refund the unsettled deposit to the owner.
+    /// The merchant may close at any time; the owner only once the delay
+    /// from `request_close` has passed. Returns the refund.
+    pub fn close(env: Env, caller: Address, channel_id: u64) -> i128 {
+        caller.require_auth();
+        let channel = Self::get_channel(env.clone(), channel_id);
+        if caller == channel.owner {
+            match channel.closes_at {
+                Some(closes_at) if env.ledger().sequence() >= closes_at => {}
+                Some(_) => panic!("Close delay active"),
+                None => panic!("Close not requested"),
+            }
+        } else if caller != channel.merchant {
+            panic!("Unauthorized: caller must be owner or merchant");
+        }
+
+        let refunded = channel.deposit - channel.settled;
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Channel(channel_id));
+        if refunded > 0 {
+            token::Client::new(&env, &channel.token).transfer(
+                &env.current_contract_address(),
+                &channel.owner,
+                &refunded,
+            );
+        }
+
+        ChannelClosed {
+            channel_id,
+            settled: channel.settled,
+            refunded,
+        }
+        .publish(&env);
+        refunded
+    }
+
+    /// Internal helper – stores `channel` and extends its TTL and the
+    /// instance's to `CHANNEL_TTL_LEDGERS`.
+    fn save_channel(env: &Env, channel_id: u64, channel: &Channel) {
+        let key = DataKey::Channel(channel_id);
+        env.storage().persistent().set(&key, channel);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, CHANNEL_TTL_LEDGERS, CHANNEL_TTL_LEDGERS);
+        env.storage()
+            .instance()
+            .extend_ttl(CHANNEL_TTL_LEDGERS, CHANNEL_TTL_LEDGERS);
+    }
+
+    /// Get an open channel.
+    pub fn get_channel(env: Env, channel_id: u64) -> Channel {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Channel(channel_id))
+            .expect("Channel not found")
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,196 @@
+extern crate std;
+
+use super::*;
+use soroban_sdk::testutils::storage::Persistent as _;
+use soroban_sdk::testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Ledger};
+use soroban_sdk::{symbol_short, vec, Env};
+
+struct Setup<'a> {
+    client: PaymentChannelContractClient<'a>,
+    token: token::Client<'a>,
+    owner: Address,
+    merchant: Address,
+    channel_id: u64,
+}
+
+fn setup(env: &Env) -> Setup<'_> {
+    env.mock_all_auths();
+    let contract_id = env.register(PaymentChannelContract, ());
+    let client = PaymentChannelContractClient::new(env, &contract_id);
+    let owner = Address::generate(env);
+    let merchant = Address::generate(env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(env))
+        .address();
+    token::StellarAssetClient::new(env, &token).mint(&owner, &1_000);
+
+    let channel_id = client.open(&owner, &merchant, &token, &600, &MIN_CLOSE_DELAY_LEDGERS);
+    Setup {
+        client,
+        token: token::Client::new(env, &token),
+        owner,
+        merchant,
+        channel_id,
+    }
+}
+
+#[test]
+fn test_open_locks_deposit() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    assert_eq!(s.channel_id, 1);
+    assert_eq!(s.token.balance(&s.owner), 400);
+    assert_eq!(s.token.balance(&s.client.address), 600);
+    assert_eq!(s.client.get_channel(&1).settled, 0);
+}
+
+#[test]
+fn test_settle_pays_difference_to_latest_update() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    assert_eq!(s.client.settle(&1, &50), 50);
+    // The owner signed this exact update, not the submitter's call
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            s.owner.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    s.client.address.clone(),
+                    symbol_short!("settle"),
+                    vec![&env, 1u64.into_val(&env), 50i128.into_val(&env)],
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+
+    assert_eq!(s.client.settle(&1, &175), 125);
+    assert_eq!(s.token.balance(&s.merchant), 175);
+    assert_eq!(s.client.get_channel(&1).settled, 175);
+}
+
+#[test]
+#[should_panic(expected = "Stale balance update")]
+fn test_stale_update_rejected() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.settle(&1, &100);
+    s.client.settle(&1, &80);
+}
+
+#[test]
+#[should_panic(expected = "Balance update exceeds deposit")]
+fn test_update_beyond_deposit_rejected() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.settle(&1, &601);
+}
+
+#[test]
+fn test_top_up_raises_deposit() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.top_up(&1, &400);
+    assert_eq!(s.client.settle(&1, &1_000), 1_000);
+}
+
+#[test]
+fn test_merchant_closes_any_time() {
+    let env = Env::default();
+    let s = setup(&env);
+    s.client.settle(&1, &200);
+
+    assert_eq!(s.client.close(&s.merchant, &1), 400);
+    assert_eq!(s.token.balance(&s.owner), 800);
+    assert!(s.client.try_get_channel(&1).is_err());
+}
+
+#[test]
+fn test_owner_closes_after_delay() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    assert_eq!(s.client.request_close(&1), MIN_CLOSE_DELAY_LEDGERS);
+    assert!(s.client.try_close(&s.owner, &1).is_err());
+    // The merchant can still settle during the delay
+    s.client.settle(&1, &300);
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number = MIN_CLOSE_DELAY_LEDGERS);
+    assert_eq!(s.client.close(&s.owner, &1), 300);
+    assert_eq!(s.token.balance(&s.owner), 700);
+    assert_eq!(s.token.balance(&s.merchant), 300);
+}
+
+#[test]
+#[should_panic(expected = "Close not requested")]
+fn test_owner_cannot_close_without_request() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.close(&s.owner, &1);
+}
+
+#[test]
+#[should_panic(expected = "Channel is closing")]
+fn test_no_top_up_while_closing() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.request_close(&1);
+    s.client.top_up(&1, &100);
+}
+
+#[test]
+#[should_panic(expected = "Close delay too short")]
+fn test_open_rejects_short_close_delay() {
+    let env = Env::default();
+    let s = setup(&env);
+
+    s.client.open(
+        &s.owner,
+        &s.merchant,
+        &s.token.address,
+        &100,
+        &(MIN_CLOSE_DELAY_LEDGERS - 1),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Close ledger overflow")]
+fn test_request_close_rejects_overflow() {
+    let env = Env::default();
+    let s = setup(&env);
+    let channel_id = s
+        .client
+        .open(&s.owner, &s.merchant, &s.token.address, &100, &u32::MAX);
+
+    env.ledger().with_mut(|li| li.sequence_number = 1);
+    s.client.request_close(&channel_id);
+}
+
+#[test]
+fn test_channel_use_extends_ttl() {
+    let env = Env::default();
+    let s = setup(&env);
+    let ttl = || {
+        env.as_contract(&s.client.address, || {
+            env.storage().persistent().get_ttl(&DataKey::Channel(1))
+        })
+    };
+    assert_eq!(ttl(), CHANNEL_TTL_LEDGERS);
+
+    env.ledger().with_mut(|li| li.sequence_number = 1_000);
+    s.client.top_up(&1, &100);
+    assert_eq!(ttl(), CHANNEL_TTL_LEDGERS);
+
+    env.ledger().with_mut(|li| li.sequence_number = 2_000);
+    s.client.settle(&1, &50);
+    assert_eq!(ttl(), CHANNEL_TTL_LEDGERS);
+}
@@ -11,6 +11,8 @@ pub struct SubscriptionMetadata {
     pub expected_amount: i128,
     pub next_renewal: u64,
     pub is_active: bool,
+    /// Hash of off-chain cancellation feedback, if the user left any.
+    pub feedback_hash: Option<BytesN<32>>,
 }
 
 #[contracttype]
@@ -49,6 +51,7 @@ pub struct SubscriptionCancelledEvent {
     pub subscription_id: BytesN<32>,
     pub user: Address,
     pub service_id: String,
+    pub feedback_hash: Option<BytesN<32>>,
 }
 
 #[contract]
@@ -101,6 +104,7 @@ impl SubscriptionRegistry {
             expected_amount,
             next_renewal,
             is_active: true,
+            feedback_hash: None,
         };
         env.storage()
             .instance()
@@ -186,8 +190,15 @@ impl SubscriptionRegistry {
         .publish(&env);
     }
 
-    /// Cancel a subscription by marking it as inactive
-    pub fn cancel_subscription(env: Env, subscription_id: BytesN<32>, user: Address) {
+    /// Cancel a subscription by marking it as inactive. `feedback_hash`
+    /// optionally anchors off-chain cancellation feedback, so it can later
+    /// be matched to this cancellation without putting it on-chain.
+    pub fn cancel_subscription(
+        env: Env,
+        subscription_id: BytesN<32>,
+        user: Address,
+        feedback_hash: Option<BytesN<32>>,
+    ) {
         let mut metadata: SubscriptionMetadata = env
             .storage()
             .instance()
@@ -199,6 +210,7 @@ impl SubscriptionRegistry {
         }
 
         metadata.is_active = false;
+        metadata.feedback_hash = feedback_hash.clone();
         env.storage()
             .instance()
             .set(&DataKey::Subscription(subscription_id.clone()), &metadata);
@@ -207,6 +219,7 @@ impl SubscriptionRegistry {
             subscription_id: subscription_id.clone(),
             user: user.clone(),
             service_id: metadata.service_id.clone(),
+            feedback_hash,
         }
         .publish(&env);
     }
@@ -0,0 +1,361 @@
+//! Arbitrary operation sequences against the renewal contract, run natively
+//! with the `invariants` feature:
+//!
+//! ```text
+//! cargo +nightly fuzz run renew_state_machine
+//! ```
+//!
+//! A run fails if the contract panics with a message it doesn't raise on
+//! purpose (including an invariant violation), if a rejected call left
+//! state behind, or if the subscriptions seen through the public API are
+//! inconsistent with what the calls returned.
+
+#![no_main]
+
+use arbitrary::{Arbitrary, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::xdr::{ContractEventBody, ScErrorType, ScVal};
+use soroban_sdk::{token, vec, Address, Env};
+use subscription_renewal::{
+    SubscriptionData, SubscriptionRenewalContract, SubscriptionRenewalContractClient,
+    SubscriptionState,
+};
+
+const SUBS: u64 = 3;
+const APPROVALS: u64 = 4;
+const MAX_OPS: usize = 64;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    InitSub {
+        sub: u8,
+    },
+    Approve {
+        sub: u8,
+        approval: u8,
+        max_spend: u16,
+        expires_in: u16,
+    },
+    Renew {
+        sub: u8,
+        approval: u8,
+        amount: u16,
+        max_retries: u8,
+        cooldown: u8,
+        succeed: bool,
+        caller: Caller,
+    },
+    SetSchedule {
+        sub: u8,
+        first_due_in: u8,
+        interval: u8,
+        window: u8,
+    },
+    Prepay {
+        sub: u8,
+        cycles: u8,
+        amount: u16,
+    },
+    SetExecutor {
+        sub: u8,
+    },
+    RemoveExecutor {
+        sub: u8,
+    },
+    Pause {
+        sub: u8,
+    },
+    Resume {
+        sub: u8,
+    },
+    Advance {
+        ledgers: u8,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+enum Caller {
+    Owner,
+    Executor,
+    Stranger,
+}
+
+/// Messages the contract panics with on purpose for these operations.
+const EXPECTED: &[&str] = &[
+    "Amount overflow",
+    "Cooldown period active",
+    "Cycle mismatch",
+    "Invalid or expired approval",
+    "Invalid prepayment",
+    "Invalid schedule",
+    "Prepayment overflow",
+    "Renewal not yet due",
+    "Subscription has no schedule",
+    "Subscription is in FAILED state",
+    "Subscription is paused",
+    "Subscription not found",
+    "Unauthorized: caller must be owner or executor, or an active keeper",
+];
+
+/// The message of the last string panic in a contract, from the host's
+/// diagnostic events; `try_` calls only return a generic error for those.
+fn last_panic(env: &Env) -> Option<String> {
+    let events = env.host().get_diagnostic_events().ok()?;
+    events.0.iter().rev().find_map(|e| {
+        let ContractEventBody::V0(body) = &e.event.body;
+        let message = match &body.data {
+            ScVal::String(s) => s.to_utf8_string_lossy(),
+            ScVal::Vec(Some(items)) => match items.first() {
+                Some(ScVal::String(s)) => s.to_utf8_string_lossy(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let panic = message.strip_prefix("caught panic '")?;
+        Some(panic[..panic.rfind("' from contract function")?].to_string())
+    })
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::abort();
+}
+
+struct Harness<'a> {
+    env: &'a Env,
+    client: SubscriptionRenewalContractClient<'a>,
+    token: Address,
+    owners: Vec<Address>,
+    executors: Vec<Address>,
+    stranger: Address,
+}
+
+impl<'a> Harness<'a> {
+    fn new(env: &'a Env) -> Self {
+        env.mock_all_auths();
+        let client = SubscriptionRenewalContractClient::new(
+            env,
+            &env.register(SubscriptionRenewalContract, ()),
+        );
+        client.init(&Address::generate(env));
+        let token = env
+            .register_stellar_asset_contract_v2(Address::generate(env))
+            .address();
+        let owners: Vec<Address> = (0..SUBS).map(|_| Address::generate(env)).collect();
+        for owner in &owners {
+            token::StellarAssetClient::new(env, &token).mint(owner, &1_000_000_000);
+        }
+        Harness {
+            env,
+            client,
+            token,
+            owners,
+            executors: (0..SUBS).map(|_| Address::generate(env)).collect(),
+            stranger: Address::generate(env),
+        }
+    }
+
+    fn subs(&self) -> Vec<Option<SubscriptionData>> {
+        (1..=SUBS)
+            .map(|sub_id| self.client.try_get_sub(&sub_id).ok().and_then(Result::ok))
+            .collect()
+    }
+
+    /// Apply `op`; returns the sub it targeted and `renew`'s result.
+    fn apply(&self, op: &Op) -> (Option<u64>, Option<bool>, bool) {
+        let sub_id = |sub: u8| sub as u64 % SUBS + 1;
+        let owner = |sub_id: u64| &self.owners[sub_id as usize - 1];
+        let now = self.env.ledger().sequence();
+
+        let (target, renewed, result) = match *op {
+            Op::InitSub { sub } => {
+                let id = sub_id(sub);
+                let merchant = Address::generate(self.env);
+                let result = self
+                    .client
+                    .try_init_sub(owner(id), &id, &merchant, &self.token, &None, &None);
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Approve {
+                sub,
+                approval,
+                max_spend,
+                expires_in,
+            } => {
+                let id = sub_id(sub);
+                let result = self.client.try_approve_renewal(
+                    &id,
+                    &(approval as u64 % APPROVALS + 1),
+                    &(max_spend as i128),
+                    &now.saturating_add(expires_in as u32),
+                );
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Renew {
+                sub,
+                approval,
+                amount,
+                max_retries,
+                cooldown,
+                succeed,
+                ref caller,
+            } => {
+                let id = sub_id(sub);
+                let caller = match caller {
+                    Caller::Owner => owner(id),
+                    Caller::Executor => &self.executors[id as usize - 1],
+                    Caller::Stranger => &self.stranger,
+                };
+                let result = self.client.try_renew(
+                    caller,
+                    &id,
+                    &(approval as u64 % APPROVALS + 1),
+                    &(amount as i128),
+                    &(max_retries as u32 % 4),
+                    &(cooldown as u32),
+                    &succeed,
+                );
+                let renewed = result.as_ref().ok().and_then(|r| r.as_ref().ok()).copied();
+                (Some(id), renewed, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::SetSchedule {
+                sub,
+                first_due_in,
+                interval,
+                window,
+            } => {
+                let id = sub_id(sub);
+                let result = self.client.try_set_schedule(
+                    &id,
+                    &(now + first_due_in as u32),
+                    &(interval as u32),
+                    &(window as u32),
+                );
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Prepay {
+                sub,
+                cycles,
+                amount,
+            } => {
+                let id = sub_id(sub);
+                let result =
+                    self.client
+                        .try_prepay_cycles(&id, &(cycles as u32 % 5), &(amount as i128));
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::SetExecutor { sub } => {
+                let id = sub_id(sub);
+                let executor = &self.executors[id as usize - 1];
+                let result = self.client.try_set_executor(&id, executor);
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::RemoveExecutor { sub } => {
+                let id = sub_id(sub);
+                let result = self.client.try_remove_executor(&id);
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Pause { sub } => {
+                let id = sub_id(sub);
+                let result = self.client.try_pause_sub(owner(id), &id);
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Resume { sub } => {
+                let id = sub_id(sub);
+                let result = self
+                    .client
+                    .try_resume_sub(&id, &vec![self.env, owner(id).clone()]);
+                (Some(id), None, result.map(|_| ()).map_err(|e| e.ok()))
+            }
+            Op::Advance { ledgers } => {
+                self.env
+                    .ledger()
+                    .with_mut(|li| li.sequence_number += ledgers as u32);
+                (None, None, Ok(()))
+            }
+        };
+
+        let rejected = match result {
+            Ok(()) => false,
+            // Typed contract errors are part of the interface
+            Err(Some(error)) if error.is_type(ScErrorType::Contract) => true,
+            Err(_) => match last_panic(self.env) {
+                Some(message) if EXPECTED.contains(&message.as_str()) => true,
+                other => fail(format!("{op:?}: unexpected panic {other:?}")),
+            },
+        };
+        (target, renewed, rejected)
+    }
+}
+
+fn check_consistent(
+    op: &Op,
+    before: &[Option<SubscriptionData>],
+    after: &[Option<SubscriptionData>],
+) {
+    for (i, data) in after.iter().enumerate() {
+        let Some(data) = data else { continue };
+        let sub_id = i as u64 + 1;
+        match data.state {
+            SubscriptionState::Active if data.failure_count != 0 => {
+                fail(format!("{op:?}: sub {sub_id} active with failures"))
+            }
+            SubscriptionState::Retrying | SubscriptionState::Failed if data.failure_count == 0 => {
+                fail(format!("{op:?}: sub {sub_id} retrying without failures"))
+            }
+            _ => {}
+        }
+
+        let Some(previous) = &before[i] else { continue };
+        if matches!(op, Op::InitSub { .. }) {
+            // Re-initializing starts the record over
+            continue;
+        }
+        if data.renewal_seq < previous.renewal_seq || data.renewal_seq > previous.renewal_seq + 1 {
+            fail(format!("{op:?}: sub {sub_id} renewal_seq jumped"));
+        }
+        if previous.state == SubscriptionState::Failed && data.state != SubscriptionState::Failed {
+            fail(format!("{op:?}: sub {sub_id} left FAILED"));
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let env = Env::default();
+    let harness = Harness::new(&env);
+
+    let mut input = Unstructured::new(data);
+    for _ in 0..MAX_OPS {
+        if input.is_empty() {
+            break;
+        }
+        let Ok(op) = Op::arbitrary(&mut input) else {
+            break;
+        };
+        let op = &op;
+        let before = harness.subs();
+        let (target, renewed, rejected) = harness.apply(op);
+        let after = harness.subs();
+
+        if rejected && before != after {
+            fail(format!("{op:?}: rejected call changed a subscription"));
+        }
+        if let (Some(sub_id), Some(renewed)) = (target, renewed) {
+            let i = sub_id as usize - 1;
+            let (Some(previous), Some(data)) = (&before[i], &after[i]) else {
+                fail(format!("{op:?}: renewed a missing subscription"));
+            };
+            let advanced = data.renewal_seq == previous.renewal_seq + 1;
+            if renewed != advanced {
+                fail(format!(
+                    "{op:?}: renew returned {renewed} but renewal_seq moved {advanced}"
+                ));
+            }
+            if renewed && data.state != SubscriptionState::Active {
+                fail(format!("{op:?}: renewed but not active"));
+            }
+        }
+        check_consistent(op, &before, &after);
+    }
+});
@@ -0,0 +1,312 @@
+//! Differential testing between contract versions.
+//!
+//! The same seeded, randomized operation sequence is applied to two
+//! deployments living in one `Env`, and after every step their return
+//! values, emitted events and observable storage are compared. Normally the
+//! pair is the current native contract and the previously released wasm:
+//!
+//! ```text
+//! SYNCRO_PREVIOUS_WASM=path/to/subscription_renewal.wasm cargo test differential
+//! ```
+//!
+//! Any divergence is reported with the seed and step so it can be replayed.
+
+extern crate std;
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events, Ledger};
+use soroban_sdk::xdr::ScVal;
+use soroban_sdk::{Address, Env, TryFromVal, Val};
+use std::format;
+use std::string::String;
+use std::vec::Vec;
+
+const SUBS: u64 = 3;
+const APPROVALS: u64 = 4;
+
+#[derive(Clone, Debug)]
+pub enum Op {
+    InitSub {
+        sub_id: u64,
+    },
+    Approve {
+        sub_id: u64,
+        approval_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+    },
+    Renew {
+        sub_id: u64,
+        approval_id: u64,
+        amount: i128,
+        max_retries: u32,
+        cooldown: u32,
+        succeed: bool,
+        by_executor: bool,
+    },
+    SetExecutor {
+        sub_id: u64,
+    },
+    RemoveExecutor {
+        sub_id: u64,
+    },
+    SetPaused(bool),
+    Advance(u32),
+}
+
+/// xorshift64*: tiny, deterministic, dependency-free.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+pub fn random_ops(seed: u64, len: usize) -> Vec<Op> {
+    let mut rng = Rng::new(seed);
+    let mut ops = Vec::with_capacity(len);
+    for _ in 0..len {
+        let sub_id = 1 + rng.below(SUBS);
+        let op = match rng.below(100) {
+            0..=9 => Op::InitSub { sub_id },
+            10..=34 => Op::Approve {
+                sub_id,
+                approval_id: 1 + rng.below(APPROVALS),
+                max_spend: 100 * (1 + rng.below(10)) as i128,
+                expires_at: 1 + rng.below(200) as u32,
+            },
+            35..=74 => Op::Renew {
+                sub_id,
+                approval_id: 1 + rng.below(APPROVALS),
+                amount: 100 * (1 + rng.below(10)) as i128,
+                max_retries: rng.below(4) as u32,
+                cooldown: rng.below(20) as u32,
+                succeed: rng.chance(50),
+                by_executor: rng.chance(20),
+            },
+            75..=79 => Op::SetExecutor { sub_id },
+            80..=82 => Op::RemoveExecutor { sub_id },
+            83..=85 => Op::SetPaused(rng.chance(50)),
+            _ => Op::Advance(rng.below(30) as u32),
+        };
+        ops.push(op);
+    }
+    ops
+}
+
+/// Addresses shared by both deployments so their state stays comparable.
+pub struct Actors {
+    pub owners: Vec<Address>,
+    pub executors: Vec<Address>,
+    pub merchant: Address,
+    pub token: Address,
+}
+
+impl Actors {
+    pub fn generate(env: &Env) -> Self {
+        let owners: Vec<Address> = (0..SUBS).map(|_| Address::generate(env)).collect();
+        let token = env
+            .register_stellar_asset_contract_v2(Address::generate(env))
+            .address();
+        // Enough for every renewal in a run, so wallet preflight never trips
+        let sac = token::StellarAssetClient::new(env, &token);
+        for owner in &owners {
+            sac.mint(owner, &i128::from(u32::MAX));
+        }
+        Actors {
+            owners,
+            executors: (0..SUBS).map(|_| Address::generate(env)).collect(),
+            merchant: Address::generate(env),
+            token,
+        }
+    }
+}
+
+/// What one deployment did in response to an operation.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    /// `None` if the call trapped.
+    result: Option<ScVal>,
+    events: Vec<(Vec<ScVal>, ScVal)>,
+}
+
+fn to_scval(env: &Env, val: &Val) -> ScVal {
+    ScVal::try_from_val(env, val).unwrap()
+}
+
+fn ok<T: soroban_sdk::IntoVal<Env, Val>, E1, E2>(
+    env: &Env,
+    res: Result<Result<T, E1>, E2>,
+) -> Option<ScVal> {
+    match res {
+        Ok(Ok(v)) => Some(to_scval(env, &v.into_val(env))),
+        _ => None,
+    }
+}
+
+fn apply(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    actors: &Actors,
+    op: &Op,
+) -> Outcome {
+    let idx = |sub_id: u64| (sub_id - 1) as usize;
+    let result = match op {
+        Op::InitSub { sub_id } => ok(
+            env,
+            client.try_init_sub(
+                &actors.owners[idx(*sub_id)],
+                sub_id,
+                &actors.merchant,
+                &actors.token,
+                &None,
+                &None,
+            ),
+        ),
+        Op::Approve {
+            sub_id,
+            approval_id,
+            max_spend,
+            expires_at,
+        } => ok(
+            env,
+            client.try_approve_renewal(sub_id, approval_id, max_spend, expires_at),
+        ),
+        Op::Renew {
+            sub_id,
+            approval_id,
+            amount,
+            max_retries,
+            cooldown,
+            succeed,
+            by_executor,
+        } => {
+            let caller = if *by_executor {
+                &actors.executors[idx(*sub_id)]
+            } else {
+                &actors.owners[idx(*sub_id)]
+            };
+            ok(
+                env,
+                client.try_renew(
+                    caller,
+                    sub_id,
+                    approval_id,
+                    amount,
+                    max_retries,
+                    cooldown,
+                    succeed,
+                ),
+            )
+        }
+        Op::SetExecutor { sub_id } => ok(
+            env,
+            client.try_set_executor(sub_id, &actors.executors[idx(*sub_id)]),
+        ),
+        Op::RemoveExecutor { sub_id } => ok(env, client.try_remove_executor(sub_id)),
+        Op::SetPaused(paused) => ok(env, client.try_set_paused(paused)),
+        Op::Advance(_) => Some(ScVal::Void),
+    };
+
+    let events = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, _, _)| *contract == client.address)
+        .map(|(_, topics, data)| {
+            (
+                topics.iter().map(|t| to_scval(env, &t)).collect(),
+                to_scval(env, &data),
+            )
+        })
+        .collect();
+
+    Outcome { result, events }
+}
+
+/// Everything a client can observe without mutating state.
+fn observe(env: &Env, client: &SubscriptionRenewalContractClient) -> Vec<ScVal> {
+    let mut seen = Vec::new();
+    seen.push(ok(env, client.try_is_paused()).unwrap());
+    for sub_id in 1..=SUBS {
+        seen.push(ok(env, client.try_get_sub(&sub_id)).unwrap_or(ScVal::Void));
+        seen.push(ok(env, client.try_get_executor(&sub_id)).unwrap());
+        seen.push(ok(env, client.try_get_freeze(&sub_id)).unwrap());
+        seen.push(ok(env, client.try_get_schedule(&sub_id)).unwrap());
+        for approval_id in 1..=APPROVALS {
+            seen.push(ok(env, client.try_get_approval(&sub_id, &approval_id)).unwrap());
+        }
+    }
+    seen
+}
+
+/// Apply `ops` to both deployments and return a description of the first
+/// divergence, if any.
+pub fn run(
+    env: &Env,
+    current: &Address,
+    previous: &Address,
+    actors: &Actors,
+    ops: &[Op],
+) -> Result<(), String> {
+    let current = SubscriptionRenewalContractClient::new(env, current);
+    let previous = SubscriptionRenewalContractClient::new(env, previous);
+
+    for (step, op) in ops.iter().enumerate() {
+        if let Op::Advance(ledgers) = op {
+            env.ledger().with_mut(|li| li.sequence_number += ledgers);
+        }
+
+        let a = apply(env, &current, actors, op);
+        let b = apply(env, &previous, actors, op);
+        if a != b {
+            return Err(format!(
+                "step {step} {op:?}: current {a:?} != previous {b:?}"
+            ));
+        }
+
+        let a = observe(env, &current);
+        let b = observe(env, &previous);
+        if a != b {
+            return Err(format!(
+                "step {step} {op:?}: state diverged\ncurrent  {a:?}\nprevious {b:?}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fresh `Env` with both deployments initialized by the same admin.
+pub fn setup(previous_wasm: Option<&[u8]>) -> (Env, Address, Address, Actors) {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let current = env.register(SubscriptionRenewalContract, ());
+    let previous = match previous_wasm {
+        Some(wasm) => env.register(wasm, ()),
+        None => env.register(SubscriptionRenewalContract, ()),
+    };
+
+    let admin = Address::generate(&env);
+    SubscriptionRenewalContractClient::new(&env, &current).init(&admin);
+    SubscriptionRenewalContractClient::new(&env, &previous).init(&admin);
+
+    let actors = Actors::generate(&env);
+    (env, current, previous, actors)
+}
@@ -0,0 +1,183 @@
+//! Snapshot fixtures for canonical multi-step scenarios.
+//!
+//! Each scenario is built imperatively once, written to
+//! `test_fixtures/<name>.json`, and afterwards reloaded straight from the
+//! snapshot so tests can start from a complex state in one line:
+//!
+//! ```ignore
+//! let fx = Fixture::load(Scenario::MidRetry);
+//! fx.client().renew(&fx.owner(), &fx.sub_id, &3, &500, &MAX_RETRIES, &COOLDOWN, &true);
+//! ```
+//!
+//! Run with `SYNCRO_REGEN_FIXTURES=1 cargo test -- --test-threads=1` to rebuild
+//! the files after an intentional change to contract storage.
+
+extern crate std;
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::xdr::{LedgerKey, ScVal};
+use soroban_sdk::{Address, Env, String};
+use std::format;
+use std::path::PathBuf;
+
+/// Fixed contract address so reloaded snapshots can re-register the contract.
+const CONTRACT_ID: &str = "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM";
+
+/// Retry policy the scenarios were built with.
+pub const MAX_RETRIES: u32 = 2;
+pub const COOLDOWN: u32 = 10;
+
+/// Approval terms used by the scenarios.
+pub const MAX_SPEND: i128 = 1000;
+pub const EXPIRES_AT: u32 = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Scenario {
+    /// One failed attempt: `Retrying`, failure_count 1, cooldown elapsed,
+    /// approvals 1 to 3 unused (failed attempts don't spend them).
+    MidRetry,
+    /// `MAX_RETRIES` failures: the next failure moves the subscription to
+    /// `Failed`. Approvals 1 to 3 unused, cooldown elapsed.
+    NearCap,
+    /// Active subscription whose only approval (id 1) expired at
+    /// `EXPIRES_AT`; the ledger is past expiry.
+    ExpiredApproval,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 3] = [
+        Scenario::MidRetry,
+        Scenario::NearCap,
+        Scenario::ExpiredApproval,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Scenario::MidRetry => "mid_retry",
+            Scenario::NearCap => "near_cap",
+            Scenario::ExpiredApproval => "expired_approval",
+        }
+    }
+
+    pub fn path(self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_fixtures")
+            .join(format!("{}.json", self.name()))
+    }
+}
+
+pub struct Fixture {
+    pub env: Env,
+    pub contract_id: Address,
+    pub sub_id: u64,
+}
+
+impl Fixture {
+    /// Load the scenario from its snapshot, building it first if the file is
+    /// missing or regeneration was requested.
+    pub fn load(scenario: Scenario) -> Self {
+        let path = scenario.path();
+        if std::env::var_os("SYNCRO_REGEN_FIXTURES").is_some() || !path.exists() {
+            let built = Self::build(scenario);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let mut snapshot = built.env.to_snapshot();
+            // Mocked auth nonces are replayed from the same PRNG seed in every
+            // fresh Env, so stored ones would collide with the next call.
+            snapshot.ledger.ledger_entries.retain(|(key, _)| {
+                !matches!(
+                    key.as_ref(),
+                    LedgerKey::ContractData(data) if matches!(data.key, ScVal::LedgerKeyNonce(_))
+                )
+            });
+            snapshot.auth = Default::default();
+            snapshot.events = Default::default();
+            snapshot.write_file(&path).unwrap();
+        }
+
+        // Full snapshots carry the address generator along with the ledger,
+        // so addresses generated in tests don't alias the scenario's.
+        let env = Env::from_snapshot_file(&path);
+        env.mock_all_auths();
+        let contract_id = contract_address(&env);
+        env.register_at(&contract_id, SubscriptionRenewalContract, ());
+        Fixture {
+            env,
+            contract_id,
+            sub_id: 1,
+        }
+    }
+
+    /// Build the scenario imperatively in a fresh `Env`.
+    pub fn build(scenario: Scenario) -> Self {
+        let env = Env::default();
+        env.mock_all_auths();
+        let contract_id = contract_address(&env);
+        env.register_at(&contract_id, SubscriptionRenewalContract, ());
+        let client = SubscriptionRenewalContractClient::new(&env, &contract_id);
+
+        client.init(&Address::generate(&env));
+        let owner = Address::generate(&env);
+        let sub_id = 1;
+        let merchant = Address::generate(&env);
+        let token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        token::StellarAssetClient::new(&env, &token).mint(&owner, &1_000_000);
+        client.init_sub(&owner, &sub_id, &merchant, &token, &None, &None);
+
+        match scenario {
+            Scenario::MidRetry => {
+                for approval_id in 1..=3 {
+                    client.approve_renewal(&sub_id, &approval_id, &MAX_SPEND, &EXPIRES_AT);
+                }
+                client.renew(&owner, &sub_id, &1, &500, &MAX_RETRIES, &COOLDOWN, &false);
+                advance(&env, COOLDOWN);
+            }
+            Scenario::NearCap => {
+                for approval_id in 1..=3 {
+                    client.approve_renewal(&sub_id, &approval_id, &MAX_SPEND, &EXPIRES_AT);
+                }
+                for approval_id in 1..=MAX_RETRIES as u64 {
+                    client.renew(
+                        &owner,
+                        &sub_id,
+                        &approval_id,
+                        &500,
+                        &MAX_RETRIES,
+                        &COOLDOWN,
+                        &false,
+                    );
+                    advance(&env, COOLDOWN);
+                }
+            }
+            Scenario::ExpiredApproval => {
+                client.approve_renewal(&sub_id, &1, &MAX_SPEND, &EXPIRES_AT);
+                env.ledger()
+                    .with_mut(|li| li.sequence_number = EXPIRES_AT + 1);
+            }
+        }
+
+        Fixture {
+            env,
+            contract_id,
+            sub_id,
+        }
+    }
+
+    pub fn client(&self) -> SubscriptionRenewalContractClient<'_> {
+        SubscriptionRenewalContractClient::new(&self.env, &self.contract_id)
+    }
+
+    pub fn owner(&self) -> Address {
+        self.client().get_sub(&self.sub_id).owner
+    }
+}
+
+pub fn contract_address(env: &Env) -> Address {
+    Address::from_string(&String::from_str(env, CONTRACT_ID))
+}
+
+fn advance(env: &Env, ledgers: u32) {
+    env.ledger().with_mut(|li| li.sequence_number += ledgers);
+}
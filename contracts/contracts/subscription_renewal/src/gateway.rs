@@ -0,0 +1,107 @@
+//! How a renewal collects the part of its charge not paid from credit.
+//!
+//! Each subscription picks a `PaymentMode`; `renew` only talks to the
+//! `PaymentGateway` it maps to, so a new way of paying is a new variant and
+//! an impl here rather than another branch in the renewal flow. Whatever
+//! the gateway leaves in the contract is then split between the protocol
+//! fee and the merchant.
+
+use crate::{Error, PaymentMode, SubscriptionData, SubscriptionRenewalContract as Contract};
+use soroban_sdk::{panic_with_error, token, Env};
+
+pub(crate) trait PaymentGateway {
+    /// Fail with a typed error if `amount` can't be collected, before the
+    /// renewal records anything.
+    fn preflight(&self, env: &Env, data: &SubscriptionData, amount: i128);
+
+    /// Move `amount` (always positive) from the owner into the contract,
+    /// returning how much of it the contract now holds for payout.
+    fn collect(&self, env: &Env, data: &SubscriptionData, amount: i128) -> i128;
+}
+
+/// The gateway for `mode`.
+pub(crate) fn for_mode(mode: &PaymentMode) -> &'static dyn PaymentGateway {
+    match mode {
+        PaymentMode::External => &External,
+        PaymentMode::Direct => &Direct,
+        PaymentMode::Allowance => &Allowance,
+        PaymentMode::Escrow => &Escrow,
+    }
+}
+
+/// Checks the owner's balance; the caller settles the payment itself, so
+/// nothing passes through the contract.
+struct External;
+
+impl PaymentGateway for External {
+    fn preflight(&self, env: &Env, data: &SubscriptionData, amount: i128) {
+        Contract::preflight_wallet(env, data, amount);
+    }
+
+    fn collect(&self, _env: &Env, _data: &SubscriptionData, _amount: i128) -> i128 {
+        0
+    }
+}
+
+/// Plain SEP-41 `transfer` from the owner, who has to authorize it.
+struct Direct;
+
+impl PaymentGateway for Direct {
+    fn preflight(&self, env: &Env, data: &SubscriptionData, amount: i128) {
+        Contract::preflight_wallet(env, data, amount);
+    }
+
+    fn collect(&self, env: &Env, data: &SubscriptionData, amount: i128) -> i128 {
+        token::Client::new(env, &data.token).transfer(
+            &data.owner,
+            env.current_contract_address(),
+            &amount,
+        );
+        Contract::adjust_tracked_balance(env, &data.token, amount);
+        amount
+    }
+}
+
+/// `transfer_from` against an allowance the owner granted the contract, so
+/// keepers can renew without the owner's signature.
+struct Allowance;
+
+impl PaymentGateway for Allowance {
+    fn preflight(&self, env: &Env, data: &SubscriptionData, amount: i128) {
+        Contract::preflight_wallet(env, data, amount);
+        let allowance = token::Client::new(env, &data.token)
+            .allowance(&data.owner, &env.current_contract_address());
+        if allowance < amount {
+            panic_with_error!(env, Error::InsufficientBalance);
+        }
+    }
+
+    fn collect(&self, env: &Env, data: &SubscriptionData, amount: i128) -> i128 {
+        let contract = env.current_contract_address();
+        token::Client::new(env, &data.token).transfer_from(
+            &contract,
+            &data.owner,
+            &contract,
+            &amount,
+        );
+        Contract::adjust_tracked_balance(env, &data.token, amount);
+        amount
+    }
+}
+
+/// Pays only from funds already held by the contract (credit and prepaid
+/// cycles); the owner's wallet is never charged.
+struct Escrow;
+
+impl PaymentGateway for Escrow {
+    fn preflight(&self, env: &Env, _data: &SubscriptionData, amount: i128) {
+        if amount > 0 {
+            panic_with_error!(env, Error::InsufficientBalance);
+        }
+    }
+
+    fn collect(&self, env: &Env, _data: &SubscriptionData, _amount: i128) -> i128 {
+        // Preflight rejects any amount left for the wallet
+        panic_with_error!(env, Error::InsufficientBalance);
+    }
+}
@@ -0,0 +1,198 @@
+//! Golden files for the events canonical flows publish.
+//!
+//! Each flow runs in a fresh `Env` and every event the contract publishes
+//! along the way is written to `test_golden/<name>.txt` as the hex of its
+//! `ContractEvent` XDR, under a comment naming the call and the event.
+//! Indexers decode exactly these bytes, so renaming an event, reordering
+//! its topics or changing a field's name or type fails the comparison.
+//!
+//! Run with `SYNCRO_REGEN_FIXTURES=1 cargo test golden` to rewrite the
+//! files after an intentional change to an event.
+
+extern crate std;
+
+use super::*;
+use crate::fixtures::{contract_address, COOLDOWN, EXPIRES_AT, MAX_RETRIES, MAX_SPEND};
+use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::xdr::{
+    ContractEventBody, ContractEventType, ContractId, Limits, ScAddress, ScVal, WriteXdr,
+};
+use soroban_sdk::{vec, Address, Env, TryFromVal};
+use std::fmt::Write;
+use std::format;
+use std::path::PathBuf;
+use std::string::String;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Flow {
+    /// Approve, renew successfully, fail once and recover.
+    Renewal,
+    /// Fail past `MAX_RETRIES` until the subscription is `Failed`.
+    RetryToFailed,
+    /// Owner-side management: executor, pause, schedule, prepayment and
+    /// payment mode.
+    Management,
+}
+
+impl Flow {
+    pub const ALL: [Flow; 3] = [Flow::Renewal, Flow::RetryToFailed, Flow::Management];
+
+    fn name(self) -> &'static str {
+        match self {
+            Flow::Renewal => "renewal",
+            Flow::RetryToFailed => "retry_to_failed",
+            Flow::Management => "management",
+        }
+    }
+
+    pub fn path(self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_golden")
+            .join(format!("{}.txt", self.name()))
+    }
+
+    /// The flow's events in golden-file form.
+    pub fn record(self) -> String {
+        let env = Env::default();
+        env.mock_all_auths();
+        let contract = contract_address(&env);
+        env.register_at(&contract, SubscriptionRenewalContract, ());
+        let client = SubscriptionRenewalContractClient::new(&env, &contract);
+        let mut golden = Recorder::new(&env, &contract);
+
+        client.init(&Address::generate(&env));
+        golden.record("init");
+        let owner = Address::generate(&env);
+        let sub_id = 1;
+        let token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        token::StellarAssetClient::new(&env, &token).mint(&owner, &1_000_000);
+        client.init_sub(
+            &owner,
+            &sub_id,
+            &Address::generate(&env),
+            &token,
+            &None,
+            &None,
+        );
+        golden.record("init_sub");
+
+        match self {
+            Flow::Renewal => {
+                for approval_id in 1..=3 {
+                    client.approve_renewal(&sub_id, &approval_id, &MAX_SPEND, &EXPIRES_AT);
+                    golden.record("approve_renewal");
+                }
+                client.renew(&owner, &sub_id, &1, &500, &MAX_RETRIES, &COOLDOWN, &true);
+                golden.record("renew");
+                advance(&env, COOLDOWN);
+                client.renew(&owner, &sub_id, &2, &500, &MAX_RETRIES, &COOLDOWN, &false);
+                golden.record("renew");
+                advance(&env, COOLDOWN);
+                client.renew(&owner, &sub_id, &3, &500, &MAX_RETRIES, &COOLDOWN, &true);
+                golden.record("renew");
+            }
+            Flow::RetryToFailed => {
+                for approval_id in 1..=MAX_RETRIES as u64 + 1 {
+                    client.approve_renewal(&sub_id, &approval_id, &MAX_SPEND, &EXPIRES_AT);
+                    golden.record("approve_renewal");
+                    client.renew(
+                        &owner,
+                        &sub_id,
+                        &approval_id,
+                        &500,
+                        &MAX_RETRIES,
+                        &COOLDOWN,
+                        &false,
+                    );
+                    golden.record("renew");
+                    advance(&env, COOLDOWN);
+                }
+            }
+            Flow::Management => {
+                client.set_executor(&sub_id, &Address::generate(&env));
+                golden.record("set_executor");
+                client.remove_executor(&sub_id);
+                golden.record("remove_executor");
+                client.pause_sub(&owner, &sub_id);
+                golden.record("pause_sub");
+                client.resume_sub(&sub_id, &vec![&env, owner.clone()]);
+                golden.record("resume_sub");
+                client.set_schedule(&sub_id, &100, &50, &10);
+                golden.record("set_schedule");
+                client.prepay_cycles(&sub_id, &2, &500);
+                golden.record("prepay_cycles");
+                client.set_payment_mode(&sub_id, &PaymentMode::Direct);
+                golden.record("set_payment_mode");
+            }
+        }
+        golden.out
+    }
+
+    /// Compare the flow against its golden file, writing the file first if
+    /// it is missing or regeneration was requested.
+    pub fn check(self) {
+        let path = self.path();
+        let recorded = self.record();
+        if std::env::var_os("SYNCRO_REGEN_FIXTURES").is_some() || !path.exists() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &recorded).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            golden,
+            recorded,
+            "events differ from {}; regenerate it only if the change is intentional",
+            path.display()
+        );
+    }
+}
+
+struct Recorder<'a> {
+    env: &'a Env,
+    contract: ContractId,
+    out: String,
+}
+
+impl<'a> Recorder<'a> {
+    fn new(env: &'a Env, contract: &Address) -> Self {
+        let ScVal::Address(ScAddress::Contract(contract)) =
+            ScVal::try_from_val(env, contract.as_val()).unwrap()
+        else {
+            unreachable!()
+        };
+        Recorder {
+            env,
+            contract,
+            out: String::new(),
+        }
+    }
+
+    /// Append the events the contract published in the last call.
+    fn record(&mut self, call: &str) {
+        let events = self.env.host().get_events().unwrap();
+        for event in events.0.iter().filter(|e| !e.failed_call).map(|e| &e.event) {
+            if event.type_ != ContractEventType::Contract
+                || event.contract_id.as_ref() != Some(&self.contract)
+            {
+                continue;
+            }
+            let ContractEventBody::V0(body) = &event.body;
+            let name = match body.topics.first() {
+                Some(ScVal::Symbol(name)) => name.to_utf8_string_lossy(),
+                _ => String::from("?"),
+            };
+            let xdr = event.to_xdr(Limits::none()).unwrap();
+            writeln!(self.out, "# {call}: {name}").unwrap();
+            for byte in xdr {
+                write!(self.out, "{byte:02x}").unwrap();
+            }
+            self.out.push('\n');
+        }
+    }
+}
+
+fn advance(env: &Env, ledgers: u32) {
+    env.ledger().with_mut(|li| li.sequence_number += ledgers);
+}
@@ -0,0 +1,113 @@
+//! Invariant checks run after every mutating entry point.
+//!
+//! Compiled in for unit tests and for builds with the `invariants` feature
+//! (e.g. a debug wasm for e2e runs); release wasm leaves them out. A
+//! violation traps with a message naming the broken invariant.
+
+use super::*;
+
+/// Check the stored state of `sub_id` against its state before the call.
+/// `previous` is `None` when the call (re)initialized the subscription.
+pub fn check_sub(env: &Env, sub_id: u64, previous: Option<&SubscriptionData>) {
+    let data: SubscriptionData = env
+        .storage()
+        .persistent()
+        .get(&sub_id)
+        .expect("Invariant violated: subscription missing after write");
+
+    if data.version != SUBSCRIPTION_DATA_VERSION {
+        panic!("Invariant violated: subscription stored in an old layout");
+    }
+
+    match data.state {
+        SubscriptionState::Active if data.failure_count != 0 => {
+            panic!("Invariant violated: active subscription has failures")
+        }
+        SubscriptionState::Retrying | SubscriptionState::Failed if data.failure_count == 0 => {
+            panic!("Invariant violated: retrying or failed subscription has no failures")
+        }
+        _ => {}
+    }
+
+    if data.last_attempt_ledger > env.ledger().sequence() {
+        panic!("Invariant violated: last attempt is in the future");
+    }
+
+    if let Some(previous) = previous {
+        if data.owner != previous.owner {
+            panic!("Invariant violated: owner changed");
+        }
+        if data.merchant != previous.merchant || data.token != previous.token {
+            panic!("Invariant violated: payment terms changed");
+        }
+        if data.last_attempt_ledger < previous.last_attempt_ledger {
+            panic!("Invariant violated: last attempt moved backwards");
+        }
+        if data.renewal_seq != previous.renewal_seq && data.renewal_seq != previous.renewal_seq + 1
+        {
+            panic!("Invariant violated: renewal_seq skipped or moved backwards");
+        }
+        if previous.state == SubscriptionState::Failed && data.state != SubscriptionState::Failed {
+            panic!("Invariant violated: subscription left FAILED state");
+        }
+        if previous.state == SubscriptionState::Completed
+            && data.state != SubscriptionState::Completed
+        {
+            panic!("Invariant violated: subscription left COMPLETED state");
+        }
+    }
+}
+
+/// Check the stored schedule of `sub_id` against its state before a renewal.
+pub fn check_schedule(env: &Env, sub_id: u64, previous: Option<&Schedule>) {
+    let schedule: Option<Schedule> = env.storage().persistent().get(&SubKey::Schedule(sub_id));
+    if let (Some(schedule), Some(previous)) = (schedule, previous) {
+        if schedule.next_due < previous.next_due {
+            panic!("Invariant violated: next_due moved backwards");
+        }
+        if schedule.missed_cycles < previous.missed_cycles {
+            panic!("Invariant violated: missed cycles decreased");
+        }
+        if schedule.prepaid_cycles > previous.prepaid_cycles {
+            panic!("Invariant violated: renewal added prepaid cycles");
+        }
+        if schedule.interval_ledgers != previous.interval_ledgers
+            || schedule.window_ledgers != previous.window_ledgers
+        {
+            panic!("Invariant violated: schedule terms changed");
+        }
+        let start: Option<u32> = env
+            .storage()
+            .persistent()
+            .get(&SubKey::ScheduleStart(sub_id));
+        if let Some(start) = start {
+            if schedule.next_due < start
+                || !(schedule.next_due - start).is_multiple_of(schedule.interval_ledgers)
+            {
+                panic!("Invariant violated: next_due off the cycle grid");
+            }
+        }
+    }
+}
+
+/// Check an approval after it was consumed: it must be spent, and its terms
+/// must be unchanged.
+pub fn check_consumed_approval(env: &Env, key: &ApprovalKey, previous: &RenewalApproval) {
+    let approval: RenewalApproval = env
+        .storage()
+        .persistent()
+        .get(key)
+        .expect("Invariant violated: approval missing after consumption");
+
+    if !approval.used {
+        panic!("Invariant violated: consumed approval not marked used");
+    }
+    if approval.sub_id != key.sub_id
+        || approval.merchant != previous.merchant
+        || approval.token != previous.token
+        || approval.max_spend != previous.max_spend
+        || approval.expires_at != previous.expires_at
+    {
+        panic!("Invariant violated: approval terms changed on consumption");
+    }
+}
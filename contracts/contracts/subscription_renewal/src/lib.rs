@@ -1,23 +1,160 @@
 #![no_std]
 
+use soroban_sdk::xdr::ToXdr;
 use soroban_sdk::{
-    contract,
-    contractevent,
-    contractimpl,
-    contracttype,
-    token,
-    xdr::ToXdr,
-    Address,
-    Bytes,
-    Env,
-    IntoVal,
-};#[contracttype]
+    contract, contractclient, contractimpl, contractmeta, contracttype, panic_with_error,
+    symbol_short, token, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, Val, Vec,
+};
+
+pub use syncro_types::*;
+
+contractmeta!(key = "semver", val = env!("CARGO_PKG_VERSION"));
+// Keep in sync with INTERFACE_ID
+contractmeta!(key = "interface", val = "syncro_renewal_v1");
+contractmeta!(key = "commit", val = env!("SYNCRO_COMMIT"));
+
+/// Interface id of the renewal contract, also embedded as `interface` meta.
+pub const INTERFACE_ID: &str = "syncro_renewal_v1";
+
+/// Optional feature sets, reported by `supports` next to `INTERFACE_ID`.
+pub const FEATURES: [&str; 9] = [
+    "templates_v1",
+    "credit_v1",
+    "crank_v1",
+    "joint_owners_v1",
+    "recovery_v1",
+    "multicall_v1",
+    "claimable_payouts_v1",
+    "economics_v1",
+    "payment_modes_v1",
+];
+
+mod gateway;
+#[cfg(any(test, feature = "invariants"))]
+mod invariants;
+mod paging;
+mod rounding;
+
+#[contracttype]
 #[derive(Clone)]
 enum ContractKey {
     Admin,
     Paused,
+    FeeConfig,
+    LoggingContract,
+    ComplianceOfficer,
+    ComplianceContract,
+    PendingRescue,
+    RetryCounting,
+    Operator,
+    CrankBounty,
+    CrankCommitReveal,
+    KeeperFee,
+    NextTemplateId,
+    VerboseEvents,
+    WithdrawalsFrozen,
+}
+
+/// Interface an external compliance contract must implement. `check`
+/// returns false to block the payment.
+#[contractclient(name = "ComplianceClient")]
+pub trait ComplianceCheck {
+    fn check(env: Env, owner: Address, merchant: Address, token: Address, amount: i128) -> bool;
+}
+
+/// Interface a merchant's pricing contract must implement. `quote` returns
+/// what cycle `cycle_id` (1-based, the subscription's `renewal_seq + 1`)
+/// of `sub_id` costs.
+#[contractclient(name = "PricingClient")]
+pub trait PricingQuote {
+    fn quote(env: Env, sub_id: u64, cycle_id: u64) -> i128;
 }
 
+/// Maximum fee percentage, expressed in basis points (100%)
+const MAX_FEE_BPS: u32 = 10_000;
+
+/// Largest early-renewal discount a merchant may offer, in basis points.
+pub const MAX_EARLY_DISCOUNT_BPS: u32 = 1_000;
+
+/// Largest prepay discount a merchant may offer, in basis points.
+pub const MAX_PREPAY_DISCOUNT_BPS: u32 = 3_000;
+
+/// Share of the usage budget, in percent, at which `UsageThresholdReached`
+/// fires.
+const USAGE_THRESHOLDS: [u32; 3] = [50, 80, 100];
+
+/// Ledgers between proposing and executing a token rescue (~1 day).
+pub const RESCUE_TIMELOCK_LEDGERS: u32 = 17_280;
+
+/// Minimum ledgers between two `UpcomingChange` notices for one
+/// subscription (~1 day).
+pub const CHANGE_NOTICE_LEDGERS: u32 = 17_280;
+
+/// Notice subscribers get before a plan migration takes effect (~1 week).
+pub const PLAN_MIGRATION_NOTICE_LEDGERS: u32 = 7 * 17_280;
+
+/// TTL a successful renewal extends the subscription's entries and the
+/// contract instance to (~30 days), so active subscriptions never archive.
+pub const RENEWAL_TTL_LEDGERS: u32 = 30 * 17_280;
+
+/// How long `renew_idempotent` remembers a key (~1 day).
+pub const IDEMPOTENCY_TTL_LEDGERS: u32 = 17_280;
+
+/// Window a merchant's `PauseLimits::max_paused_ledgers` applies to
+/// (~1 year).
+pub const PAUSE_QUOTA_WINDOW_LEDGERS: u32 = 365 * 17_280;
+
+/// Most subscribers one `migrate_subs_to_plan` call walks.
+pub const MAX_MIGRATION_PAGE: u32 = paging::MIGRATION.max_items();
+
+/// Length of the windows `get_owner_spending` totals over (~30 days).
+/// Window `n` covers ledgers from `n * SPENDING_WINDOW_LEDGERS` up to the
+/// next window.
+pub const SPENDING_WINDOW_LEDGERS: u32 = 30 * 17_280;
+
+/// Length of a merchant's settlement periods (~1 day). Period `n` covers
+/// ledgers from `n * SETTLEMENT_PERIOD_LEDGERS` up to the next period.
+pub const SETTLEMENT_PERIOD_LEDGERS: u32 = 17_280;
+
+/// Most receipts one `export_settlement` call returns.
+pub const MAX_SETTLEMENT_PAGE: u32 = paging::SETTLEMENT_EXPORT.max_items();
+
+/// Length of the windows merchant health is counted in (~7 days);
+/// `get_merchant_health` covers the current and previous one.
+pub const HEALTH_WINDOW_LEDGERS: u32 = 7 * 17_280;
+
+/// Renewal attempts a merchant needs in its health windows before its
+/// failure rate counts.
+pub const MIN_HEALTH_ATTEMPTS: u32 = 10;
+
+/// Failure rate, in basis points, at which a merchant must re-verify.
+pub const FAILURE_ALERT_BPS: u32 = 5_000;
+
+/// Ledgers a merchant has to re-verify before its renewals stop (~3 days).
+pub const REVERIFY_GRACE_LEDGERS: u32 = 3 * 17_280;
+
+/// Most whole tokens a configured price, cap or limit may be, scaled by
+/// the token's decimals. An amount entered for a token with more decimals
+/// (18 instead of 7) lands far above it and is rejected.
+pub const MAX_WHOLE_TOKENS: i128 = 1_000_000_000;
+
+/// Active subscriptions an owner may hold with one merchant, unless the
+/// merchant sets its own limit.
+pub const DEFAULT_MAX_OWNER_SUBS: u32 = 1;
+
+/// Most requests one `approve_renewals` call takes.
+pub const MAX_APPROVAL_BATCH: u32 = paging::APPROVAL_BATCH.max_items();
+
+/// Most labels an owner can attach to one subscription.
+pub const MAX_LABELS: u32 = 8;
+
+/// Retry terms keepers and cranks renew on when a subscription has no
+/// `RetryPolicy` of its own (3 retries, ~1 hour apart).
+pub const DEFAULT_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_retries: 3,
+    cooldown_ledgers: 720,
+};
+
 /// Storage key for approvals: (sub_id, approval_id)
 #[contracttype]
 #[derive(Clone)]
@@ -26,6 +163,15 @@ struct ApprovalKey {
     approval_id: u64,
 }
 
+/// Storage keys for co-signing: the rule per subscription, and approvals
+/// waiting for the guardian's countersignature by (sub_id, approval_id).
+#[contracttype]
+#[derive(Clone)]
+enum CosignKey {
+    Rule(u64),
+    Pending(u64, u64),
+}
+
 /// Storage key for executor: sub_id
 #[contracttype]
 #[derive(Clone)]
@@ -33,238 +179,5062 @@ struct ExecutorKey {
     sub_id: u64,
 }
 
-/// Renewal approval bound to subscription, amount, and expiration
+/// Per-subscription storage keys. Enum variants keep these distinct from
+/// single-field struct keys like `ExecutorKey`, which encode the same way.
+#[contracttype]
+#[derive(Clone)]
+enum SubKey {
+    Frozen(u64),
+    Schedule(u64),
+    Pricing(u64),
+    UsageBudget(u64),
+    Usage(u64),
+    Overage(u64),
+    CrankedDue(u64),
+    JointOwners(u64),
+    Paused(u64),
+    RecoveryGuardian(u64),
+    Recovery(u64),
+    RetryPolicy(u64),
+    Template(u64),
+    OfferExpiry(u64),
+    PriceChange(u64),
+    LastNotice(u64),
+    PlanMigration(u64),
+    Economics(u64),
+    PaymentMode(u64),
+    ScheduleStart(u64),
+    LastEventLedger(u64),
+    PauseUsage(u64),
+    LimitedPause(u64),
+    /// `renewal_seq` of the cycle the owner last retried early in.
+    CooldownBypass(u64),
+    RetrySubsidy(u64),
+    Labels(u64),
+    StartLedger(u64),
+    MaxCycles(u64),
+    Installments(u64),
+    /// Due date of the last cycle a charge preview was published for.
+    PreviewedDue(u64),
+    /// Owner-set `NotificationPrefs`; absent means the defaults.
+    NotificationPrefs(u64),
+    /// `renew_idempotent`'s arguments and result for (sub_id, caller, key),
+    /// in temporary storage.
+    Idempotency(u64, Address, BytesN<32>),
+    /// `ApprovalUsage` of (sub_id, approval_id), once a renewal reserved it.
+    ApprovalUsage(u64, u64),
+    /// Health window the subscription last counted a failure against its
+    /// merchant in.
+    HealthFailure(u64),
+}
+
+/// Storage key for merchant subscription templates.
+#[contracttype]
+#[derive(Clone)]
+enum TemplateKey {
+    Terms(u64),
+    SubscriberCount(u64),
+    Subscriber(u64, u32),
+}
+
+/// Per-merchant settings.
+#[contracttype]
+#[derive(Clone)]
+enum MerchantKey {
+    EarlyDiscount(Address),
+    PrepayDiscount(Address),
+    /// Undeliverable payouts held for (merchant, token).
+    Claimable(Address, Address),
+    WebhookKey(Address),
+    PauseLimits(Address),
+    /// Subscriptions of the merchant an owner has paused under its limits.
+    OwnerPauses(Address, Address),
+    PricingContract(Address),
+    /// Ledgers before each due date `emit_charge_preview` opens.
+    PreviewLead(Address),
+    /// Set while the merchant only takes subscribers on its allowlist.
+    AllowlistOnly(Address),
+    /// (merchant, owner) on the merchant's allowlist.
+    Allowlisted(Address, Address),
+    MaxOwnerSubs(Address),
+    /// Subscriptions of the merchant an owner holds that haven't failed or
+    /// completed.
+    OwnerSubs(Address, Address),
+    /// Renewal outcomes in one `HEALTH_WINDOW_LEDGERS` window.
+    Health(Address, u32),
+    ReverifyBy(Address),
+}
+
+/// Storage keys for volume circuit limits and their running totals, either
+/// protocol-wide or for one token.
+#[contracttype]
+#[derive(Clone)]
+enum VolumeKey {
+    GlobalLimit,
+    GlobalUsage,
+    TokenLimit(Address),
+    TokenUsage(Address),
+}
+
+/// Storage key for token balances the contract holds on behalf of users
+/// (treasury, escrow); these are never rescuable.
+#[contracttype]
+#[derive(Clone)]
+enum TrackedKey {
+    Balance(Address),
+}
+
+/// Storage key for token metadata cached from the token contract.
+#[contracttype]
+#[derive(Clone)]
+enum TokenKey {
+    Decimals(Address),
+}
+
+/// Storage key for crank commitments, by commitment hash.
+#[contracttype]
+#[derive(Clone)]
+enum CrankCommitKey {
+    Commitment(BytesN<32>),
+}
+
+/// A sealed claim to crank, revealed in a later ledger.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct RenewalApproval {
-    pub sub_id: u64,
-    pub max_spend: i128,
-    pub expires_at: u32,
-    pub used: bool,
+pub struct CrankCommitment {
+    pub committer: Address,
+    pub ledger: u32,
 }
 
-/// Represents the current state of a subscription
+/// Storage key for registered keepers.
 #[contracttype]
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum SubscriptionState {
-    Active,
-    Retrying,
-    Failed,
+#[derive(Clone)]
+enum KeeperKey {
+    Keeper(Address),
+}
+
+/// Storage key for prepaid account credit of an owner in one token.
+/// `Promo` credit is keyed by (merchant, owner, token) and only pays for
+/// that merchant's subscriptions.
+#[contracttype]
+#[derive(Clone)]
+enum CreditKey {
+    Balance(Address, Address),
+    Promo(Address, Address, Address),
+}
+
+/// Storage key for an owner's spending in (owner, token, window).
+#[contracttype]
+#[derive(Clone)]
+enum SpendingKey {
+    Window(Address, Address, u32),
 }
 
-/// Core subscription data stored on-chain
+/// Storage keys for a merchant's settlement periods: the period's summary,
+/// and its receipts by (merchant, period, index).
+#[contracttype]
+#[derive(Clone)]
+enum SettlementKey {
+    Period(Address, u32),
+    Receipt(Address, u32, u32),
+}
+
+/// A proposed token rescue awaiting its timelock.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct SubscriptionData {
-    pub owner: Address,
-    pub state: SubscriptionState,
-    pub failure_count: u32,
-    pub last_attempt_ledger: u32,
+pub struct PendingRescue {
+    pub token: Address,
+    pub to: Address,
+    pub amount: i128,
+    pub executable_at: u32,
 }
 
-/// Events for subscription renewal tracking
-#[contractevent]
-pub struct RenewalSuccess {
-    pub sub_id: u64,
-    pub owner: Address,
+/// Guardian allowed to recover a subscription after `delay_ledgers`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryGuardian {
+    pub guardian: Address,
+    pub delay_ledgers: u32,
 }
 
-#[contractevent]
-pub struct RenewalFailed {
-    pub sub_id: u64,
-    pub failure_count: u32,
-    pub ledger: u32,
+/// A requested owner rotation awaiting its delay.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingRecovery {
+    pub new_owner: Address,
+    pub executable_at: u32,
 }
 
-#[contractevent]
-pub struct StateTransition {
-    pub sub_id: u64,
-    pub new_state: SubscriptionState,
+/// Maximum value processed within `window_ledgers`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VolumeLimit {
+    pub max_amount: i128,
+    pub window_ledgers: u32,
+}
+
+/// Value processed in the window starting at `window_start`. A new window
+/// starts with the first renewal after the previous one has elapsed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VolumeUsage {
+    pub window_start: u32,
+    pub total: i128,
+}
+
+/// Merchant-funded budget, held in the subscription's token, that
+/// reimburses keepers `per_retry` for each retry they make.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetrySubsidy {
+    pub per_retry: i128,
+    pub remaining: i128,
+    /// `renewal_seq` of the cycle the last subsidised retry was in.
+    pub paid_cycle: u64,
+    /// `failure_count` the last subsidised retry followed; 0 if none was.
+    pub paid_retry: u32,
+}
+
+/// Bounds a merchant puts on owners pausing its subscriptions.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PauseLimits {
+    /// Ledgers one subscription may spend paused per
+    /// `PAUSE_QUOTA_WINDOW_LEDGERS`.
+    pub max_paused_ledgers: u32,
+    /// Subscriptions one owner may have paused with the merchant at once.
+    pub max_concurrent: u32,
+}
+
+/// Paused time a subscription used in the quota window starting at
+/// `window_start`, which opens with the first pause after the previous
+/// window elapsed.
+#[contracttype]
+#[derive(Clone)]
+struct PauseUsage {
+    window_start: u32,
+    paused_ledgers: u32,
+}
+
+/// Renewals of a merchant's subscriptions in one health window.
+#[contracttype]
+#[derive(Clone, Default)]
+struct HealthCounts {
+    attempts: u32,
+    failures: u32,
+}
+
+/// A pause counted against the merchant's `PauseLimits`.
+#[contracttype]
+#[derive(Clone)]
+struct LimitedPause {
+    started: u32,
+    /// Owner whose concurrent pauses it counts toward.
+    owner: Address,
+    /// Last ledger within the allowance; the merchant may end it after.
+    resume_by: u32,
+}
+
+/// Approvals with `max_spend` above `min_amount` stay pending until
+/// `guardian` countersigns them.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CosignRule {
+    pub guardian: Address,
+    pub min_amount: i128,
+}
+
+/// A registered keeper and its track record. Active keepers may call
+/// `renew` for any subscription; approvals still bound what is charged.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Keeper {
+    pub suspended: bool,
+    /// Renewals that went through.
+    pub successes: u64,
+    /// Renewal calls that returned false (missed cycle).
+    pub wasted: u64,
+    /// Keeper fees earned and not yet claimed, by token.
+    pub rewards: Map<Address, i128>,
+}
+
+/// Protocol fee configuration. `percentage` is expressed in basis points.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfig {
+    pub percentage: u32,
+    pub recipient: Address,
+}
+
+/// A merchant's renewal record over the current and previous
+/// `HEALTH_WINDOW_LEDGERS`, from `get_merchant_health`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantHealth {
+    /// Charged renewal attempts, failed or not. Each subscription counts
+    /// at most one failed attempt per window.
+    pub attempts: u32,
+    pub failures: u32,
+    /// `failures / attempts` in basis points; 0 without attempts.
+    pub failure_bps: u32,
+    /// Deadline to re-verify, once the failure rate reached
+    /// `FAILURE_ALERT_BPS`; renewals stop after it.
+    pub reverify_by: Option<u32>,
+}
+
+/// What a privileged call would change, from its `simulate_*` dry run:
+/// the `action`, `old_value` and `new_value` its `AdminAction` event would
+/// carry.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminEffect {
+    pub action: Symbol,
+    pub old_value: Bytes,
+    pub new_value: Bytes,
+}
+
+/// Approvals granted by `subscribe_and_approve`: `cycles` approvals of
+/// `max_spend` each, with consecutive ids from `approval_id`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApprovalTerms {
+    pub approval_id: u64,
+    pub max_spend: i128,
+    pub expires_at: u32,
+    pub cycles: u32,
 }
 
-#[contractevent]
-pub struct PauseToggled {
-    pub paused: bool,
+/// A first cycle charged when a subscription is created, by `init_sub` or
+/// `subscribe_from_template`: approval `approval_id` is granted for
+/// `amount` and spent in the same call.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FirstCharge {
+    pub approval_id: u64,
+    pub amount: i128,
+}
+
+/// How renewals have used an approval, from `get_approval_usage`. A
+/// failed renewal hands its reservation back, so an approval can be
+/// reserved several times before one renewal spends it.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ApprovalUsage {
+    /// Renewals that reserved it, failed or not.
+    pub attempts: u32,
+    /// Reservations released because the renewal failed.
+    pub failures: u32,
+    /// What the renewal that spent it charged.
+    pub charged: i128,
+    /// Part of `max_spend` that renewal left unused; it lapses with the
+    /// approval.
+    pub unspent: i128,
+    /// What it can still authorize: its `max_spend` until it is used or
+    /// expires, then 0.
+    pub remaining: i128,
 }
 
-#[contractevent]
-pub struct ApprovalCreated {
+/// One approval to create with `approve_renewals`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApprovalRequest {
     pub sub_id: u64,
     pub approval_id: u64,
     pub max_spend: i128,
     pub expires_at: u32,
 }
 
-#[contractevent]
-pub struct ApprovalRejected {
+/// What `approve_renewals` did with one request.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ApprovalResult {
+    Approved,
+    /// Parked for the co-sign guardian, as with `approve_renewal`.
+    AwaitingGuardian,
+    NotFound,
+    /// The subscription belongs to someone else.
+    NotOwner,
+    /// Joint subscriptions need `approve_renewal_joint`.
+    JointlyOwned,
+    Frozen,
+}
+
+/// Arguments of `approve_renewal`, for `multicall`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApproveOp {
     pub sub_id: u64,
     pub approval_id: u64,
-    pub reason: u32, // 1=expired, 2=used, 3=amount_exceeded, 4=not_found
+    pub max_spend: i128,
+    pub expires_at: u32,
 }
 
-#[contractevent]
-pub struct ExecutorAssigned {
+/// Arguments of `renew`, for `multicall` (where the batch caller renews)
+/// and `renew_idempotent`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RenewOp {
     pub sub_id: u64,
-    pub executor: Address,
+    pub approval_id: u64,
+    pub amount: i128,
+    pub max_retries: u32,
+    pub cooldown_ledgers: u32,
+    pub succeed: bool,
+}
+
+/// One step of a `multicall`, each mapping to the entry point of the same
+/// name.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UserOp {
+    Approve(ApproveOp),
+    Renew(RenewOp),
+    /// `(sub_id, cycles, amount_per_cycle)`
+    PrepayCycles(u64, u32, i128),
+    SetExecutor(u64, Address),
+    RemoveExecutor(u64),
+}
+
+/// How `max_retries` passed to `renew` is interpreted.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetryCounting {
+    /// Retries after the first attempt: `max_retries = 2` fails the
+    /// subscription on the third failure. The default.
+    Retries,
+    /// Total failed attempts: `max_retries = 2` fails the subscription on
+    /// the second failure.
+    Attempts,
+}
+
+impl RetryCounting {
+    /// Whether `failure_count` failures exhaust a budget of `max_retries`.
+    pub fn exhausted(self, failure_count: u32, max_retries: u32) -> bool {
+        match self {
+            RetryCounting::Retries => failure_count > max_retries,
+            RetryCounting::Attempts => failure_count >= max_retries,
+        }
+    }
+}
+
+/// Billing schedule of a subscription; unscheduled subscriptions renew on
+/// demand. A renewal for the cycle due at
+/// `next_due` is accepted within `[next_due, next_due + window_ledgers]`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+    pub next_due: u32,
+    pub interval_ledgers: u32,
+    pub window_ledgers: u32,
+    /// Cycles whose window closed without a renewal.
+    pub missed_cycles: u32,
+    /// Cycles already paid for by `prepay_cycles`.
+    pub prepaid_cycles: u32,
+}
+
+/// Retry policy fixed by a template; `renew` uses it in place of the
+/// `max_retries` and `cooldown_ledgers` it is called with. Keepers and
+/// cranks fall back to `DEFAULT_RETRY_POLICY`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub cooldown_ledgers: u32,
+}
+
+/// Full terms of a subscription, defined once by the merchant. The first
+/// cycle falls due `trial_ledgers` after subscribing.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionTemplate {
+    pub merchant: Address,
+    pub token: Address,
+    pub pricing: Pricing,
+    pub interval_ledgers: u32,
+    pub window_ledgers: u32,
+    pub trial_ledgers: u32,
+    pub retry: RetryPolicy,
+}
+
+/// A scheduled move of a subscription to another template (plan).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanMigration {
+    pub template_id: u64,
+    pub effective_at: u32,
+}
+
+/// A merchant-scheduled price change, taking effect at `effective_at`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceChange {
+    pub pricing: Pricing,
+    pub effective_at: u32,
+}
+
+/// Something queued against a subscription, from `get_pending_changes`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PendingChange {
+    /// Billing starts at this ledger.
+    Start(u32),
+    PriceChange(PriceChange),
+    PlanMigration(PlanMigration),
+    /// Ownership moves by recovery once `executable_at` passes and it is
+    /// executed.
+    Transfer(PendingRecovery),
+}
+
+/// Price of one cycle: `unit_price * quantity` (seats, units, ...).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pricing {
+    pub unit_price: i128,
+    pub quantity: u32,
+}
+
+impl Pricing {
+    pub fn amount(&self) -> i128 {
+        self.unit_price
+            .checked_mul(self.quantity as i128)
+            .expect("Amount overflow")
+    }
+}
+
+/// Metered usage allowance: usage beyond `included_usage` in a cycle is
+/// billed at `overage_price` per unit on top of the base amount.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OveragePricing {
+    pub included_usage: u64,
+    pub overage_price: i128,
+}
+
+/// Co-owners of a joint subscription. Creating approvals and changing the
+/// owner set take `threshold` of them; any one can pause the subscription.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JointOwners {
+    pub owners: Vec<Address>,
+    pub threshold: u32,
+}
+
+/// Promotional credit a merchant granted an owner, usable until
+/// `expires_at` (inclusive).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PromoCredit {
+    pub amount: i128,
+    pub expires_at: u32,
+}
+
+/// Running totals of where a subscription's payments went. The merchant
+/// keeps `charged - protocol_fees - keeper_fees - refunded`; fees are only
+/// taken from payments the contract collects, never from External ones.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SubEconomics {
+    /// Renewals and prepayments, including the part paid from credit.
+    pub charged: i128,
+    pub credit_applied: i128,
+    /// Protocol fees paid to the fee recipient, at the fee in force at
+    /// each charge.
+    pub protocol_fees: i128,
+    /// Crank bounties paid out for this subscription.
+    pub keeper_fees: i128,
+    pub refunded: i128,
+}
+
+/// What the owner wants to hear about a subscription, from
+/// `get_notification_prefs`. The contract only publishes the notices and
+/// alerts these allow; the webhook bridge and wallets deliver the rest of
+/// what they read here.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NotificationPrefs {
+    /// `UpcomingChange` notices and charge previews.
+    pub reminders: bool,
+    /// Lowest usage threshold, in percent of the budget, that raises a
+    /// `UsageThresholdReached` alert; above 100 silences them.
+    pub usage_alert_pct: u32,
+    /// Charge previews below this amount are skipped.
+    pub preview_min_amount: i128,
+}
+
+/// A fixed total price paid off over a subscription's `max_cycles`, from
+/// `get_installment_plan`. `paid` counts installments, before any early
+/// discount and without overage.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentPlan {
+    pub total_price: i128,
+    pub paid: i128,
+}
+
+/// What an owner was charged in one token during one spending window,
+/// before refunds, from `get_owner_spending`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OwnerSpending {
+    pub window: u32,
+    pub total: i128,
+    pub by_merchant: Map<Address, i128>,
 }
 
-#[contractevent]
-pub struct ExecutorRemoved {
+/// One successful renewal as booked for the merchant's settlement.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementReceipt {
     pub sub_id: u64,
+    pub renewal_seq: u64,
+    pub ledger: u32,
+    pub token: Address,
+    pub charged: i128,
+    pub credit_applied: i128,
+}
+
+/// A merchant's settlement period so far. `hash` chains the period's
+/// receipts in order: starting from 32 zero bytes, each receipt sets it to
+/// sha256 over the previous hash and the receipt's XDR.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementPeriod {
+    pub count: u32,
+    pub hash: BytesN<32>,
+}
+
+/// Where `export_settlement` reads from: a settlement period and the index
+/// of its first receipt to return.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementCursor {
+    pub period: u32,
+    pub index: u32,
+}
+
+/// A page of a merchant's settlement period, from `export_settlement`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementExport {
+    /// The page's `SettlementReceipt`s, each as XDR, back to back.
+    pub receipts: Bytes,
+    /// Receipts in `receipts`.
+    pub count: u32,
+    /// Index the next page of the period starts at; `None` once the
+    /// period is exhausted.
+    pub next_index: Option<u32>,
+    /// The whole period as stored on-chain, to check the export against.
+    pub period: SettlementPeriod,
+}
+
+/// Where a subscription's renewal events stand, from
+/// `get_last_event_seq`. A client that has seen `renewal_seq` and scanned
+/// up to `ledger` has missed nothing.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EventCursor {
+    /// `renewal_seq` of the latest `RenewalSuccess`; 0 before the first.
+    pub renewal_seq: u64,
+    /// Ledger of the latest renewal success, failure or missed cycle; 0
+    /// if there was none.
+    pub ledger: u32,
 }
 
-#[contract]
-pub struct SubscriptionRenewalContract;
+/// Whether the owner's wallet can pay the next charge, from
+/// `check_payment_readiness`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentReadiness {
+    /// The owner has a balance entry (trustline, for Stellar assets).
+    pub trustline: bool,
+    /// The issuer hasn't deauthorized the owner. Tokens without
+    /// authorization flags count as authorized once the trustline exists.
+    pub authorized: bool,
+    pub balance: i128,
+    /// Price of the next cycle; 0 for subscriptions without pricing.
+    pub next_charge: i128,
+    /// Promo and account credit that would pay first.
+    pub credit: i128,
+    /// Trustline, authorization and balance all cover the next charge.
+    pub ready: bool,
+}
+
+/// Breakdown of one charge, as reported in the receipt.
+struct Charge {
+    unit_price: i128,
+    quantity: u32,
+    discount: i128,
+    overage_units: u64,
+    overage: i128,
+    charged: i128,
+    credit_applied: i128,
+}
+
+/// `SubscriptionData` as stored before layouts were versioned. Read-only:
+/// entries are upgraded on load and written back in the current layout.
+#[contracttype]
+#[derive(Clone)]
+struct SubscriptionDataV0 {
+    owner: Address,
+    merchant: Address,
+    token: Address,
+    state: SubscriptionState,
+    failure_count: u32,
+    last_attempt_ledger: u32,
+    renewal_seq: u64,
+}
+
+impl From<SubscriptionDataV0> for SubscriptionData {
+    fn from(old: SubscriptionDataV0) -> Self {
+        SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            owner: old.owner,
+            merchant: old.merchant,
+            token: old.token,
+            state: old.state,
+            failure_count: old.failure_count,
+            last_attempt_ledger: old.last_attempt_ledger,
+            renewal_seq: old.renewal_seq,
+        }
+    }
+}
+
+/// Whoever gets a cut of a renewal charge ahead of the merchant, and the
+/// most they get.
+enum Cut<'a> {
+    /// Paid to a permissionless cranker right away.
+    Crank(&'a Address, i128),
+    /// Held for a registered keeper until it claims it.
+    Keeper(&'a Address, i128),
+}
+
+#[contract]
+pub struct SubscriptionRenewalContract;
+
+#[contractimpl]
+impl SubscriptionRenewalContract {
+    // ── Admin / Pause management ──────────────────────────────────
+
+    /// Initialize the contract admin. Can only be called once.
+    pub fn init(env: Env, admin: Address) {
+        if env.storage().instance().has(&ContractKey::Admin) {
+            panic!("Already initialized");
+        }
+        env.storage().instance().set(&ContractKey::Admin, &admin);
+        env.storage().instance().set(&ContractKey::Paused, &false);
+    }
+
+    /// Crate version of the deployed build, e.g. `"0.0.1"`.
+    pub fn get_version(env: Env) -> String {
+        String::from_str(&env, env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Whether this deployment implements `interface_id`: the contract's
+    /// `INTERFACE_ID` or one of its `FEATURES`.
+    pub fn supports(env: Env, interface_id: Symbol) -> bool {
+        core::iter::once(INTERFACE_ID)
+            .chain(FEATURES)
+            .any(|id| Symbol::new(&env, id) == interface_id)
+    }
+
+    /// Internal helper – loads admin, calls `require_auth` and returns it.
+    fn require_admin(env: &Env) -> Address {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&ContractKey::Admin)
+            .expect("Contract not initialized");
+        admin.require_auth();
+        admin
+    }
+
+    /// Internal helper – publishes the `AdminAction` audit event.
+    fn admin_action<O, N>(env: &Env, action: Symbol, admin: Address, old_value: O, new_value: N)
+    where
+        O: IntoVal<Env, Val>,
+        N: IntoVal<Env, Val>,
+    {
+        let effect = Self::admin_effect(env, action, old_value, new_value);
+        AdminAction {
+            action: effect.action,
+            admin,
+            old_value: effect.old_value,
+            new_value: effect.new_value,
+        }
+        .publish(env);
+    }
+
+    /// Internal helper – encodes a setting's change as `AdminAction` does.
+    fn admin_effect<O, N>(env: &Env, action: Symbol, old_value: O, new_value: N) -> AdminEffect
+    where
+        O: IntoVal<Env, Val>,
+        N: IntoVal<Env, Val>,
+    {
+        let old_value: Val = old_value.into_val(env);
+        let new_value: Val = new_value.into_val(env);
+        AdminEffect {
+            action,
+            old_value: old_value.to_xdr(env),
+            new_value: new_value.to_xdr(env),
+        }
+    }
+
+    /// Pause or unpause all renewal execution. Admin only.
+    pub fn set_paused(env: Env, paused: bool) {
+        let admin = Self::require_admin(&env);
+        let old = Self::is_paused(env.clone());
+        env.storage().instance().set(&ContractKey::Paused, &paused);
+        PauseToggled { paused }.publish(&env);
+        Self::admin_action(&env, symbol_short!("pause"), admin, old, paused);
+    }
+
+    /// Freeze or unfreeze every token movement out of the contract during
+    /// an incident: payouts are held as claimable, and claims, refunds to
+    /// wallets, subsidy and promo withdrawals and rescues are rejected.
+    /// Subscriptions can still be managed and queried. Admin only.
+    pub fn set_withdrawals_frozen(env: Env, frozen: bool) {
+        let admin = Self::require_admin(&env);
+        let old = Self::is_withdrawals_frozen(env.clone());
+        env.storage()
+            .instance()
+            .set(&ContractKey::WithdrawalsFrozen, &frozen);
+        WithdrawalsFrozen { frozen }.publish(&env);
+        Self::admin_action(&env, symbol_short!("wd_freeze"), admin, old, frozen);
+    }
+
+    /// Whether token movements out of the contract are frozen.
+    pub fn is_withdrawals_frozen(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&ContractKey::WithdrawalsFrozen)
+            .unwrap_or(false)
+    }
+
+    /// Internal helper – aborts while withdrawals are frozen.
+    fn require_withdrawals_open(env: &Env) {
+        if Self::is_withdrawals_frozen(env.clone()) {
+            panic!("Withdrawals are frozen");
+        }
+    }
+
+    /// Query the current pause state.
+    pub fn is_paused(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&ContractKey::Paused)
+            .unwrap_or(false)
+    }
+
+    /// Emit informational events (`TtlExtended`, `UsageThresholdReached`)
+    /// or only the lifecycle ones. On by default. Admin only.
+    pub fn set_verbose_events(env: Env, enabled: bool) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_verbose_events(env.clone());
+        env.storage()
+            .instance()
+            .set(&ContractKey::VerboseEvents, &enabled);
+        Self::admin_action(&env, symbol_short!("verbose"), admin, old, enabled);
+    }
+
+    /// Whether informational events are emitted.
+    pub fn get_verbose_events(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&ContractKey::VerboseEvents)
+            .unwrap_or(true)
+    }
+
+    // ── Volume circuit limits ─────────────────────────────────────
+
+    /// Set or clear the volume limit for `token`, or the protocol-wide limit
+    /// when `token` is `None`. Admin only.
+    pub fn set_volume_limit(env: Env, token: Option<Address>, limit: Option<VolumeLimit>) {
+        let admin = Self::require_admin(&env);
+        if let Some(limit) = &limit {
+            if limit.max_amount < 0 || limit.window_ledgers == 0 {
+                panic!("Invalid volume limit");
+            }
+            if let Some(token) = &token {
+                Self::require_sane_amount(&env, token, limit.max_amount);
+            }
+        }
+
+        let key = Self::volume_key(token.clone(), true);
+        let old = Self::get_volume_limit(env.clone(), token);
+        match &limit {
+            Some(limit) => env.storage().instance().set(&key, limit),
+            None => env.storage().instance().remove(&key),
+        }
+        Self::admin_action(&env, symbol_short!("volume"), admin, old, limit);
+    }
+
+    /// Get the volume limit for `token`, or the protocol-wide one.
+    pub fn get_volume_limit(env: Env, token: Option<Address>) -> Option<VolumeLimit> {
+        env.storage().instance().get(&Self::volume_key(token, true))
+    }
+
+    /// Get the running total for `token`, or the protocol-wide one.
+    pub fn get_volume_usage(env: Env, token: Option<Address>) -> Option<VolumeUsage> {
+        env.storage()
+            .instance()
+            .get(&Self::volume_key(token, false))
+    }
+
+    fn volume_key(token: Option<Address>, limit: bool) -> VolumeKey {
+        match (token, limit) {
+            (None, true) => VolumeKey::GlobalLimit,
+            (None, false) => VolumeKey::GlobalUsage,
+            (Some(token), true) => VolumeKey::TokenLimit(token),
+            (Some(token), false) => VolumeKey::TokenUsage(token),
+        }
+    }
+
+    /// Internal helper – adds `amount` to the window of one limit. Aborts
+    /// with `Error::VolumeLimitExceeded` if it would go over.
+    fn record_volume_for(env: &Env, token: Option<Address>, amount: i128) {
+        let limit = match Self::get_volume_limit(env.clone(), token.clone()) {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let now = env.ledger().sequence();
+        let mut usage = Self::get_volume_usage(env.clone(), token.clone())
+            .filter(|usage| now < usage.window_start.saturating_add(limit.window_ledgers))
+            .unwrap_or(VolumeUsage {
+                window_start: now,
+                total: 0,
+            });
+
+        usage.total = match usage.total.checked_add(amount) {
+            Some(total) if total <= limit.max_amount => total,
+            _ => panic_with_error!(env, Error::VolumeLimitExceeded),
+        };
+        env.storage()
+            .instance()
+            .set(&Self::volume_key(token, false), &usage);
+    }
+
+    /// Internal helper – records a processed payment against the token and
+    /// protocol-wide limits.
+    fn record_volume(env: &Env, token: &Address, amount: i128) {
+        Self::record_volume_for(env, Some(token.clone()), amount);
+        Self::record_volume_for(env, None, amount);
+    }
+
+    /// Internal helper – room left under the token and protocol-wide
+    /// limits in their current windows, whichever is tighter.
+    fn volume_headroom(env: &Env, token: &Address) -> Option<i128> {
+        let now = env.ledger().sequence();
+        [Some(token.clone()), None]
+            .into_iter()
+            .filter_map(|token| {
+                let limit = Self::get_volume_limit(env.clone(), token.clone())?;
+                let used = Self::get_volume_usage(env.clone(), token)
+                    .filter(|usage| now < usage.window_start.saturating_add(limit.window_ledgers))
+                    .map_or(0, |usage| usage.total);
+                Some(limit.max_amount - used)
+            })
+            .min()
+    }
+
+    // ── Token amounts ─────────────────────────────────────────────
+
+    /// The token's `decimals()`, queried once and cached.
+    pub fn get_token_decimals(env: Env, token: Address) -> u32 {
+        let key = TokenKey::Decimals(token.clone());
+        if let Some(decimals) = env.storage().persistent().get(&key) {
+            return decimals;
+        }
+        let decimals = token::Client::new(&env, &token).decimals();
+        env.storage().persistent().set(&key, &decimals);
+        decimals
+    }
+
+    /// Largest amount, in base units, a price, cap or limit in `token` may
+    /// be configured at: `MAX_WHOLE_TOKENS` scaled by its decimals.
+    pub fn get_max_amount(env: Env, token: Address) -> i128 {
+        let decimals = Self::get_token_decimals(env, token);
+        10i128
+            .checked_pow(decimals)
+            .and_then(|unit| unit.checked_mul(MAX_WHOLE_TOKENS))
+            .unwrap_or(i128::MAX)
+    }
+
+    /// Internal helper – aborts if `amount` is beyond what can sensibly be
+    /// configured in `token`.
+    fn require_sane_amount(env: &Env, token: &Address, amount: i128) {
+        if amount > Self::get_max_amount(env.clone(), token.clone()) {
+            panic!("Amount out of range for token");
+        }
+    }
+
+    // ── Account credit ────────────────────────────────────────────
+
+    /// Internal helper – adjusts the balance of `token` held for users.
+    fn adjust_tracked_balance(env: &Env, token: &Address, delta: i128) {
+        let key = TrackedKey::Balance(token.clone());
+        let balance: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(balance + delta));
+    }
+
+    /// Grant `owner` `amount` of credit in `token`, funded by `granter`
+    /// (typically a merchant or the protocol treasury). The tokens are held
+    /// by the contract until renewals consume the credit.
+    pub fn grant_credit(env: Env, granter: Address, owner: Address, token: Address, amount: i128) {
+        granter.require_auth();
+        if amount <= 0 {
+            panic!("Credit amount must be positive");
+        }
+        Self::deposit_credit(&env, granter, owner, token, amount);
+    }
+
+    /// Internal helper – moves `amount` of `token` from `granter` into the
+    /// contract and credits it to `owner`.
+    fn deposit_credit(env: &Env, granter: Address, owner: Address, token: Address, amount: i128) {
+        token::Client::new(env, &token).transfer(&granter, env.current_contract_address(), &amount);
+        Self::adjust_tracked_balance(env, &token, amount);
+
+        let balance = Self::get_credit(env.clone(), owner.clone(), token.clone()) + amount;
+        env.storage()
+            .persistent()
+            .set(&CreditKey::Balance(owner.clone(), token.clone()), &balance);
+
+        CreditGranted {
+            owner,
+            token,
+            granter,
+            amount,
+            balance,
+        }
+        .publish(env);
+    }
+
+    /// Refund `amount` of a subscription's charges to its owner. With
+    /// `to_credit` the refund lands in the owner's credit balance, where the
+    /// next renewal spends it, instead of going back to their wallet.
+    pub fn refund(env: Env, sub_id: u64, amount: i128, to_credit: bool) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.merchant.require_auth();
+        if amount <= 0 {
+            panic!("Refund amount must be positive");
+        }
+
+        if to_credit {
+            Self::deposit_credit(
+                &env,
+                data.merchant.clone(),
+                data.owner.clone(),
+                data.token.clone(),
+                amount,
+            );
+        } else {
+            Self::require_withdrawals_open(&env);
+            token::Client::new(&env, &data.token).transfer(&data.merchant, &data.owner, &amount);
+        }
+
+        Self::update_economics(&env, sub_id, |economics| economics.refunded += amount);
+        Refunded {
+            sub_id,
+            owner: data.owner,
+            amount,
+            to_credit,
+        }
+        .publish(&env);
+    }
+
+    /// Credit `owner` holds in `token`.
+    pub fn get_credit(env: Env, owner: Address, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&CreditKey::Balance(owner, token))
+            .unwrap_or(0)
+    }
+
+    /// Internal helper – pays up to `amount` of a renewal from the owner's
+    /// credit; the held tokens stay in the contract until the charge is
+    /// paid out. Returns the amount paid.
+    fn apply_credit(env: &Env, sub_id: u64, data: &SubscriptionData, amount: i128) -> i128 {
+        let credit = Self::get_credit(env.clone(), data.owner.clone(), data.token.clone());
+        let applied = credit.min(amount);
+        if applied <= 0 {
+            return 0;
+        }
+
+        let balance = credit - applied;
+        env.storage().persistent().set(
+            &CreditKey::Balance(data.owner.clone(), data.token.clone()),
+            &balance,
+        );
+
+        CreditApplied {
+            owner: data.owner.clone(),
+            token: data.token.clone(),
+            sub_id,
+            amount: applied,
+            balance,
+        }
+        .publish(env);
+        applied
+    }
+
+    /// Internal helper – sends `amount` of held `token` to `merchant`. If
+    /// the merchant can't receive it (no trustline, frozen account), or
+    /// withdrawals are frozen, the payout is kept as a claimable balance
+    /// instead of failing the caller's operation.
+    fn pay_out(env: &Env, merchant: &Address, token: &Address, sub_id: u64, amount: i128) {
+        match Self::deliver(env, merchant, token, amount) {
+            None => PayoutSent {
+                merchant: merchant.clone(),
+                token: token.clone(),
+                sub_id,
+                amount,
+            }
+            .publish(env),
+            Some(claimable) => PayoutDeferred {
+                merchant: merchant.clone(),
+                token: token.clone(),
+                sub_id,
+                amount,
+                claimable,
+            }
+            .publish(env),
+        }
+    }
+
+    /// Internal helper – `deliver`s `amount` to `to`, publishing
+    /// `PayoutDeferred` if it was added to `to`'s claimable balance instead.
+    fn deliver_or_defer(env: &Env, to: &Address, token: &Address, sub_id: u64, amount: i128) {
+        if let Some(claimable) = Self::deliver(env, to, token, amount) {
+            PayoutDeferred {
+                merchant: to.clone(),
+                token: token.clone(),
+                sub_id,
+                amount,
+                claimable,
+            }
+            .publish(env);
+        }
+    }
+
+    /// Internal helper – sends `amount` of held `token` to `to`, or adds it
+    /// to `to`'s claimable balance when that fails or withdrawals are
+    /// frozen. Returns the new claimable balance if it was held.
+    fn deliver(env: &Env, to: &Address, token: &Address, amount: i128) -> Option<i128> {
+        let sent = !Self::is_withdrawals_frozen(env.clone())
+            && token::Client::new(env, token)
+                .try_transfer(&env.current_contract_address(), to, &amount)
+                .is_ok_and(|result| result.is_ok());
+        if sent {
+            Self::adjust_tracked_balance(env, token, -amount);
+            return None;
+        }
+
+        let claimable = Self::get_claimable(env.clone(), to.clone(), token.clone()) + amount;
+        env.storage().persistent().set(
+            &MerchantKey::Claimable(to.clone(), token.clone()),
+            &claimable,
+        );
+        Some(claimable)
+    }
+
+    /// Internal helper – pays out `amount` of a charge the contract holds:
+    /// the protocol fee to the fee recipient, up to the `cut` of what is
+    /// left to the cranker or keeper, and the rest to the merchant. The fee
+    /// is rounded down, so the residue stays with the merchant. Returns the
+    /// cut paid.
+    fn pay_charge(
+        env: &Env,
+        sub_id: u64,
+        data: &SubscriptionData,
+        amount: i128,
+        cut: Option<Cut>,
+    ) -> i128 {
+        if amount <= 0 {
+            return 0;
+        }
+        let mut rest = amount;
+        if let Some(config) = Self::get_fee_config(env.clone()) {
+            let (fee, merchant_share) = rounding::split_bps(amount, config.percentage);
+            if fee > 0 {
+                Self::deliver_or_defer(env, &config.recipient, &data.token, sub_id, fee);
+                Self::update_economics(env, sub_id, |economics| economics.protocol_fees += fee);
+                ProtocolFeePaid {
+                    recipient: config.recipient,
+                    token: data.token.clone(),
+                    sub_id,
+                    amount: fee,
+                }
+                .publish(env);
+            }
+            rest = merchant_share;
+        }
+        let mut paid = 0;
+        if let Some(cut) = cut {
+            let (Cut::Crank(_, most) | Cut::Keeper(_, most)) = cut;
+            paid = most.min(rest);
+            if paid > 0 {
+                match cut {
+                    Cut::Crank(cranker, _) => {
+                        Self::deliver_or_defer(env, cranker, &data.token, sub_id, paid)
+                    }
+                    Cut::Keeper(keeper, _) => {
+                        Self::accrue_keeper_reward(env, keeper, &data.token, sub_id, paid)
+                    }
+                }
+                Self::update_economics(env, sub_id, |economics| economics.keeper_fees += paid);
+                rest -= paid;
+            }
+        }
+        if rest > 0 {
+            Self::pay_out(env, &data.merchant, &data.token, sub_id, rest);
+        }
+        paid
+    }
+
+    /// Payouts held for `merchant` in `token` because delivery failed.
+    pub fn get_claimable(env: Env, merchant: Address, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::Claimable(merchant, token))
+            .unwrap_or(0)
+    }
+
+    /// Send the merchant's claimable `token` balance to `to`, e.g. once the
+    /// trustline exists or to another account. Fee recipients claim held
+    /// fees the same way. Merchant only. Returns the amount claimed.
+    pub fn claim_payout(env: Env, merchant: Address, token: Address, to: Address) -> i128 {
+        merchant.require_auth();
+        Self::require_withdrawals_open(&env);
+        let key = MerchantKey::Claimable(merchant.clone(), token.clone());
+        let amount: i128 = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Nothing to claim");
+
+        env.storage().persistent().remove(&key);
+        Self::adjust_tracked_balance(&env, &token, -amount);
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+
+        PayoutClaimed {
+            merchant,
+            token,
+            to,
+            amount,
+        }
+        .publish(&env);
+        amount
+    }
+
+    /// Grant `owner` promotional credit in `token`, funded from the
+    /// merchant's claimable balance. It only pays for renewals of this merchant's
+    /// subscriptions and lapses after `expires_at`. Granting again tops up
+    /// the balance and replaces the expiry.
+    pub fn grant_promo_credit(
+        env: Env,
+        merchant: Address,
+        owner: Address,
+        token: Address,
+        amount: i128,
+        expires_at: u32,
+    ) {
+        merchant.require_auth();
+        if amount <= 0 {
+            panic!("Credit amount must be positive");
+        }
+        if expires_at <= env.ledger().sequence() {
+            panic!("Promo credit already expired");
+        }
+
+        let key = CreditKey::Promo(merchant.clone(), owner.clone(), token.clone());
+        let mut promo = env.storage().persistent().get(&key).unwrap_or(PromoCredit {
+            amount: 0,
+            expires_at,
+        });
+        if promo.expires_at < env.ledger().sequence() {
+            panic!("Reclaim expired promo credit first");
+        }
+
+        // The credit is carved out of payouts the contract already holds
+        // for the merchant, so the tracked balance doesn't change.
+        let claimable_key = MerchantKey::Claimable(merchant.clone(), token.clone());
+        let claimable = Self::get_claimable(env.clone(), merchant.clone(), token.clone());
+        if claimable < amount {
+            panic!("Insufficient claimable balance");
+        }
+        if claimable == amount {
+            env.storage().persistent().remove(&claimable_key);
+        } else {
+            env.storage()
+                .persistent()
+                .set(&claimable_key, &(claimable - amount));
+        }
+
+        promo.amount += amount;
+        promo.expires_at = expires_at;
+        env.storage().persistent().set(&key, &promo);
+
+        PromoCreditGranted {
+            merchant,
+            owner,
+            token,
+            amount,
+            balance: promo.amount,
+            expires_at,
+        }
+        .publish(&env);
+    }
+
+    /// Promotional credit `merchant` granted `owner` in `token`, if any.
+    pub fn get_promo_credit(
+        env: Env,
+        merchant: Address,
+        owner: Address,
+        token: Address,
+    ) -> Option<PromoCredit> {
+        env.storage()
+            .persistent()
+            .get(&CreditKey::Promo(merchant, owner, token))
+    }
+
+    /// Return expired, unused promotional credit to the merchant.
+    pub fn reclaim_promo_credit(
+        env: Env,
+        merchant: Address,
+        owner: Address,
+        token: Address,
+    ) -> i128 {
+        merchant.require_auth();
+        Self::require_withdrawals_open(&env);
+        let key = CreditKey::Promo(merchant.clone(), owner.clone(), token.clone());
+        let promo: PromoCredit = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("No promo credit");
+        if promo.expires_at >= env.ledger().sequence() {
+            panic!("Promo credit not expired");
+        }
+
+        env.storage().persistent().remove(&key);
+        Self::adjust_tracked_balance(&env, &token, -promo.amount);
+        token::Client::new(&env, &token).transfer(
+            &env.current_contract_address(),
+            &merchant,
+            &promo.amount,
+        );
+
+        PromoCreditExpired {
+            merchant,
+            owner,
+            token,
+            amount: promo.amount,
+        }
+        .publish(&env);
+        promo.amount
+    }
+
+    /// Internal helper – pays up to `amount` of a renewal from unexpired
+    /// promotional credit the subscription's merchant granted its owner;
+    /// like account credit, it is paid out with the rest of the charge.
+    /// Returns the amount paid.
+    fn apply_promo_credit(env: &Env, sub_id: u64, data: &SubscriptionData, amount: i128) -> i128 {
+        let key = CreditKey::Promo(
+            data.merchant.clone(),
+            data.owner.clone(),
+            data.token.clone(),
+        );
+        let mut promo: PromoCredit = match env.storage().persistent().get(&key) {
+            Some(promo) => promo,
+            None => return 0,
+        };
+        if promo.expires_at < env.ledger().sequence() {
+            return 0;
+        }
+        let applied = promo.amount.min(amount);
+        if applied <= 0 {
+            return 0;
+        }
+
+        promo.amount -= applied;
+        if promo.amount == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &promo);
+        }
+
+        PromoCreditApplied {
+            merchant: data.merchant.clone(),
+            owner: data.owner.clone(),
+            token: data.token.clone(),
+            sub_id,
+            amount: applied,
+            balance: promo.amount,
+        }
+        .publish(env);
+        applied
+    }
+
+    // ── Token rescue ──────────────────────────────────────────────
+
+    /// Balance of `token` the contract holds on behalf of users.
+    pub fn get_tracked_balance(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&TrackedKey::Balance(token))
+            .unwrap_or(0)
+    }
+
+    /// Propose moving `amount` of `token` stranded at the contract address
+    /// to `to`. Executable with `rescue_tokens` after
+    /// `RESCUE_TIMELOCK_LEDGERS`; a new proposal replaces the old one.
+    /// Admin only.
+    pub fn propose_rescue(env: Env, token: Address, to: Address, amount: i128) {
+        let admin = Self::require_admin(&env);
+        if amount <= 0 {
+            panic!("Rescue amount must be positive");
+        }
+
+        let old = Self::get_pending_rescue(env.clone());
+        let executable_at = env.ledger().sequence() + RESCUE_TIMELOCK_LEDGERS;
+        let pending = PendingRescue {
+            token: token.clone(),
+            to: to.clone(),
+            amount,
+            executable_at,
+        };
+        env.storage()
+            .instance()
+            .set(&ContractKey::PendingRescue, &pending);
+
+        RescueProposed {
+            token,
+            to,
+            amount,
+            executable_at,
+        }
+        .publish(&env);
+        Self::admin_action(&env, symbol_short!("rescue_p"), admin, old, Some(pending));
+    }
+
+    /// Drop the pending rescue proposal. Admin only.
+    pub fn cancel_rescue(env: Env) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_pending_rescue(env.clone());
+        env.storage().instance().remove(&ContractKey::PendingRescue);
+        Self::admin_action(
+            &env,
+            symbol_short!("rescue_c"),
+            admin,
+            old,
+            None::<PendingRescue>,
+        );
+    }
+
+    /// Get the pending rescue proposal, if any.
+    pub fn get_pending_rescue(env: Env) -> Option<PendingRescue> {
+        env.storage().instance().get(&ContractKey::PendingRescue)
+    }
+
+    /// Execute the pending rescue once its timelock has elapsed. The
+    /// arguments must match the proposal, and the contract must hold at
+    /// least `amount` beyond its tracked balance of `token`. Admin only.
+    pub fn rescue_tokens(env: Env, token: Address, to: Address, amount: i128) {
+        let admin = Self::require_admin(&env);
+        Self::require_withdrawals_open(&env);
+        let pending = Self::check_rescue(&env, &token, &to, amount);
+
+        env.storage().instance().remove(&ContractKey::PendingRescue);
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+
+        TokensRescued { token, to, amount }.publish(&env);
+        Self::admin_action(
+            &env,
+            symbol_short!("rescue"),
+            admin,
+            Some(pending),
+            None::<PendingRescue>,
+        );
+    }
+
+    /// Dry run of `rescue_tokens`: makes the same checks and returns what
+    /// it would change, without moving anything.
+    pub fn simulate_rescue(env: Env, token: Address, to: Address, amount: i128) -> AdminEffect {
+        let pending = Self::check_rescue(&env, &token, &to, amount);
+        Self::admin_effect(
+            &env,
+            symbol_short!("rescue"),
+            Some(pending),
+            None::<PendingRescue>,
+        )
+    }
+
+    /// Internal helper – the pending rescue, once `rescue_tokens` may
+    /// execute it with these arguments.
+    fn check_rescue(env: &Env, token: &Address, to: &Address, amount: i128) -> PendingRescue {
+        let pending = Self::get_pending_rescue(env.clone()).expect("No rescue proposed");
+
+        if pending.token != *token || pending.to != *to || pending.amount != amount {
+            panic!("Rescue does not match proposal");
+        }
+        if env.ledger().sequence() < pending.executable_at {
+            panic!("Rescue timelock active");
+        }
+
+        let balance = token::Client::new(env, token).balance(&env.current_contract_address());
+        let stranded = balance - Self::get_tracked_balance(env.clone(), token.clone());
+        if amount > stranded {
+            panic!("Rescue exceeds untracked balance");
+        }
+        pending
+    }
+
+    // ── Compliance freeze ─────────────────────────────────────────
+
+    /// Set the compliance officer allowed to freeze subscriptions alongside
+    /// the admin. Admin only.
+    pub fn set_compliance_officer(env: Env, officer: Address) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_compliance_officer(env.clone());
+        env.storage()
+            .instance()
+            .set(&ContractKey::ComplianceOfficer, &officer);
+        Self::admin_action(&env, symbol_short!("officer"), admin, old, Some(officer));
+    }
+
+    /// Set or clear the external compliance contract consulted by `renew`.
+    /// Admin only.
+    pub fn set_compliance_contract(env: Env, address: Option<Address>) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_compliance_contract(env.clone());
+        match &address {
+            Some(address) => env
+                .storage()
+                .instance()
+                .set(&ContractKey::ComplianceContract, address),
+            None => env
+                .storage()
+                .instance()
+                .remove(&ContractKey::ComplianceContract),
+        }
+        Self::admin_action(&env, symbol_short!("screen"), admin, old, address);
+    }
+
+    /// Get the external compliance contract, if set.
+    pub fn get_compliance_contract(env: Env) -> Option<Address> {
+        env.storage()
+            .instance()
+            .get(&ContractKey::ComplianceContract)
+    }
+
+    /// Internal helper – asks the compliance contract, if any, to approve
+    /// the payment. Aborts with `Error::ComplianceRejected` if it declines.
+    fn screen_payment(env: &Env, data: &SubscriptionData, amount: i128) {
+        if let Some(address) = Self::get_compliance_contract(env.clone()) {
+            let allowed = ComplianceClient::new(env, &address).check(
+                &data.owner,
+                &data.merchant,
+                &data.token,
+                &amount,
+            );
+            if !allowed {
+                panic_with_error!(env, Error::ComplianceRejected);
+            }
+        }
+    }
+
+    /// Internal helper – checks that the owner can pay `amount` from their
+    /// wallet before the charge is attempted, so a doomed renewal fails in
+    /// simulation rather than inside the token transfer.
+    fn preflight_wallet(env: &Env, data: &SubscriptionData, amount: i128) {
+        if amount <= 0 {
+            return;
+        }
+        let balance = match token::Client::new(env, &data.token).try_balance(&data.owner) {
+            Ok(Ok(balance)) => balance,
+            _ => panic_with_error!(env, Error::TrustlineMissing),
+        };
+        if balance < amount {
+            panic_with_error!(env, Error::InsufficientBalance);
+        }
+    }
+
+    /// Choose how renewals of `sub_id` collect the wallet part of a charge.
+    /// Owner only.
+    pub fn set_payment_mode(env: Env, sub_id: u64, mode: PaymentMode) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        Self::require_not_frozen(&env, sub_id);
+
+        env.storage()
+            .persistent()
+            .set(&SubKey::PaymentMode(sub_id), &mode);
+        PaymentModeSet { sub_id, mode }.publish(&env);
+    }
+
+    /// Payment mode of `sub_id`; `External` unless the owner chose another.
+    pub fn get_payment_mode(env: Env, sub_id: u64) -> PaymentMode {
+        env.storage()
+            .persistent()
+            .get(&SubKey::PaymentMode(sub_id))
+            .unwrap_or_default()
+    }
+
+    /// Diagnose whether the next renewal of `sub_id` can be paid: trustline,
+    /// issuer authorization and balance net of credit.
+    pub fn check_payment_readiness(env: Env, sub_id: u64) -> PaymentReadiness {
+        let data = Self::get_sub(env.clone(), sub_id);
+        let next_charge = Self::get_amount(env.clone(), sub_id).unwrap_or(0);
+        Self::payment_readiness(&env, &data, next_charge)
+    }
+
+    /// Internal helper – whether credit and the owner's wallet cover
+    /// `next_charge`.
+    fn payment_readiness(
+        env: &Env,
+        data: &SubscriptionData,
+        next_charge: i128,
+    ) -> PaymentReadiness {
+        let promo = Self::get_promo_credit(
+            env.clone(),
+            data.merchant.clone(),
+            data.owner.clone(),
+            data.token.clone(),
+        )
+        .filter(|promo| promo.expires_at >= env.ledger().sequence())
+        .map_or(0, |promo| promo.amount);
+        let credit = promo + Self::get_credit(env.clone(), data.owner.clone(), data.token.clone());
+
+        let (trustline, balance) =
+            match token::Client::new(env, &data.token).try_balance(&data.owner) {
+                Ok(Ok(balance)) => (true, balance),
+                _ => (false, 0),
+            };
+        let authorized =
+            match token::StellarAssetClient::new(env, &data.token).try_authorized(&data.owner) {
+                Ok(Ok(authorized)) => authorized,
+                _ => trustline,
+            };
+        let wallet_charge = (next_charge - credit).max(0);
+
+        PaymentReadiness {
+            trustline,
+            authorized,
+            balance,
+            next_charge,
+            credit,
+            ready: wallet_charge == 0 || (trustline && authorized && balance >= wallet_charge),
+        }
+    }
+
+    /// Get the compliance officer, if set.
+    pub fn get_compliance_officer(env: Env) -> Option<Address> {
+        env.storage()
+            .instance()
+            .get(&ContractKey::ComplianceOfficer)
+    }
+
+    /// Internal helper – requires `caller` to be the admin or the compliance
+    /// officer.
+    fn require_compliance(env: &Env, caller: &Address) {
+        caller.require_auth();
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&ContractKey::Admin)
+            .expect("Contract not initialized");
+        if *caller != admin && Some(caller.clone()) != Self::get_compliance_officer(env.clone()) {
+            panic!("Unauthorized: caller must be admin or compliance officer");
+        }
+    }
+
+    /// Place a legal hold on a subscription. Frozen subscriptions reject
+    /// renewals, new approvals and executor changes, but keep all their
+    /// state so they can be unfrozen later.
+    pub fn freeze_sub(env: Env, caller: Address, sub_id: u64, reason: Symbol) {
+        Self::require_compliance(&env, &caller);
+        if !env.storage().persistent().has(&sub_id) {
+            panic!("Subscription not found");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&SubKey::Frozen(sub_id), &reason);
+        SubscriptionFrozen { sub_id, reason }.publish(&env);
+    }
+
+    /// Lift a freeze placed by `freeze_sub`.
+    pub fn unfreeze_sub(env: Env, caller: Address, sub_id: u64) {
+        Self::require_compliance(&env, &caller);
+        let key = SubKey::Frozen(sub_id);
+        if !env.storage().persistent().has(&key) {
+            panic!("Subscription is not frozen");
+        }
+
+        env.storage().persistent().remove(&key);
+        SubscriptionUnfrozen { sub_id }.publish(&env);
+    }
+
+    /// Get the freeze reason, if the subscription is frozen.
+    pub fn get_freeze(env: Env, sub_id: u64) -> Option<Symbol> {
+        env.storage().persistent().get(&SubKey::Frozen(sub_id))
+    }
+
+    /// Internal helper – panics if the subscription is frozen.
+    fn require_not_frozen(env: &Env, sub_id: u64) {
+        if env.storage().persistent().has(&SubKey::Frozen(sub_id)) {
+            panic!("Subscription is frozen");
+        }
+    }
+
+    // ── Keepers ───────────────────────────────────────────────────
+
+    /// Set the operator allowed to manage keepers alongside the admin.
+    /// Admin only.
+    pub fn set_operator(env: Env, operator: Address) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_operator(env.clone());
+        env.storage()
+            .instance()
+            .set(&ContractKey::Operator, &operator);
+        Self::admin_action(&env, symbol_short!("operator"), admin, old, Some(operator));
+    }
+
+    /// Get the operator, if set.
+    pub fn get_operator(env: Env) -> Option<Address> {
+        env.storage().instance().get(&ContractKey::Operator)
+    }
+
+    /// Internal helper – requires `caller` to be the admin or the operator.
+    fn require_operator(env: &Env, caller: &Address) {
+        caller.require_auth();
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&ContractKey::Admin)
+            .expect("Contract not initialized");
+        if *caller != admin && Some(caller.clone()) != Self::get_operator(env.clone()) {
+            panic!("Unauthorized: caller must be admin or operator");
+        }
+    }
+
+    /// Register `keeper`, allowing it to call `renew` for any subscription.
+    pub fn register_keeper(env: Env, caller: Address, keeper: Address) {
+        Self::require_operator(&env, &caller);
+        let key = KeeperKey::Keeper(keeper.clone());
+        if env.storage().persistent().has(&key) {
+            panic!("Keeper already registered");
+        }
+        env.storage().persistent().set(
+            &key,
+            &Keeper {
+                suspended: false,
+                successes: 0,
+                wasted: 0,
+                rewards: Map::new(&env),
+            },
+        );
+        KeeperRegistered { keeper }.publish(&env);
+    }
+
+    /// Suspend a misbehaving keeper, or reinstate it. Its stats are kept.
+    pub fn set_keeper_suspended(env: Env, caller: Address, keeper: Address, suspended: bool) {
+        Self::require_operator(&env, &caller);
+        let key = KeeperKey::Keeper(keeper.clone());
+        let mut record: Keeper = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Keeper not registered");
+        record.suspended = suspended;
+        env.storage().persistent().set(&key, &record);
+        KeeperSuspended {
+            keeper,
+            suspended,
+            by: caller,
+        }
+        .publish(&env);
+    }
+
+    /// Get a keeper's registration and stats.
+    pub fn get_keeper(env: Env, keeper: Address) -> Option<Keeper> {
+        env.storage().persistent().get(&KeeperKey::Keeper(keeper))
+    }
+
+    /// Internal helper – true if `caller` is a registered, unsuspended keeper.
+    fn is_active_keeper(env: &Env, caller: &Address) -> bool {
+        Self::get_keeper(env.clone(), caller.clone()).is_some_and(|keeper| !keeper.suspended)
+    }
+
+    /// Internal helper – books the outcome of a `renew` call against the
+    /// caller's keeper stats, if it is a keeper.
+    fn record_keeper_attempt(env: &Env, caller: &Address, renewed: bool) {
+        let key = KeeperKey::Keeper(caller.clone());
+        if let Some(mut keeper) = env.storage().persistent().get::<_, Keeper>(&key) {
+            if renewed {
+                keeper.successes += 1;
+            } else {
+                keeper.wasted += 1;
+            }
+            env.storage().persistent().set(&key, &keeper);
+        }
+    }
+
+    /// Pay keepers a fixed `fee` per renewal out of the collected charge,
+    /// or stop paying them with `None`. Admin only.
+    pub fn set_keeper_fee(env: Env, fee: Option<i128>) {
+        let admin = Self::require_admin(&env);
+        if fee.is_some_and(|fee| fee < 0) {
+            panic!("Invalid keeper fee");
+        }
+        let old = Self::get_keeper_fee(env.clone());
+        match fee {
+            Some(fee) => env.storage().instance().set(&ContractKey::KeeperFee, &fee),
+            None => env.storage().instance().remove(&ContractKey::KeeperFee),
+        }
+        Self::admin_action(&env, symbol_short!("keep_fee"), admin, old, fee);
+    }
+
+    /// Fee a keeper earns per renewal, if any.
+    pub fn get_keeper_fee(env: Env) -> Option<i128> {
+        env.storage().instance().get(&ContractKey::KeeperFee)
+    }
+
+    /// Internal helper – adds `amount` of held `token` to the keeper's
+    /// unclaimed rewards.
+    fn accrue_keeper_reward(
+        env: &Env,
+        keeper: &Address,
+        token: &Address,
+        sub_id: u64,
+        amount: i128,
+    ) {
+        let key = KeeperKey::Keeper(keeper.clone());
+        let mut record: Keeper = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Keeper not registered");
+        let unclaimed = record.rewards.get(token.clone()).unwrap_or(0) + amount;
+        record.rewards.set(token.clone(), unclaimed);
+        env.storage().persistent().set(&key, &record);
+        KeeperRewarded {
+            keeper: keeper.clone(),
+            token: token.clone(),
+            sub_id,
+            amount,
+            unclaimed,
+        }
+        .publish(env);
+    }
+
+    /// Send the keeper's unclaimed `token` rewards to `to`. Suspended
+    /// keepers keep what they earned. Keeper only. Returns the amount
+    /// claimed.
+    pub fn claim_keeper_rewards(env: Env, keeper: Address, token: Address, to: Address) -> i128 {
+        keeper.require_auth();
+        Self::require_withdrawals_open(&env);
+        let key = KeeperKey::Keeper(keeper.clone());
+        let mut record: Keeper = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Keeper not registered");
+        let amount = record.rewards.get(token.clone()).expect("Nothing to claim");
+
+        record.rewards.remove(token.clone());
+        env.storage().persistent().set(&key, &record);
+        Self::adjust_tracked_balance(&env, &token, -amount);
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+
+        KeeperRewardsClaimed {
+            keeper,
+            token,
+            to,
+            amount,
+        }
+        .publish(&env);
+        amount
+    }
+
+    // ── Retry policy ──────────────────────────────────────────────
+
+    /// Choose how `renew` counts `max_retries`. Admin only.
+    pub fn set_retry_counting(env: Env, counting: RetryCounting) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_retry_counting(env.clone());
+        env.storage()
+            .instance()
+            .set(&ContractKey::RetryCounting, &counting);
+        Self::admin_action(&env, symbol_short!("retries"), admin, old, counting);
+    }
+
+    /// Get how `max_retries` is counted. Defaults to `Retries`.
+    pub fn get_retry_counting(env: Env) -> RetryCounting {
+        env.storage()
+            .instance()
+            .get(&ContractKey::RetryCounting)
+            .unwrap_or(RetryCounting::Retries)
+    }
+
+    // ── Retry subsidies ───────────────────────────────────────────
+
+    /// Deposit `amount` of the subscription's token to reimburse keepers
+    /// `per_retry` for each retry after the first attempt of a cycle, so
+    /// retrying low-value subscriptions still pays. Tops up any existing
+    /// budget and sets its rate. Merchant only.
+    pub fn fund_retry_subsidy(
+        env: Env,
+        merchant: Address,
+        sub_id: u64,
+        amount: i128,
+        per_retry: i128,
+    ) {
+        merchant.require_auth();
+        let data = Self::get_sub(env.clone(), sub_id);
+        if merchant != data.merchant {
+            panic!("Unauthorized: caller must be the merchant");
+        }
+        if amount < 0 || per_retry <= 0 {
+            panic!("Invalid subsidy");
+        }
+
+        token::Client::new(&env, &data.token).transfer(
+            &merchant,
+            env.current_contract_address(),
+            &amount,
+        );
+        Self::adjust_tracked_balance(&env, &data.token, amount);
+        let mut subsidy = Self::get_retry_subsidy(env.clone(), sub_id).unwrap_or(RetrySubsidy {
+            per_retry,
+            remaining: 0,
+            paid_cycle: 0,
+            paid_retry: 0,
+        });
+        subsidy.per_retry = per_retry;
+        subsidy.remaining = subsidy
+            .remaining
+            .checked_add(amount)
+            .expect("Amount overflow");
+        env.storage()
+            .persistent()
+            .set(&SubKey::RetrySubsidy(sub_id), &subsidy);
+        RetrySubsidyFunded {
+            sub_id,
+            amount,
+            per_retry,
+            remaining: subsidy.remaining,
+        }
+        .publish(&env);
+    }
+
+    /// Take back what is left of a subscription's retry subsidy. Merchant
+    /// only. Returns the amount withdrawn.
+    pub fn withdraw_retry_subsidy(env: Env, merchant: Address, sub_id: u64) -> i128 {
+        merchant.require_auth();
+        Self::require_withdrawals_open(&env);
+        let data = Self::get_sub(env.clone(), sub_id);
+        if merchant != data.merchant {
+            panic!("Unauthorized: caller must be the merchant");
+        }
+        let subsidy = Self::get_retry_subsidy(env.clone(), sub_id).expect("No retry subsidy");
+
+        env.storage()
+            .persistent()
+            .remove(&SubKey::RetrySubsidy(sub_id));
+        Self::adjust_tracked_balance(&env, &data.token, -subsidy.remaining);
+        token::Client::new(&env, &data.token).transfer(
+            &env.current_contract_address(),
+            &merchant,
+            &subsidy.remaining,
+        );
+        subsidy.remaining
+    }
+
+    /// Get a subscription's retry subsidy, if its merchant funded one.
+    pub fn get_retry_subsidy(env: Env, sub_id: u64) -> Option<RetrySubsidy> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::RetrySubsidy(sub_id))
+    }
+
+    /// Internal helper – reimburses an active keeper for a retry out of the
+    /// subscription's subsidy, given the subscription as it was before the
+    /// attempt. Only a retry made once the keepers' cooldown has elapsed is
+    /// paid, and only once per retry of a cycle. A keeper that can't
+    /// receive the token isn't paid, and the budget is kept.
+    fn pay_retry_subsidy(env: &Env, caller: &Address, sub_id: u64, before: &SubscriptionData) {
+        if before.failure_count == 0 || !Self::is_active_keeper(env, caller) {
+            return;
+        }
+        let mut subsidy = match Self::get_retry_subsidy(env.clone(), sub_id) {
+            Some(subsidy) => subsidy,
+            None => return,
+        };
+        let cooldown = Self::get_retry_policy(env.clone(), sub_id)
+            .unwrap_or(DEFAULT_RETRY_POLICY)
+            .cooldown_ledgers;
+        if env.ledger().sequence() < before.last_attempt_ledger.saturating_add(cooldown)
+            || (subsidy.paid_cycle, subsidy.paid_retry)
+                == (before.renewal_seq, before.failure_count)
+        {
+            return;
+        }
+        let amount = subsidy.per_retry.min(subsidy.remaining);
+        if amount <= 0 || Self::is_withdrawals_frozen(env.clone()) {
+            return;
+        }
+
+        let token = before.token.clone();
+        let sent = token::Client::new(env, &token)
+            .try_transfer(&env.current_contract_address(), caller, &amount)
+            .is_ok_and(|result| result.is_ok());
+        if !sent {
+            return;
+        }
+        Self::adjust_tracked_balance(env, &token, -amount);
+        subsidy.remaining -= amount;
+        subsidy.paid_cycle = before.renewal_seq;
+        subsidy.paid_retry = before.failure_count;
+        env.storage()
+            .persistent()
+            .set(&SubKey::RetrySubsidy(sub_id), &subsidy);
+        RetrySubsidyPaid {
+            keeper: caller.clone(),
+            sub_id,
+            amount,
+            remaining: subsidy.remaining,
+        }
+        .publish(env);
+    }
+
+    // ── Subscription logic ────────────────────────────────────────
+
+    /// Initialize a subscription paying `merchant` in `token`, charging
+    /// the first cycle right away when `first_charge` is given. With a
+    /// future `start_ledger`, renewals are rejected until then. The owner
+    /// must be on the merchant's allowlist if it keeps one.
+    pub fn init_sub(
+        env: Env,
+        info: Address,
+        sub_id: u64,
+        merchant: Address,
+        token: Address,
+        first_charge: Option<FirstCharge>,
+        start_ledger: Option<u32>,
+    ) {
+        info.require_auth();
+        if env.storage().persistent().has(&sub_id) {
+            panic!("Subscription already exists");
+        }
+        Self::require_allowlisted(&env, &merchant, &info);
+        Self::claim_owner_slot(&env, &merchant, &info);
+        let key = sub_id;
+        let data = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            owner: info,
+            merchant,
+            token,
+            state: SubscriptionState::Active,
+            failure_count: 0,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&key, &data);
+        Self::set_start_ledger(&env, sub_id, start_ledger);
+
+        #[cfg(any(test, feature = "invariants"))]
+        invariants::check_sub(&env, sub_id, None);
+
+        if let Some(first_charge) = first_charge {
+            Self::charge_first_cycle(&env, &data.owner, sub_id, first_charge);
+        }
+    }
+
+    /// Publish subscription terms users can sign up to as-is. Returns the
+    /// template id. Merchant only.
+    pub fn create_template(env: Env, terms: SubscriptionTemplate) -> u64 {
+        terms.merchant.require_auth();
+        if terms.pricing.unit_price < 0 || terms.pricing.quantity == 0 {
+            panic!("Invalid pricing");
+        }
+        Self::require_sane_amount(&env, &terms.token, terms.pricing.amount());
+        if terms.interval_ledgers == 0 || terms.window_ledgers >= terms.interval_ledgers {
+            panic!("Invalid schedule");
+        }
+
+        let template_id: u64 = env
+            .storage()
+            .instance()
+            .get(&ContractKey::NextTemplateId)
+            .unwrap_or(1);
+        env.storage()
+            .instance()
+            .set(&ContractKey::NextTemplateId, &(template_id + 1));
+        env.storage()
+            .persistent()
+            .set(&TemplateKey::Terms(template_id), &terms);
+
+        TemplateCreated {
+            merchant: terms.merchant,
+            template_id,
+        }
+        .publish(&env);
+        template_id
+    }
+
+    /// Get a subscription template.
+    pub fn get_template(env: Env, template_id: u64) -> Option<SubscriptionTemplate> {
+        env.storage()
+            .persistent()
+            .get(&TemplateKey::Terms(template_id))
+    }
+
+    /// Start subscription `sub_id` on a template's terms. The owner signs
+    /// only the template id, so the terms can't differ from what the
+    /// merchant published. With `first_charge`, the first cycle is charged
+    /// at sign-up instead of when the schedule first comes due; its amount
+    /// must be the template's price and the template can't have a trial.
+    /// With a future `start_ledger`, the schedule (and any trial) begins
+    /// then instead of now.
+    pub fn subscribe_from_template(
+        env: Env,
+        owner: Address,
+        sub_id: u64,
+        template_id: u64,
+        first_charge: Option<FirstCharge>,
+        start_ledger: Option<u32>,
+    ) {
+        owner.require_auth();
+        Self::subscribe(&env, owner.clone(), sub_id, template_id, start_ledger);
+        if let Some(first_charge) = first_charge {
+            let terms = Self::get_template(env.clone(), template_id).expect("Template not found");
+            if terms.trial_ledgers > 0 {
+                panic!("Template has a trial period");
+            }
+            Self::charge_first_cycle(&env, &owner, sub_id, first_charge);
+        }
+    }
+
+    /// Internal helper – grants the first charge's approval and renews
+    /// against it on the owner's behalf. The owner must already have
+    /// authorized the call.
+    fn charge_first_cycle(env: &Env, owner: &Address, sub_id: u64, first_charge: FirstCharge) {
+        if first_charge.amount <= 0 {
+            panic!("Invalid first charge");
+        }
+        Self::create_approval(
+            env,
+            sub_id,
+            first_charge.approval_id,
+            first_charge.amount,
+            env.ledger().sequence(),
+        );
+        Self::renew_sub(
+            env.clone(),
+            owner.clone(),
+            sub_id,
+            first_charge.approval_id,
+            first_charge.amount,
+            0,
+            0,
+            true,
+            false,
+            // The owner's signature on the creating call covers the renewal
+            Some(owner),
+        );
+    }
+
+    /// Internal helper – records when billing starts; a start that isn't
+    /// ahead of the current ledger is dropped.
+    fn set_start_ledger(env: &Env, sub_id: u64, start_ledger: Option<u32>) {
+        let key = SubKey::StartLedger(sub_id);
+        match start_ledger {
+            Some(start) if start > env.ledger().sequence() => {
+                env.storage().persistent().set(&key, &start)
+            }
+            _ => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Ledger the subscription's billing starts at, if it was created with
+    /// a deferred start.
+    pub fn get_start_ledger(env: Env, sub_id: u64) -> Option<u32> {
+        env.storage().persistent().get(&SubKey::StartLedger(sub_id))
+    }
+
+    /// Internal helper – aborts with `Error::NotStarted` before the
+    /// subscription's start ledger.
+    fn require_started(env: &Env, sub_id: u64) {
+        if Self::get_start_ledger(env.clone(), sub_id)
+            .is_some_and(|start| env.ledger().sequence() < start)
+        {
+            panic_with_error!(env, Error::NotStarted);
+        }
+    }
+
+    /// Subscribe to a template and approve its first `terms.cycles`
+    /// renewals, under one owner signature. Approval ids run from
+    /// `terms.approval_id` upwards, one per cycle.
+    pub fn subscribe_and_approve(
+        env: Env,
+        owner: Address,
+        sub_id: u64,
+        template_id: u64,
+        terms: ApprovalTerms,
+    ) {
+        owner.require_auth();
+        if terms.cycles == 0 {
+            panic!("Invalid cycle count");
+        }
+        Self::subscribe(&env, owner, sub_id, template_id, None);
+        for cycle in 0..terms.cycles {
+            Self::create_approval(
+                &env,
+                sub_id,
+                terms.approval_id + cycle as u64,
+                terms.max_spend,
+                terms.expires_at,
+            );
+        }
+    }
+
+    /// Internal helper – creates a subscription on a template's terms,
+    /// starting at `start_ledger` if that is ahead.
+    fn subscribe(
+        env: &Env,
+        owner: Address,
+        sub_id: u64,
+        template_id: u64,
+        start_ledger: Option<u32>,
+    ) {
+        if env.storage().persistent().has(&sub_id) {
+            panic!("Subscription already exists");
+        }
+        let terms = Self::get_template(env.clone(), template_id).expect("Template not found");
+        Self::require_allowlisted(env, &terms.merchant, &owner);
+        Self::claim_owner_slot(env, &terms.merchant, &owner);
+
+        let data = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            owner: owner.clone(),
+            merchant: terms.merchant,
+            token: terms.token,
+            state: SubscriptionState::Active,
+            failure_count: 0,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&sub_id, &data);
+        env.storage()
+            .persistent()
+            .set(&SubKey::Pricing(sub_id), &terms.pricing);
+        Self::set_start_ledger(env, sub_id, start_ledger);
+        let start = env.ledger().sequence().max(start_ledger.unwrap_or(0));
+        let first_due = start + terms.trial_ledgers;
+        env.storage().persistent().set(
+            &SubKey::Schedule(sub_id),
+            &Schedule {
+                next_due: first_due,
+                interval_ledgers: terms.interval_ledgers,
+                window_ledgers: terms.window_ledgers,
+                missed_cycles: 0,
+                prepaid_cycles: 0,
+            },
+        );
+        env.storage()
+            .persistent()
+            .set(&SubKey::ScheduleStart(sub_id), &first_due);
+        env.storage()
+            .persistent()
+            .set(&SubKey::RetryPolicy(sub_id), &terms.retry);
+        env.storage()
+            .persistent()
+            .set(&SubKey::Template(sub_id), &template_id);
+        Self::index_subscriber(env, template_id, sub_id);
+
+        #[cfg(any(test, feature = "invariants"))]
+        invariants::check_sub(env, sub_id, None);
+
+        SubscribedFromTemplate {
+            owner,
+            sub_id,
+            template_id,
+        }
+        .publish(env);
+    }
+
+    /// Template a subscription was created from, if any.
+    pub fn get_sub_template(env: Env, sub_id: u64) -> Option<u64> {
+        match Self::get_plan_migration(env.clone(), sub_id) {
+            Some(migration) if env.ledger().sequence() >= migration.effective_at => {
+                Some(migration.template_id)
+            }
+            _ => env.storage().persistent().get(&SubKey::Template(sub_id)),
+        }
+    }
+
+    /// Internal helper – aborts if the subscription is on a template, whose
+    /// published terms its owner can't change.
+    fn require_own_terms(env: &Env, sub_id: u64) {
+        if Self::get_sub_template(env.clone(), sub_id).is_some() {
+            panic!("Subscription follows its template's terms");
+        }
+    }
+
+    /// Scheduled plan migration of a subscription, if any.
+    pub fn get_plan_migration(env: Env, sub_id: u64) -> Option<PlanMigration> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::PlanMigration(sub_id))
+    }
+
+    /// Internal helper – appends `sub_id` to a template's subscriber list.
+    fn index_subscriber(env: &Env, template_id: u64, sub_id: u64) {
+        let count = Self::get_subscriber_count(env.clone(), template_id);
+        env.storage()
+            .persistent()
+            .set(&TemplateKey::Subscriber(template_id, count), &sub_id);
+        env.storage()
+            .persistent()
+            .set(&TemplateKey::SubscriberCount(template_id), &(count + 1));
+    }
+
+    /// Number of entries in a template's subscriber list. Subscriptions
+    /// later migrated away stay listed.
+    pub fn get_subscriber_count(env: Env, template_id: u64) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&TemplateKey::SubscriberCount(template_id))
+            .unwrap_or(0)
+    }
+
+    /// Schedule subscribers of `old_template_id` to move to
+    /// `new_template_id`, `PLAN_MIGRATION_NOTICE_LEDGERS` from now: the new
+    /// plan's price, retry policy and billing interval apply from then on,
+    /// the interval from the first due date after that. Subscribers with a
+    /// price change still pending are skipped; run the migration again
+    /// once it has applied. Walks up to
+    /// `limit` list entries from `cursor`, at most `MAX_MIGRATION_PAGE`, and
+    /// returns the cursor to resume from, equal to `get_subscriber_count`
+    /// once done. Merchant only.
+    pub fn migrate_subs_to_plan(
+        env: Env,
+        old_template_id: u64,
+        new_template_id: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> u32 {
+        let old = Self::get_template(env.clone(), old_template_id).expect("Template not found");
+        let new = Self::get_template(env.clone(), new_template_id).expect("Template not found");
+        old.merchant.require_auth();
+        if old.merchant != new.merchant || old.token != new.token {
+            panic!("Plans must share merchant and token");
+        }
+
+        let effective_at = env.ledger().sequence() + PLAN_MIGRATION_NOTICE_LEDGERS;
+        let end = Self::get_subscriber_count(env.clone(), old_template_id)
+            .min(cursor.saturating_add(limit.min(MAX_MIGRATION_PAGE)));
+        for index in cursor..end {
+            let sub_id: u64 = env
+                .storage()
+                .persistent()
+                .get(&TemplateKey::Subscriber(old_template_id, index))
+                .unwrap();
+            // Skip entries that already moved, or are scheduled to
+            let current = match Self::get_plan_migration(env.clone(), sub_id) {
+                Some(migration) => Some(migration.template_id),
+                None => env.storage().persistent().get(&SubKey::Template(sub_id)),
+            };
+            if current != Some(old_template_id) {
+                continue;
+            }
+            Self::settle_plan_migration(&env, sub_id);
+            Self::settle_price_change(&env, sub_id);
+            if Self::get_price_change(env.clone(), sub_id).is_some() {
+                continue;
+            }
+
+            env.storage().persistent().set(
+                &SubKey::PlanMigration(sub_id),
+                &PlanMigration {
+                    template_id: new_template_id,
+                    effective_at,
+                },
+            );
+            env.storage().persistent().set(
+                &SubKey::PriceChange(sub_id),
+                &PriceChange {
+                    pricing: new.pricing.clone(),
+                    effective_at,
+                },
+            );
+            Self::index_subscriber(&env, new_template_id, sub_id);
+            PlanMigrationScheduled {
+                sub_id,
+                old_template_id,
+                new_template_id,
+                effective_at,
+            }
+            .publish(&env);
+        }
+        end.max(cursor)
+    }
+
+    /// Internal helper – the template a plan migration has moved the
+    /// subscription to, until the migration is settled.
+    fn applied_migration(env: &Env, sub_id: u64) -> Option<SubscriptionTemplate> {
+        Self::get_plan_migration(env.clone(), sub_id)
+            .filter(|migration| env.ledger().sequence() >= migration.effective_at)
+            .map(|migration| Self::get_template(env.clone(), migration.template_id).unwrap())
+    }
+
+    /// Internal helper – folds a plan migration that already applies into
+    /// the subscription's stored template, retry policy and schedule. The
+    /// schedule keeps its next due date and counts from it on the new
+    /// interval.
+    fn settle_plan_migration(env: &Env, sub_id: u64) {
+        let migration = match Self::get_plan_migration(env.clone(), sub_id) {
+            Some(migration) if env.ledger().sequence() >= migration.effective_at => migration,
+            _ => return,
+        };
+        let template = Self::get_template(env.clone(), migration.template_id).unwrap();
+        if let Some(schedule) = Self::get_schedule(env.clone(), sub_id) {
+            env.storage()
+                .persistent()
+                .set(&SubKey::Schedule(sub_id), &schedule);
+            env.storage()
+                .persistent()
+                .set(&SubKey::ScheduleStart(sub_id), &schedule.next_due);
+        }
+        env.storage()
+            .persistent()
+            .set(&SubKey::Template(sub_id), &migration.template_id);
+        env.storage()
+            .persistent()
+            .set(&SubKey::RetryPolicy(sub_id), &template.retry);
+        env.storage()
+            .persistent()
+            .remove(&SubKey::PlanMigration(sub_id));
+    }
+
+    /// Retry policy fixed for a subscription, if any.
+    pub fn get_retry_policy(env: Env, sub_id: u64) -> Option<RetryPolicy> {
+        match Self::applied_migration(&env, sub_id) {
+            Some(template) => Some(template.retry),
+            None => env.storage().persistent().get(&SubKey::RetryPolicy(sub_id)),
+        }
+    }
+
+    /// Offer `owner` a subscription paying `merchant` in `token`. It stays
+    /// in PendingAcceptance, and can't renew, until the owner accepts it no
+    /// later than `expires_at`. Merchant only.
+    pub fn offer_sub(
+        env: Env,
+        merchant: Address,
+        sub_id: u64,
+        owner: Address,
+        token: Address,
+        expires_at: u32,
+    ) {
+        merchant.require_auth();
+        if env.storage().persistent().has(&sub_id) {
+            panic!("Subscription already exists");
+        }
+        if expires_at < env.ledger().sequence() {
+            panic!("Offer already expired");
+        }
+
+        let data = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            owner: owner.clone(),
+            merchant: merchant.clone(),
+            token,
+            state: SubscriptionState::PendingAcceptance,
+            failure_count: 0,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&sub_id, &data);
+        env.storage()
+            .persistent()
+            .set(&SubKey::OfferExpiry(sub_id), &expires_at);
+
+        #[cfg(any(test, feature = "invariants"))]
+        invariants::check_sub(&env, sub_id, None);
+
+        SubscriptionOffered {
+            owner,
+            sub_id,
+            merchant,
+            expires_at,
+        }
+        .publish(&env);
+    }
+
+    /// Ledger an open offer expires at, if the subscription is one.
+    pub fn get_offer_expiry(env: Env, sub_id: u64) -> Option<u32> {
+        env.storage().persistent().get(&SubKey::OfferExpiry(sub_id))
+    }
+
+    /// Internal helper – loads an open, unexpired offer.
+    fn open_offer(env: &Env, sub_id: u64) -> SubscriptionData {
+        let data = Self::get_sub(env.clone(), sub_id);
+        if data.state != SubscriptionState::PendingAcceptance {
+            panic!("Subscription is not pending acceptance");
+        }
+        data
+    }
+
+    /// Accept an offered subscription, activating it with its first
+    /// approval. Owner only.
+    pub fn accept_sub(env: Env, sub_id: u64, approval_id: u64, max_spend: i128, expires_at: u32) {
+        let mut data = Self::open_offer(&env, sub_id);
+        data.owner.require_auth();
+        Self::require_allowlisted(&env, &data.merchant, &data.owner);
+        Self::claim_owner_slot(&env, &data.merchant, &data.owner);
+        let offer_expiry = Self::get_offer_expiry(env.clone(), sub_id).unwrap_or(u32::MAX);
+        if env.ledger().sequence() > offer_expiry {
+            panic!("Offer expired");
+        }
+
+        data.state = SubscriptionState::Active;
+        env.storage().persistent().set(&sub_id, &data);
+        env.storage()
+            .persistent()
+            .remove(&SubKey::OfferExpiry(sub_id));
+        StateTransition {
+            sub_id,
+            new_state: SubscriptionState::Active,
+        }
+        .publish(&env);
+        OfferAccepted { sub_id }.publish(&env);
+
+        Self::create_approval(&env, sub_id, approval_id, max_spend, expires_at);
+    }
+
+    /// Decline an offered subscription, deleting it. Owner only.
+    pub fn decline_sub(env: Env, sub_id: u64) {
+        let data = Self::open_offer(&env, sub_id);
+        data.owner.require_auth();
+        env.storage().persistent().remove(&sub_id);
+        env.storage()
+            .persistent()
+            .remove(&SubKey::OfferExpiry(sub_id));
+        OfferDeclined { sub_id }.publish(&env);
+    }
+
+    /// Delete an offer nobody accepted before its expiry. Anyone may call.
+    pub fn expire_offer(env: Env, sub_id: u64) {
+        Self::open_offer(&env, sub_id);
+        let expired_at = Self::get_offer_expiry(env.clone(), sub_id).expect("Offer has no expiry");
+        if env.ledger().sequence() <= expired_at {
+            panic!("Offer not expired");
+        }
+        env.storage().persistent().remove(&sub_id);
+        env.storage()
+            .persistent()
+            .remove(&SubKey::OfferExpiry(sub_id));
+        OfferExpired { sub_id, expired_at }.publish(&env);
+    }
+
+    /// Put a subscription on a billing schedule: the first cycle is due at
+    /// `first_due` and each one after `interval_ledgers` more. Renewals are
+    /// accepted for `window_ledgers` after each due date. Owner only, and
+    /// not for subscriptions on a template.
+    pub fn set_schedule(
+        env: Env,
+        sub_id: u64,
+        first_due: u32,
+        interval_ledgers: u32,
+        window_ledgers: u32,
+    ) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        Self::require_own_terms(&env, sub_id);
+        if interval_ledgers == 0 || window_ledgers >= interval_ledgers {
+            panic!("Invalid schedule");
+        }
+
+        let prepaid_cycles = Self::get_schedule(env.clone(), sub_id)
+            .map(|schedule| schedule.prepaid_cycles)
+            .unwrap_or(0);
+        let schedule = Schedule {
+            next_due: first_due,
+            interval_ledgers,
+            window_ledgers,
+            missed_cycles: 0,
+            prepaid_cycles,
+        };
+        env.storage()
+            .persistent()
+            .set(&SubKey::Schedule(sub_id), &schedule);
+        env.storage()
+            .persistent()
+            .set(&SubKey::ScheduleStart(sub_id), &first_due);
+    }
+
+    /// Get the billing schedule, if the subscription has one.
+    pub fn get_schedule(env: Env, sub_id: u64) -> Option<Schedule> {
+        let schedule: Schedule = env.storage().persistent().get(&SubKey::Schedule(sub_id))?;
+        match Self::applied_migration(&env, sub_id) {
+            Some(template) => Some(Schedule {
+                interval_ledgers: template.interval_ledgers,
+                window_ledgers: template.window_ledgers,
+                ..schedule
+            }),
+            None => Some(schedule),
+        }
+    }
+
+    /// Number of billing cycles of `sub_id` that have fallen due by now,
+    /// counted from the first due date of its schedule; 0 before it.
+    /// `None` without a schedule.
+    pub fn current_cycle(env: Env, sub_id: u64) -> Option<u32> {
+        let schedule = Self::get_schedule(env.clone(), sub_id)?;
+        let start = Self::schedule_start(&env, sub_id, &schedule);
+        let now = env.ledger().sequence();
+        if now < start {
+            return Some(0);
+        }
+        Some((now - start) / schedule.interval_ledgers + 1)
+    }
+
+    /// Whether the owner of `sub_id` should have access now; the one rule
+    /// merchant contracts and services should gate on. Active and Retrying
+    /// subscriptions are entitled; with a schedule, only until the renewal
+    /// window of the first cycle not yet paid or prepaid closes, which also
+    /// covers a trial before the first due date. Pending offers, failed,
+    /// completed and unknown subscriptions are not, nor are paused or
+    /// frozen ones, which can't be billed.
+    pub fn is_entitled(env: Env, sub_id: u64) -> bool {
+        let data = match Self::load_sub(&env, sub_id) {
+            Some(data) => data,
+            None => return false,
+        };
+        if Self::is_sub_paused(env.clone(), sub_id)
+            || Self::get_freeze(env.clone(), sub_id).is_some()
+        {
+            return false;
+        }
+        match data.state {
+            SubscriptionState::Active | SubscriptionState::Retrying => {}
+            SubscriptionState::Failed
+            | SubscriptionState::PendingAcceptance
+            | SubscriptionState::Completed => return false,
+        }
+        match Self::get_schedule(env.clone(), sub_id) {
+            Some(schedule) => {
+                let paid_through = schedule.next_due.saturating_add(
+                    schedule
+                        .prepaid_cycles
+                        .saturating_mul(schedule.interval_ledgers),
+                );
+                env.ledger().sequence() <= paid_through.saturating_add(schedule.window_ledgers)
+            }
+            None => true,
+        }
+    }
+
+    /// Internal helper – first due date of the schedule. Schedules set
+    /// before it was recorded, or moved to another plan's interval, count
+    /// from their current due date.
+    fn schedule_start(env: &Env, sub_id: u64, schedule: &Schedule) -> u32 {
+        if Self::applied_migration(env, sub_id).is_some() {
+            return schedule.next_due;
+        }
+        env.storage()
+            .persistent()
+            .get(&SubKey::ScheduleStart(sub_id))
+            .unwrap_or(schedule.next_due)
+    }
+
+    /// Internal helper – checks the cycle a renewal at `schedule.next_due`
+    /// pays for against the one derived from the ledger. Rejects a due date
+    /// off the schedule's grid, or one that is neither the current cycle
+    /// nor, for early renewals, the next.
+    fn require_billable_cycle(env: &Env, sub_id: u64, schedule: &Schedule) {
+        let start = Self::schedule_start(env, sub_id, schedule);
+        let offset = schedule
+            .next_due
+            .checked_sub(start)
+            .expect("Cycle mismatch");
+        if !offset.is_multiple_of(schedule.interval_ledgers) {
+            panic!("Cycle mismatch");
+        }
+        let cycle = offset / schedule.interval_ledgers + 1;
+        let current = Self::current_cycle(env.clone(), sub_id).unwrap_or(0);
+        if cycle != current && cycle != current + 1 {
+            panic!("Cycle mismatch");
+        }
+    }
+
+    /// Internal helper – enforces the end of the renewal window. Past it,
+    /// marks every elapsed cycle missed, moves `next_due` forward and
+    /// returns true.
+    fn skip_missed_cycles(env: &Env, sub_id: u64, schedule: &mut Schedule) -> bool {
+        let now = env.ledger().sequence();
+        let window_end = schedule.next_due + schedule.window_ledgers;
+        if now <= window_end {
+            return false;
+        }
+
+        let missed = (now - window_end - 1) / schedule.interval_ledgers + 1;
+        Self::note_renewal_event(env, sub_id);
+        for _ in 0..missed {
+            schedule.missed_cycles += 1;
+            CycleMissed {
+                sub_id,
+                due: schedule.next_due,
+                missed_cycles: schedule.missed_cycles,
+            }
+            .publish(env);
+            schedule.next_due += schedule.interval_ledgers;
+        }
+        true
+    }
+
+    /// Opt in to early renewals at a discount of `discount_bps`, which may
+    /// be zero. Merchant only.
+    pub fn set_early_discount(env: Env, merchant: Address, discount_bps: u32) {
+        merchant.require_auth();
+        if discount_bps > MAX_EARLY_DISCOUNT_BPS {
+            panic!("Early discount too large");
+        }
+        env.storage()
+            .persistent()
+            .set(&MerchantKey::EarlyDiscount(merchant), &discount_bps);
+    }
+
+    /// Get the merchant's early-renewal discount, if it accepts early
+    /// renewals.
+    pub fn get_early_discount(env: Env, merchant: Address) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::EarlyDiscount(merchant))
+    }
+
+    /// Internal helper – admits a renewal before `next_due` and returns the
+    /// discount in basis points. Early renewals must be initiated by the
+    /// owner, may run at most one cycle ahead, and need a merchant that
+    /// accepts them.
+    fn early_discount_bps(
+        env: &Env,
+        caller: &Address,
+        data: &SubscriptionData,
+        schedule: &Schedule,
+    ) -> u32 {
+        let now = env.ledger().sequence();
+        if now >= schedule.next_due {
+            return 0;
+        }
+        let discount = Self::get_early_discount(env.clone(), data.merchant.clone());
+        match discount {
+            Some(bps)
+                if *caller == data.owner
+                    && now + schedule.interval_ledgers >= schedule.next_due =>
+            {
+                bps
+            }
+            _ => panic!("Renewal not yet due"),
+        }
+    }
+
+    /// Price the subscription as `quantity` units of `unit_price` per cycle.
+    /// Once set, `renew` and `prepay_cycles` only accept that amount.
+    /// Owner only, and not for subscriptions on a template.
+    pub fn set_pricing(env: Env, sub_id: u64, unit_price: i128, quantity: u32) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        Self::require_own_terms(&env, sub_id);
+        if unit_price < 0 || quantity == 0 {
+            panic!("Invalid pricing");
+        }
+
+        let pricing = Pricing {
+            unit_price,
+            quantity,
+        };
+        // Reject prices whose per-cycle amount overflows or is out of range
+        Self::require_sane_amount(&env, &data.token, pricing.amount());
+        Self::settle_price_change(&env, sub_id);
+        env.storage()
+            .persistent()
+            .set(&SubKey::Pricing(sub_id), &pricing);
+    }
+
+    /// Get the per-cycle pricing, if set.
+    pub fn get_pricing(env: Env, sub_id: u64) -> Option<Pricing> {
+        match Self::get_price_change(env.clone(), sub_id) {
+            Some(change) if env.ledger().sequence() >= change.effective_at => Some(change.pricing),
+            _ => env.storage().persistent().get(&SubKey::Pricing(sub_id)),
+        }
+    }
+
+    /// Schedule new pricing taking effect at `effective_at`; until then
+    /// renewals keep the current price. Replaces any earlier scheduled
+    /// change. Merchant only.
+    pub fn schedule_price_change(
+        env: Env,
+        sub_id: u64,
+        unit_price: i128,
+        quantity: u32,
+        effective_at: u32,
+    ) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.merchant.require_auth();
+        if unit_price < 0 || quantity == 0 {
+            panic!("Invalid pricing");
+        }
+        if effective_at <= env.ledger().sequence() {
+            panic!("Price change must be in the future");
+        }
+        let pricing = Pricing {
+            unit_price,
+            quantity,
+        };
+        Self::require_sane_amount(&env, &data.token, pricing.amount());
+
+        Self::settle_price_change(&env, sub_id);
+        env.storage().persistent().set(
+            &SubKey::PriceChange(sub_id),
+            &PriceChange {
+                pricing,
+                effective_at,
+            },
+        );
+        PriceChangeScheduled {
+            sub_id,
+            unit_price,
+            quantity,
+            effective_at,
+        }
+        .publish(&env);
+    }
+
+    /// Scheduled price change, if any, including one that already applies.
+    pub fn get_price_change(env: Env, sub_id: u64) -> Option<PriceChange> {
+        env.storage().persistent().get(&SubKey::PriceChange(sub_id))
+    }
+
+    /// Internal helper – folds a price change that already applies into
+    /// the stored pricing.
+    fn settle_price_change(env: &Env, sub_id: u64) {
+        if let Some(change) = Self::get_price_change(env.clone(), sub_id) {
+            if env.ledger().sequence() >= change.effective_at {
+                env.storage()
+                    .persistent()
+                    .set(&SubKey::Pricing(sub_id), &change.pricing);
+                env.storage()
+                    .persistent()
+                    .remove(&SubKey::PriceChange(sub_id));
+            }
+        }
+    }
+
+    /// Publish an `UpcomingChange` notice for a future-dated change, at
+    /// most once per `CHANGE_NOTICE_LEDGERS`, unless the owner turned
+    /// reminders off. Anyone may call.
+    pub fn emit_change_notice(env: Env, sub_id: u64) {
+        Self::require_reminders(&env, sub_id);
+        let now = env.ledger().sequence();
+        let change = Self::get_price_change(env.clone(), sub_id)
+            .filter(|change| change.effective_at > now)
+            .expect("No upcoming change");
+        let last: Option<u32> = env.storage().persistent().get(&SubKey::LastNotice(sub_id));
+        if last.is_some_and(|last| now < last + CHANGE_NOTICE_LEDGERS) {
+            panic!("Notice already sent this window");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&SubKey::LastNotice(sub_id), &now);
+        UpcomingChange {
+            sub_id,
+            kind: symbol_short!("price"),
+            effective_at: change.effective_at,
+            new_amount: change.pricing.amount(),
+        }
+        .publish(&env);
+    }
+
+    /// Everything queued against `sub_id` that hasn't taken effect yet,
+    /// with the ledger it does: a deferred start, a price change, a plan
+    /// migration and an ownership transfer, in that order.
+    pub fn get_pending_changes(env: Env, sub_id: u64) -> Vec<PendingChange> {
+        let now = env.ledger().sequence();
+        let mut changes = Vec::new(&env);
+        if let Some(start) = Self::get_start_ledger(env.clone(), sub_id).filter(|s| *s > now) {
+            changes.push_back(PendingChange::Start(start));
+        }
+        if let Some(change) =
+            Self::get_price_change(env.clone(), sub_id).filter(|c| c.effective_at > now)
+        {
+            changes.push_back(PendingChange::PriceChange(change));
+        }
+        if let Some(migration) =
+            Self::get_plan_migration(env.clone(), sub_id).filter(|m| m.effective_at > now)
+        {
+            changes.push_back(PendingChange::PlanMigration(migration));
+        }
+        if let Some(recovery) = Self::get_pending_recovery(env.clone(), sub_id) {
+            changes.push_back(PendingChange::Transfer(recovery));
+        }
+        changes
+    }
+
+    /// Per-cycle amount: the installment due on an installment plan, else
+    /// the merchant's quote if it prices dynamically, else
+    /// `unit_price * quantity` if pricing is set.
+    pub fn get_amount(env: Env, sub_id: u64) -> Option<i128> {
+        Self::get_installment_due(env.clone(), sub_id)
+            .or_else(|| Self::quote_for(&env, sub_id))
+            .or_else(|| Self::get_pricing(env, sub_id).map(|pricing| pricing.amount()))
+    }
+
+    /// Internal helper – the pricing `amount` is charged under. Subscriptions
+    /// without pricing are a single unit of `amount`, as are installments.
+    fn pricing_for(env: &Env, sub_id: u64, amount: i128) -> Pricing {
+        if let Some(due) = Self::get_installment_due(env.clone(), sub_id) {
+            if amount != due {
+                panic!("Amount does not match installment");
+            }
+            return Pricing {
+                unit_price: amount,
+                quantity: 1,
+            };
+        }
+        match Self::get_pricing(env.clone(), sub_id) {
+            Some(pricing) if pricing.amount() != amount => {
+                panic!("Amount does not match pricing")
+            }
+            Some(pricing) => pricing,
+            None => Pricing {
+                unit_price: amount,
+                quantity: 1,
+            },
+        }
+    }
+
+    // ── Metered usage ─────────────────────────────────────────────
+
+    /// Set or clear the per-cycle usage budget. Owner only.
+    pub fn set_usage_budget(env: Env, sub_id: u64, budget: Option<u64>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+
+        let key = SubKey::UsageBudget(sub_id);
+        match budget {
+            Some(0) => panic!("Usage budget must be positive"),
+            Some(budget) => env.storage().persistent().set(&key, &budget),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Get the per-cycle usage budget, if set.
+    pub fn get_usage_budget(env: Env, sub_id: u64) -> Option<u64> {
+        env.storage().persistent().get(&SubKey::UsageBudget(sub_id))
+    }
+
+    /// Usage accrued in the current cycle.
+    pub fn get_usage(env: Env, sub_id: u64) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&SubKey::Usage(sub_id))
+            .unwrap_or(0)
+    }
+
+    /// Accrue `units` of metered usage for the current cycle and return how
+    /// many were accepted. Accrual stops at the usage budget; crossing 50,
+    /// 80 and 100% of it emits `UsageThresholdReached`, from the owner's
+    /// `usage_alert_pct` up. Merchant only.
+    pub fn record_usage(env: Env, sub_id: u64, units: u64) -> u64 {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.merchant.require_auth();
+        Self::require_not_frozen(&env, sub_id);
+
+        let used = Self::get_usage(env.clone(), sub_id);
+        let budget = Self::get_usage_budget(env.clone(), sub_id);
+        let accepted = match budget {
+            Some(budget) => units.min(budget.saturating_sub(used)),
+            None => units,
+        };
+        let total = used.checked_add(accepted).expect("Usage overflow");
+        env.storage()
+            .persistent()
+            .set(&SubKey::Usage(sub_id), &total);
+
+        // Threshold alerts are informational
+        if let Some(budget) = budget.filter(|_| Self::get_verbose_events(env.clone())) {
+            let min_pct = Self::get_notification_prefs(env.clone(), sub_id).usage_alert_pct;
+            for threshold_pct in USAGE_THRESHOLDS.into_iter().filter(|pct| *pct >= min_pct) {
+                let mark = budget as u128 * threshold_pct as u128;
+                if (used as u128) * 100 < mark && (total as u128) * 100 >= mark {
+                    UsageThresholdReached {
+                        sub_id,
+                        threshold_pct,
+                        used: total,
+                        budget,
+                    }
+                    .publish(&env);
+                }
+            }
+        }
+        accepted
+    }
+
+    /// Set or clear overage billing: usage beyond `included_usage` per cycle
+    /// is charged at `overage_price` per unit. Owner only, and not for
+    /// subscriptions on a template.
+    pub fn set_overage_pricing(env: Env, sub_id: u64, overage: Option<OveragePricing>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        Self::require_own_terms(&env, sub_id);
+
+        let key = SubKey::Overage(sub_id);
+        match overage {
+            Some(overage) if overage.overage_price < 0 => panic!("Invalid overage pricing"),
+            Some(overage) => {
+                Self::require_sane_amount(&env, &data.token, overage.overage_price);
+                env.storage().persistent().set(&key, &overage)
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Get the overage pricing, if set.
+    pub fn get_overage_pricing(env: Env, sub_id: u64) -> Option<OveragePricing> {
+        env.storage().persistent().get(&SubKey::Overage(sub_id))
+    }
+
+    /// Internal helper – billable overage units and amount for the current
+    /// cycle: `max(0, usage - included) * overage_price`.
+    fn overage_for(env: &Env, sub_id: u64) -> (u64, i128) {
+        let pricing = match Self::get_overage_pricing(env.clone(), sub_id) {
+            Some(pricing) => pricing,
+            None => return (0, 0),
+        };
+        let units = Self::get_usage(env.clone(), sub_id).saturating_sub(pricing.included_usage);
+        let amount = pricing
+            .overage_price
+            .checked_mul(units as i128)
+            .expect("Overage overflow");
+        (units, amount)
+    }
+
+    /// Offer a discount of `discount_bps` on prepaid cycles. Merchant only.
+    pub fn set_prepay_discount(env: Env, merchant: Address, discount_bps: u32) {
+        merchant.require_auth();
+        if discount_bps > MAX_PREPAY_DISCOUNT_BPS {
+            panic!("Prepay discount too large");
+        }
+        env.storage()
+            .persistent()
+            .set(&MerchantKey::PrepayDiscount(merchant), &discount_bps);
+    }
+
+    /// Get the merchant's prepay discount in basis points (zero if unset).
+    pub fn get_prepay_discount(env: Env, merchant: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::PrepayDiscount(merchant))
+            .unwrap_or(0)
+    }
+
+    /// Pay the merchant for `cycles` future cycles of `amount_per_cycle` at
+    /// once, less the merchant's prepay discount. Each prepaid cycle is
+    /// consumed by `renew` on a later due date without a transfer. Owner
+    /// only; the subscription must be on a schedule.
+    pub fn prepay_cycles(env: Env, sub_id: u64, cycles: u32, amount_per_cycle: i128) -> i128 {
+        Self::prepay_cycles_impl(env, sub_id, cycles, amount_per_cycle, None)
+    }
+
+    /// Internal helper – the body of `prepay_cycles`, skipping the owner's
+    /// auth if they are the `signer` of the enclosing call.
+    fn prepay_cycles_impl(
+        env: Env,
+        sub_id: u64,
+        cycles: u32,
+        amount_per_cycle: i128,
+        signer: Option<&Address>,
+    ) -> i128 {
+        if Self::is_paused(env.clone()) {
+            panic!("Protocol is paused");
+        }
+        Self::require_not_frozen(&env, sub_id);
+
+        let data = Self::get_sub(env.clone(), sub_id);
+        Self::require_auth_of(&data.owner, signer);
+        if data.state == SubscriptionState::Failed {
+            panic!("Subscription is in FAILED state");
+        }
+        if data.state == SubscriptionState::PendingAcceptance {
+            panic!("Subscription not accepted");
+        }
+        Self::settle_plan_migration(&env, sub_id);
+        let mut schedule =
+            Self::get_schedule(env.clone(), sub_id).expect("Subscription has no schedule");
+        if cycles == 0 || amount_per_cycle <= 0 {
+            panic!("Invalid prepayment");
+        }
+        if Self::get_max_cycles(env.clone(), sub_id).is_some_and(|max_cycles| {
+            data.renewal_seq + schedule.prepaid_cycles as u64 + cycles as u64 > max_cycles as u64
+        }) {
+            panic!("Prepayment exceeds remaining cycles");
+        }
+        if Self::get_installment_plan(env.clone(), sub_id).is_some() {
+            panic!("Installment plans are paid off with pay_off");
+        }
+        Self::pricing_for(&env, sub_id, amount_per_cycle);
+
+        let gross = amount_per_cycle
+            .checked_mul(cycles as i128)
+            .expect("Prepayment overflow");
+        let discount_bps = Self::get_prepay_discount(env.clone(), data.merchant.clone());
+        let (discount, charged) = rounding::split_bps(gross, discount_bps);
+
+        Self::screen_payment(&env, &data, charged);
+        Self::record_volume(&env, &data.token, charged);
+        token::Client::new(&env, &data.token).transfer(
+            &data.owner,
+            env.current_contract_address(),
+            &charged,
+        );
+        Self::adjust_tracked_balance(&env, &data.token, charged);
+        Self::pay_charge(&env, sub_id, &data, charged, None);
+
+        schedule.prepaid_cycles += cycles;
+        env.storage()
+            .persistent()
+            .set(&SubKey::Schedule(sub_id), &schedule);
+
+        Self::record_charge(&env, &data, sub_id, charged, 0);
+        CyclesPrepaid {
+            sub_id,
+            cycles,
+            charged,
+            discount,
+            prepaid_cycles: schedule.prepaid_cycles,
+        }
+        .publish(&env);
+        charged
+    }
+
+    // ── Labels ────────────────────────────────────────────────────
+
+    /// Replace the labels on a subscription with `labels`, up to
+    /// `MAX_LABELS` distinct hashes chosen by the owner's wallet (e.g. the
+    /// first 8 bytes of a hash of "work"), so its folders and tags follow
+    /// the owner across devices. An empty list clears them. Owner only.
+    pub fn set_labels(env: Env, sub_id: u64, labels: Vec<BytesN<8>>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        if labels.len() > MAX_LABELS {
+            panic!("Too many labels");
+        }
+        for (i, label) in labels.iter().enumerate() {
+            if labels.first_index_of(&label) != Some(i as u32) {
+                panic!("Duplicate label");
+            }
+        }
+
+        let key = SubKey::Labels(sub_id);
+        if labels.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &labels);
+        }
+    }
+
+    /// Labels the owner attached to `sub_id`, in the order set.
+    pub fn get_labels(env: Env, sub_id: u64) -> Vec<BytesN<8>> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::Labels(sub_id))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // ── Notification preferences ──────────────────────────────────
+
+    /// Set what the owner is notified about for `sub_id`. Owner only.
+    pub fn set_notification_prefs(env: Env, sub_id: u64, prefs: NotificationPrefs) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        if prefs.preview_min_amount < 0 {
+            panic!("Invalid notification prefs");
+        }
+        env.storage()
+            .persistent()
+            .set(&SubKey::NotificationPrefs(sub_id), &prefs);
+    }
+
+    /// The owner's notification preferences for `sub_id`; everything is
+    /// on until the owner sets them.
+    pub fn get_notification_prefs(env: Env, sub_id: u64) -> NotificationPrefs {
+        env.storage()
+            .persistent()
+            .get(&SubKey::NotificationPrefs(sub_id))
+            .unwrap_or(NotificationPrefs {
+                reminders: true,
+                usage_alert_pct: 0,
+                preview_min_amount: 0,
+            })
+    }
+
+    /// Internal helper – aborts a reminder the owner opted out of.
+    fn require_reminders(env: &Env, sub_id: u64) {
+        if !Self::get_notification_prefs(env.clone(), sub_id).reminders {
+            panic!("Owner opted out of reminders");
+        }
+    }
+
+    // ── Term limits ───────────────────────────────────────────────
+
+    /// Limit the subscription to `max_cycles` successful renewals in all,
+    /// after which it becomes `Completed` (e.g. a 12-month installment
+    /// plan), or lift the limit with `None`. The limit must leave room for
+    /// the cycles already renewed or prepaid. Owner only.
+    pub fn set_max_cycles(env: Env, sub_id: u64, max_cycles: Option<u32>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        if data.state == SubscriptionState::Completed {
+            panic!("Subscription is completed");
+        }
+        if Self::get_installment_plan(env.clone(), sub_id).is_some() {
+            panic!("Installment plan fixes the term");
+        }
+
+        let key = SubKey::MaxCycles(sub_id);
+        match max_cycles {
+            Some(max_cycles) => {
+                let prepaid = Self::get_schedule(env.clone(), sub_id)
+                    .map_or(0, |schedule| schedule.prepaid_cycles);
+                if max_cycles as u64 <= data.renewal_seq
+                    || (max_cycles as u64) < data.renewal_seq + prepaid as u64
+                {
+                    panic!("Invalid max cycles");
+                }
+                env.storage().persistent().set(&key, &max_cycles);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Successful renewals the subscription is limited to, if any.
+    pub fn get_max_cycles(env: Env, sub_id: u64) -> Option<u32> {
+        env.storage().persistent().get(&SubKey::MaxCycles(sub_id))
+    }
+
+    /// Internal helper – completes the subscription once its renewals
+    /// reach `max_cycles`.
+    fn complete_if_done(env: &Env, sub_id: u64, data: &mut SubscriptionData) {
+        if Self::get_max_cycles(env.clone(), sub_id)
+            .is_some_and(|max_cycles| data.renewal_seq >= max_cycles as u64)
+        {
+            Self::complete(env, sub_id, data);
+        }
+    }
+
+    /// Internal helper – moves the subscription to `Completed`, dropping it
+    /// from its schedule and plan so keepers and plan migrations pass it by.
+    fn complete(env: &Env, sub_id: u64, data: &mut SubscriptionData) {
+        data.state = SubscriptionState::Completed;
+        env.storage().persistent().set(&sub_id, data);
+        Self::release_owner_slot(env, &data.merchant, &data.owner);
+        let storage = env.storage().persistent();
+        storage.remove(&SubKey::Schedule(sub_id));
+        storage.remove(&SubKey::ScheduleStart(sub_id));
+        storage.remove(&SubKey::Template(sub_id));
+        storage.remove(&SubKey::PlanMigration(sub_id));
+        storage.remove(&SubKey::PriceChange(sub_id));
+
+        StateTransition {
+            sub_id,
+            new_state: SubscriptionState::Completed,
+        }
+        .publish(env);
+        SubscriptionCompleted {
+            sub_id,
+            cycles: data.renewal_seq as u32,
+        }
+        .publish(env);
+    }
+
+    // ── Installment plans ─────────────────────────────────────────
+
+    /// Fix the price of a term-limited subscription at `total_price`, paid
+    /// in `max_cycles` installments of `total_price / max_cycles`, the
+    /// last one taking the rounding remainder. Must be set before the first
+    /// renewal, and fixes `max_cycles` from then on. Needs both the owner
+    /// and the merchant.
+    pub fn set_installment_plan(env: Env, sub_id: u64, total_price: i128) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        data.merchant.require_auth();
+        let max_cycles =
+            Self::get_max_cycles(env.clone(), sub_id).expect("Subscription has no term limit");
+        if data.renewal_seq > 0 {
+            panic!("Subscription already renewed");
+        }
+        if total_price < max_cycles as i128 {
+            panic!("Invalid total price");
+        }
+        Self::require_sane_amount(&env, &data.token, total_price);
+        if Self::get_schedule(env.clone(), sub_id).is_some_and(|s| s.prepaid_cycles > 0) {
+            panic!("Subscription has prepaid cycles");
+        }
+
+        env.storage().persistent().set(
+            &SubKey::Installments(sub_id),
+            &InstallmentPlan {
+                total_price,
+                paid: 0,
+            },
+        );
+        InstallmentPlanSet {
+            sub_id,
+            total_price,
+            cycles: max_cycles,
+        }
+        .publish(&env);
+    }
+
+    /// The subscription's installment plan, if it has one.
+    pub fn get_installment_plan(env: Env, sub_id: u64) -> Option<InstallmentPlan> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::Installments(sub_id))
+    }
+
+    /// Amount the next installment must be, or `None` without a plan or
+    /// once it is paid off.
+    pub fn get_installment_due(env: Env, sub_id: u64) -> Option<i128> {
+        let plan = Self::get_installment_plan(env.clone(), sub_id)?;
+        let max_cycles = Self::get_max_cycles(env.clone(), sub_id)?;
+        let renewed = Self::load_sub(&env, sub_id)?.renewal_seq;
+        if plan.paid >= plan.total_price {
+            return None;
+        }
+        if renewed + 1 >= max_cycles as u64 {
+            Some(plan.total_price - plan.paid)
+        } else {
+            Some(plan.total_price / max_cycles as i128)
+        }
+    }
+
+    /// Internal helper – counts a renewal's installment towards the plan.
+    fn record_installment(env: &Env, sub_id: u64, amount: i128) {
+        if let Some(mut plan) = Self::get_installment_plan(env.clone(), sub_id) {
+            plan.paid += amount;
+            env.storage()
+                .persistent()
+                .set(&SubKey::Installments(sub_id), &plan);
+        }
+    }
+
+    /// Pay what remains of the installment plan in one charge, collected
+    /// like a renewal in the subscription's payment mode, and complete the
+    /// subscription. Returns the amount charged. Owner only.
+    pub fn pay_off(env: Env, sub_id: u64) -> i128 {
+        if Self::is_paused(env.clone()) {
+            panic!("Protocol is paused");
+        }
+        Self::require_not_frozen(&env, sub_id);
+        if Self::is_sub_paused(env.clone(), sub_id) {
+            panic!("Subscription is paused");
+        }
+        Self::require_started(&env, sub_id);
+
+        let mut data = Self::get_sub(env.clone(), sub_id);
+        if Self::merchant_suspended(&env, &data.merchant) {
+            panic_with_error!(env, Error::MerchantSuspended);
+        }
+        data.owner.require_auth();
+        if data.state == SubscriptionState::Failed {
+            panic!("Subscription is in FAILED state");
+        }
+        if data.state == SubscriptionState::Completed {
+            panic!("Subscription is completed");
+        }
+        if data.state == SubscriptionState::PendingAcceptance {
+            panic!("Subscription not accepted");
+        }
+        let mut plan =
+            Self::get_installment_plan(env.clone(), sub_id).expect("No installment plan");
+        let remaining = plan.total_price - plan.paid;
+
+        Self::screen_payment(&env, &data, remaining);
+        Self::record_volume(&env, &data.token, remaining);
+        if remaining > 0 {
+            let gateway = gateway::for_mode(&Self::get_payment_mode(env.clone(), sub_id));
+            gateway.preflight(&env, &data, remaining);
+            let held = gateway.collect(&env, &data, remaining);
+            Self::pay_charge(&env, sub_id, &data, held, None);
+        }
+        Self::record_charge(&env, &data, sub_id, remaining, 0);
+
+        plan.paid = plan.total_price;
+        env.storage()
+            .persistent()
+            .set(&SubKey::Installments(sub_id), &plan);
+        InstallmentsPaidOff {
+            sub_id,
+            amount: remaining,
+        }
+        .publish(&env);
+
+        data.failure_count = 0;
+        Self::complete(&env, sub_id, &mut data);
+        remaining
+    }
+
+    // ── Dynamic pricing ───────────────────────────────────────────
+
+    /// Price the merchant's renewals with `quote` from a pricing contract
+    /// instead of the amount the caller passes, or stop with `None`.
+    /// Installment plans keep their fixed installments. Merchant only.
+    pub fn set_pricing_contract(env: Env, merchant: Address, contract: Option<Address>) {
+        merchant.require_auth();
+        let key = MerchantKey::PricingContract(merchant.clone());
+        match &contract {
+            Some(contract) => env.storage().persistent().set(&key, contract),
+            None => env.storage().persistent().remove(&key),
+        }
+        PricingContractSet { merchant, contract }.publish(&env);
+    }
+
+    /// The merchant's pricing contract, if it prices dynamically.
+    pub fn get_pricing_contract(env: Env, merchant: Address) -> Option<Address> {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::PricingContract(merchant))
+    }
+
+    /// Internal helper – the merchant's quote for the next cycle of
+    /// `sub_id`, unless it has no pricing contract or is on an installment
+    /// plan.
+    fn quote_for(env: &Env, sub_id: u64) -> Option<i128> {
+        let data = Self::load_sub(env, sub_id)?;
+        let contract = Self::get_pricing_contract(env.clone(), data.merchant)?;
+        if Self::get_installment_plan(env.clone(), sub_id).is_some() {
+            return None;
+        }
+        let quote = PricingClient::new(env, &contract).quote(&sub_id, &(data.renewal_seq + 1));
+        if quote < 0 {
+            panic!("Invalid quote");
+        }
+        Some(quote)
+    }
+
+    /// Open charge previews for the merchant's dynamically priced
+    /// subscriptions `lead_ledgers` before each due date, or stop with
+    /// `None`. Merchant only.
+    pub fn set_preview_lead(env: Env, merchant: Address, lead_ledgers: Option<u32>) {
+        merchant.require_auth();
+        let key = MerchantKey::PreviewLead(merchant);
+        match lead_ledgers {
+            Some(0) => panic!("Invalid preview lead"),
+            Some(lead_ledgers) => env.storage().persistent().set(&key, &lead_ledgers),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Ledgers before due the merchant's charge previews open, if enabled.
+    pub fn get_preview_lead(env: Env, merchant: Address) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::PreviewLead(merchant))
+    }
+
+    /// Publish what the next renewal of a dynamically priced subscription
+    /// will charge, so the owner can revoke the approval if the price is
+    /// unacceptable. Anyone may call it, once per cycle, from the
+    /// merchant's preview lead before the due date until it falls due, if
+    /// the owner's notification prefs want it. Returns the quoted amount.
+    pub fn emit_charge_preview(env: Env, sub_id: u64) -> i128 {
+        let data = Self::get_sub(env.clone(), sub_id);
+        let lead = Self::get_preview_lead(env.clone(), data.merchant.clone())
+            .expect("Charge previews disabled");
+        let due = Self::get_schedule(env.clone(), sub_id)
+            .expect("Subscription has no schedule")
+            .next_due;
+        let now = env.ledger().sequence();
+        if now < due.saturating_sub(lead) || now >= due {
+            panic!("Outside the preview window");
+        }
+        let key = SubKey::PreviewedDue(sub_id);
+        if env.storage().persistent().get(&key) == Some(due) {
+            panic!("Charge already previewed");
+        }
+        Self::require_reminders(&env, sub_id);
+        let amount = Self::quote_for(&env, sub_id).expect("Subscription is not dynamically priced");
+        if amount < Self::get_notification_prefs(env.clone(), sub_id).preview_min_amount {
+            panic!("Charge below the owner's preview threshold");
+        }
+
+        env.storage().persistent().set(&key, &due);
+        ChargePreview {
+            owner: data.owner,
+            sub_id,
+            due,
+            amount,
+        }
+        .publish(&env);
+        amount
+    }
+
+    // ── Subscriber allowlist ──────────────────────────────────────
+
+    /// Only let owners on the merchant's allowlist start or accept its
+    /// subscriptions, or let anyone again. Existing subscriptions are left
+    /// alone. Merchant only.
+    pub fn set_allowlist_mode(env: Env, merchant: Address, enabled: bool) {
+        merchant.require_auth();
+        let key = MerchantKey::AllowlistOnly(merchant.clone());
+        if enabled {
+            env.storage().persistent().set(&key, &());
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        AllowlistModeSet { merchant, enabled }.publish(&env);
+    }
+
+    /// Whether the merchant only takes subscribers on its allowlist.
+    pub fn get_allowlist_mode(env: Env, merchant: Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&MerchantKey::AllowlistOnly(merchant))
+    }
+
+    /// Add `owner` to the merchant's allowlist, or remove it. Merchant
+    /// only.
+    pub fn set_allowlisted(env: Env, merchant: Address, owner: Address, allowed: bool) {
+        merchant.require_auth();
+        let key = MerchantKey::Allowlisted(merchant.clone(), owner.clone());
+        if allowed {
+            env.storage().persistent().set(&key, &());
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        AllowlistUpdated {
+            merchant,
+            owner,
+            allowed,
+        }
+        .publish(&env);
+    }
+
+    /// Whether `owner` is on the merchant's allowlist.
+    pub fn is_allowlisted(env: Env, merchant: Address, owner: Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&MerchantKey::Allowlisted(merchant, owner))
+    }
+
+    /// Internal helper – aborts if the merchant's allowlist is on and
+    /// `owner` isn't on it.
+    fn require_allowlisted(env: &Env, merchant: &Address, owner: &Address) {
+        if Self::get_allowlist_mode(env.clone(), merchant.clone())
+            && !Self::is_allowlisted(env.clone(), merchant.clone(), owner.clone())
+        {
+            panic!("Owner is not on the merchant's allowlist");
+        }
+    }
+
+    // ── Subscriptions per owner ───────────────────────────────────
+
+    /// Let each owner hold up to `limit` active subscriptions with the
+    /// merchant at once; `DEFAULT_MAX_OWNER_SUBS` until set. Owners already
+    /// over it keep what they have. Merchant only.
+    pub fn set_max_owner_subs(env: Env, merchant: Address, limit: u32) {
+        merchant.require_auth();
+        if limit == 0 {
+            panic!("Invalid subscription limit");
+        }
+        env.storage()
+            .persistent()
+            .set(&MerchantKey::MaxOwnerSubs(merchant), &limit);
+    }
+
+    /// Active subscriptions an owner may hold with the merchant.
+    pub fn get_max_owner_subs(env: Env, merchant: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::MaxOwnerSubs(merchant))
+            .unwrap_or(DEFAULT_MAX_OWNER_SUBS)
+    }
+
+    /// Subscriptions `owner` holds with the merchant that haven't failed
+    /// or completed. Offers count once accepted.
+    pub fn get_owner_sub_count(env: Env, merchant: Address, owner: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::OwnerSubs(merchant, owner))
+            .unwrap_or(0)
+    }
+
+    /// Internal helper – counts a new active subscription. Aborts with
+    /// `Error::TooManySubscriptions` if the owner is at the merchant's
+    /// limit.
+    fn claim_owner_slot(env: &Env, merchant: &Address, owner: &Address) {
+        let count = Self::get_owner_sub_count(env.clone(), merchant.clone(), owner.clone());
+        if count >= Self::get_max_owner_subs(env.clone(), merchant.clone()) {
+            panic_with_error!(env, Error::TooManySubscriptions);
+        }
+        env.storage().persistent().set(
+            &MerchantKey::OwnerSubs(merchant.clone(), owner.clone()),
+            &(count + 1),
+        );
+    }
+
+    /// Internal helper – stops counting a subscription that failed,
+    /// completed or was replaced.
+    fn release_owner_slot(env: &Env, merchant: &Address, owner: &Address) {
+        let key = MerchantKey::OwnerSubs(merchant.clone(), owner.clone());
+        let count: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        if count > 1 {
+            env.storage().persistent().set(&key, &(count - 1));
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Internal helper – whether `data` holds one of its owner's slots.
+    fn holds_owner_slot(data: &SubscriptionData) -> bool {
+        !matches!(
+            data.state,
+            SubscriptionState::PendingAcceptance
+                | SubscriptionState::Failed
+                | SubscriptionState::Completed
+        )
+    }
+
+    // ── Merchant health ───────────────────────────────────────────
+
+    /// The merchant's renewal failure rate over the last two health
+    /// windows, and its re-verification deadline if the rate got too high.
+    pub fn get_merchant_health(env: Env, merchant: Address) -> MerchantHealth {
+        let window = env.ledger().sequence() / HEALTH_WINDOW_LEDGERS;
+        let mut health = MerchantHealth {
+            attempts: 0,
+            failures: 0,
+            failure_bps: 0,
+            reverify_by: env
+                .storage()
+                .persistent()
+                .get(&MerchantKey::ReverifyBy(merchant.clone())),
+        };
+        for window in [window.checked_sub(1), Some(window)].into_iter().flatten() {
+            let counts: HealthCounts = env
+                .storage()
+                .persistent()
+                .get(&MerchantKey::Health(merchant.clone(), window))
+                .unwrap_or_default();
+            health.attempts += counts.attempts;
+            health.failures += counts.failures;
+        }
+        if health.attempts > 0 {
+            health.failure_bps =
+                (health.failures as u64 * MAX_FEE_BPS as u64 / health.attempts as u64) as u32;
+        }
+        health
+    }
+
+    /// Confirm the merchant fixed its billing setup after being asked to
+    /// re-verify, lifting any suspension and starting its health record
+    /// over. Merchant only.
+    pub fn reverify_merchant(env: Env, merchant: Address) {
+        merchant.require_auth();
+        let key = MerchantKey::ReverifyBy(merchant.clone());
+        if !env.storage().persistent().has(&key) {
+            panic!("Re-verification not required");
+        }
+        env.storage().persistent().remove(&key);
+        let window = env.ledger().sequence() / HEALTH_WINDOW_LEDGERS;
+        for window in [window.checked_sub(1), Some(window)].into_iter().flatten() {
+            env.storage()
+                .persistent()
+                .remove(&MerchantKey::Health(merchant.clone(), window));
+        }
+        MerchantReverified { merchant }.publish(&env);
+    }
+
+    /// Internal helper – whether the merchant let its re-verification
+    /// deadline pass.
+    fn merchant_suspended(env: &Env, merchant: &Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::ReverifyBy(merchant.clone()))
+            .is_some_and(|by: u32| env.ledger().sequence() > by)
+    }
+
+    /// Internal helper – counts a charged renewal attempt, asking the
+    /// merchant to re-verify once its failure rate reaches
+    /// `FAILURE_ALERT_BPS`. A subscription counts at most one failed
+    /// attempt per window, so no single subscriber can sink its merchant.
+    fn record_merchant_outcome(env: &Env, merchant: &Address, sub_id: u64, renewed: bool) {
+        let window = env.ledger().sequence() / HEALTH_WINDOW_LEDGERS;
+        if !renewed {
+            let failure_key = SubKey::HealthFailure(sub_id);
+            if env.storage().persistent().get(&failure_key) == Some(window) {
+                return;
+            }
+            env.storage().persistent().set(&failure_key, &window);
+        }
+        let key = MerchantKey::Health(merchant.clone(), window);
+        let mut counts: HealthCounts = env.storage().persistent().get(&key).unwrap_or_default();
+        counts.attempts += 1;
+        if !renewed {
+            counts.failures += 1;
+        }
+        env.storage().persistent().set(&key, &counts);
+        if renewed {
+            return;
+        }
+
+        let health = Self::get_merchant_health(env.clone(), merchant.clone());
+        if health.reverify_by.is_none()
+            && health.attempts >= MIN_HEALTH_ATTEMPTS
+            && health.failure_bps >= FAILURE_ALERT_BPS
+        {
+            let reverify_by = env.ledger().sequence() + REVERIFY_GRACE_LEDGERS;
+            env.storage()
+                .persistent()
+                .set(&MerchantKey::ReverifyBy(merchant.clone()), &reverify_by);
+            MerchantReverifyRequired {
+                merchant: merchant.clone(),
+                failure_bps: health.failure_bps,
+                reverify_by,
+            }
+            .publish(env);
+        }
+    }
+
+    // ── Executor management ───────────────────────────────────────
+
+    /// Assign executor for subscription (owner only)
+    pub fn set_executor(env: Env, sub_id: u64, executor: Address) {
+        Self::set_executor_impl(env, sub_id, executor, None);
+    }
+
+    /// Internal helper – the body of `set_executor`, skipping the owner's
+    /// auth if they are the `signer` of the enclosing call.
+    fn set_executor_impl(env: Env, sub_id: u64, executor: Address, signer: Option<&Address>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+
+        Self::require_auth_of(&data.owner, signer);
+        Self::require_not_frozen(&env, sub_id);
+
+        let key = ExecutorKey { sub_id };
+        env.storage().persistent().set(&key, &executor);
+
+        ExecutorAssigned { sub_id, executor }.publish(&env);
+    }
+
+    /// Remove executor (owner only)
+    pub fn remove_executor(env: Env, sub_id: u64) {
+        Self::remove_executor_impl(env, sub_id, None);
+    }
+
+    /// Internal helper – the body of `remove_executor`, skipping the
+    /// owner's auth if they are the `signer` of the enclosing call.
+    fn remove_executor_impl(env: Env, sub_id: u64, signer: Option<&Address>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+
+        Self::require_auth_of(&data.owner, signer);
+
+        let key = ExecutorKey { sub_id };
+        env.storage().persistent().remove(&key);
+
+        ExecutorRemoved { sub_id }.publish(&env);
+    }
+
+    /// Get executor for subscription
+    pub fn get_executor(env: Env, sub_id: u64) -> Option<Address> {
+        let key = ExecutorKey { sub_id };
+        env.storage().persistent().get(&key)
+    }
+
+    // ── Joint ownership ───────────────────────────────────────────
+
+    /// Make the subscription jointly owned by `owners`, or change its
+    /// owner set. `signers` must satisfy the current owners: the owner
+    /// alone for a single-owner subscription, otherwise the threshold.
+    /// The original owner stays the paying account.
+    pub fn set_joint_owners(
+        env: Env,
+        sub_id: u64,
+        signers: Vec<Address>,
+        owners: Vec<Address>,
+        threshold: u32,
+    ) {
+        Self::require_owners(&env, sub_id, &signers);
+        if threshold == 0 || threshold > owners.len() {
+            panic!("Invalid threshold");
+        }
+        for (i, owner) in owners.iter().enumerate() {
+            if owners.first_index_of(&owner) != Some(i as u32) {
+                panic!("Duplicate owner");
+            }
+        }
+
+        env.storage().persistent().set(
+            &SubKey::JointOwners(sub_id),
+            &JointOwners {
+                owners: owners.clone(),
+                threshold,
+            },
+        );
+        JointOwnersSet {
+            sub_id,
+            owners,
+            threshold,
+        }
+        .publish(&env);
+    }
+
+    /// Co-owners of a joint subscription, if it is one.
+    pub fn get_joint_owners(env: Env, sub_id: u64) -> Option<JointOwners> {
+        env.storage().persistent().get(&SubKey::JointOwners(sub_id))
+    }
+
+    /// Internal helper – requires owner-level consent for `sub_id`: the
+    /// threshold of distinct co-owners among `signers` for a joint
+    /// subscription, else the single owner.
+    fn require_owners(env: &Env, sub_id: u64, signers: &Vec<Address>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        let joint = match Self::get_joint_owners(env.clone(), sub_id) {
+            Some(joint) => joint,
+            None => {
+                data.owner.require_auth();
+                return;
+            }
+        };
+        for (i, signer) in signers.iter().enumerate() {
+            if !joint.owners.contains(&signer) {
+                panic!("Signer is not a co-owner");
+            }
+            if signers.first_index_of(&signer) != Some(i as u32) {
+                panic!("Duplicate signer");
+            }
+            signer.require_auth();
+        }
+        if signers.len() < joint.threshold {
+            panic!("Not enough co-owner signatures");
+        }
+    }
+
+    /// Pause renewals of a subscription. Any single owner or co-owner may
+    /// pause.
+    pub fn pause_sub(env: Env, caller: Address, sub_id: u64) {
+        caller.require_auth();
+        let data = Self::get_sub(env.clone(), sub_id);
+        let co_owner = Self::get_joint_owners(env.clone(), sub_id)
+            .is_some_and(|joint| joint.owners.contains(&caller));
+        if caller != data.owner && !co_owner {
+            panic!("Unauthorized: caller must be an owner");
+        }
+        if !Self::is_sub_paused(env.clone(), sub_id) {
+            Self::start_limited_pause(&env, sub_id, &data);
+        }
+        env.storage()
+            .persistent()
+            .set(&SubKey::Paused(sub_id), &true);
+        SubscriptionPaused { sub_id, by: caller }.publish(&env);
+    }
+
+    /// Resume a paused subscription. Takes the same consent as creating an
+    /// approval.
+    pub fn resume_sub(env: Env, sub_id: u64, signers: Vec<Address>) {
+        Self::require_owners(&env, sub_id, &signers);
+        Self::end_limited_pause(&env, sub_id);
+        env.storage().persistent().remove(&SubKey::Paused(sub_id));
+        SubscriptionResumed { sub_id }.publish(&env);
+    }
+
+    /// Resume a pause that ran past its allowance; see
+    /// `get_pause_deadline`. Merchant only.
+    pub fn end_overdue_pause(env: Env, merchant: Address, sub_id: u64) {
+        merchant.require_auth();
+        let data = Self::get_sub(env.clone(), sub_id);
+        if merchant != data.merchant {
+            panic!("Unauthorized: caller must be the merchant");
+        }
+        match Self::get_pause_deadline(env.clone(), sub_id) {
+            Some(resume_by) if env.ledger().sequence() > resume_by => {}
+            _ => panic!("Pause is within its allowance"),
+        }
+        Self::end_limited_pause(&env, sub_id);
+        env.storage().persistent().remove(&SubKey::Paused(sub_id));
+        SubscriptionResumed { sub_id }.publish(&env);
+    }
+
+    /// Last ledger the current pause is within the merchant's allowance,
+    /// if it is counted against `PauseLimits`.
+    pub fn get_pause_deadline(env: Env, sub_id: u64) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::LimitedPause(sub_id))
+            .map(|pause: LimitedPause| pause.resume_by)
+    }
+
+    /// Limit how long and how many of `merchant`'s subscriptions owners
+    /// may pause, or lift the limits with `None`. Pauses already running
+    /// keep the terms they started under. Merchant only.
+    pub fn set_pause_limits(env: Env, merchant: Address, limits: Option<PauseLimits>) {
+        merchant.require_auth();
+        let key = MerchantKey::PauseLimits(merchant);
+        match limits {
+            Some(limits) => env.storage().persistent().set(&key, &limits),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Get the merchant's pause limits, if it set any.
+    pub fn get_pause_limits(env: Env, merchant: Address) -> Option<PauseLimits> {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::PauseLimits(merchant))
+    }
+
+    /// Internal helper – checks a new pause against the merchant's limits
+    /// and counts it. Aborts with `Error::PauseQuotaExhausted` if the
+    /// subscription has no paused time left in the window, or
+    /// `Error::TooManyPauses` if the owner is at the concurrent limit.
+    fn start_limited_pause(env: &Env, sub_id: u64, data: &SubscriptionData) {
+        let limits = match Self::get_pause_limits(env.clone(), data.merchant.clone()) {
+            Some(limits) => limits,
+            None => return,
+        };
+
+        let now = env.ledger().sequence();
+        let usage_key = SubKey::PauseUsage(sub_id);
+        let usage = env
+            .storage()
+            .persistent()
+            .get(&usage_key)
+            .filter(|usage: &PauseUsage| {
+                now < usage
+                    .window_start
+                    .saturating_add(PAUSE_QUOTA_WINDOW_LEDGERS)
+            })
+            .unwrap_or(PauseUsage {
+                window_start: now,
+                paused_ledgers: 0,
+            });
+        if usage.paused_ledgers >= limits.max_paused_ledgers {
+            panic_with_error!(env, Error::PauseQuotaExhausted);
+        }
+
+        let count_key = MerchantKey::OwnerPauses(data.merchant.clone(), data.owner.clone());
+        let count: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+        if count >= limits.max_concurrent {
+            panic_with_error!(env, Error::TooManyPauses);
+        }
+
+        env.storage().persistent().set(&count_key, &(count + 1));
+        env.storage().persistent().set(
+            &SubKey::LimitedPause(sub_id),
+            &LimitedPause {
+                started: now,
+                owner: data.owner.clone(),
+                resume_by: now.saturating_add(limits.max_paused_ledgers - usage.paused_ledgers),
+            },
+        );
+        env.storage().persistent().set(&usage_key, &usage);
+    }
+
+    /// Internal helper – charges a limited pause's length to the
+    /// subscription's allowance and releases its concurrent slot.
+    fn end_limited_pause(env: &Env, sub_id: u64) {
+        let pause_key = SubKey::LimitedPause(sub_id);
+        let pause: LimitedPause = match env.storage().persistent().get(&pause_key) {
+            Some(pause) => pause,
+            None => return,
+        };
+        env.storage().persistent().remove(&pause_key);
+
+        let data = Self::get_sub(env.clone(), sub_id);
+        let count_key = MerchantKey::OwnerPauses(data.merchant, pause.owner);
+        let count: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+        if count > 1 {
+            env.storage().persistent().set(&count_key, &(count - 1));
+        } else {
+            env.storage().persistent().remove(&count_key);
+        }
+
+        let usage_key = SubKey::PauseUsage(sub_id);
+        if let Some(mut usage) = env.storage().persistent().get::<_, PauseUsage>(&usage_key) {
+            let paused = env.ledger().sequence() - pause.started;
+            usage.paused_ledgers = usage.paused_ledgers.saturating_add(paused);
+            env.storage().persistent().set(&usage_key, &usage);
+        }
+    }
+
+    /// Whether the subscription's owners paused it.
+    pub fn is_sub_paused(env: Env, sub_id: u64) -> bool {
+        env.storage().persistent().has(&SubKey::Paused(sub_id))
+    }
+
+    // ── Owner recovery ────────────────────────────────────────────
+
+    /// Register a recovery guardian who can move the subscription to a new
+    /// owner `delay_ledgers` after requesting it, or remove it with `None`.
+    /// Any pending recovery is dropped. Owner only.
+    pub fn set_recovery_guardian(env: Env, sub_id: u64, guardian: Option<RecoveryGuardian>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        env.storage().persistent().remove(&SubKey::Recovery(sub_id));
+        match guardian {
+            Some(guardian) => env
+                .storage()
+                .persistent()
+                .set(&SubKey::RecoveryGuardian(sub_id), &guardian),
+            None => env
+                .storage()
+                .persistent()
+                .remove(&SubKey::RecoveryGuardian(sub_id)),
+        }
+    }
+
+    /// Recovery guardian of a subscription, if any.
+    pub fn get_recovery_guardian(env: Env, sub_id: u64) -> Option<RecoveryGuardian> {
+        env.storage()
+            .persistent()
+            .get(&SubKey::RecoveryGuardian(sub_id))
+    }
+
+    /// Start moving the subscription to `new_owner`. Guardian only; the
+    /// owner can cancel until the delay has passed.
+    pub fn request_recovery(env: Env, sub_id: u64, new_owner: Address) {
+        let guardian =
+            Self::get_recovery_guardian(env.clone(), sub_id).expect("No recovery guardian");
+        guardian.guardian.require_auth();
+
+        let executable_at = env.ledger().sequence() + guardian.delay_ledgers;
+        env.storage().persistent().set(
+            &SubKey::Recovery(sub_id),
+            &PendingRecovery {
+                new_owner: new_owner.clone(),
+                executable_at,
+            },
+        );
+        RecoveryRequested {
+            sub_id,
+            new_owner,
+            executable_at,
+        }
+        .publish(&env);
+    }
+
+    /// Cancel a pending recovery. Owner only.
+    pub fn cancel_recovery(env: Env, sub_id: u64) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        if !env.storage().persistent().has(&SubKey::Recovery(sub_id)) {
+            panic!("No pending recovery");
+        }
+        env.storage().persistent().remove(&SubKey::Recovery(sub_id));
+        RecoveryCancelled { sub_id }.publish(&env);
+    }
+
+    /// Get the pending recovery, if any.
+    pub fn get_pending_recovery(env: Env, sub_id: u64) -> Option<PendingRecovery> {
+        env.storage().persistent().get(&SubKey::Recovery(sub_id))
+    }
+
+    /// Rotate the owner once the recovery delay has passed. Guardian only.
+    /// A joint subscription swaps the old owner for the new one in its
+    /// owner set.
+    pub fn execute_recovery(env: Env, sub_id: u64) {
+        let guardian =
+            Self::get_recovery_guardian(env.clone(), sub_id).expect("No recovery guardian");
+        guardian.guardian.require_auth();
+        Self::require_not_frozen(&env, sub_id);
+
+        let pending = Self::get_pending_recovery(env.clone(), sub_id).expect("No pending recovery");
+        if env.ledger().sequence() < pending.executable_at {
+            panic!("Recovery delay not elapsed");
+        }
+        env.storage().persistent().remove(&SubKey::Recovery(sub_id));
+
+        let mut data = Self::get_sub(env.clone(), sub_id);
+        let old_owner = data.owner.clone();
+        data.owner = pending.new_owner.clone();
+        env.storage().persistent().set(&sub_id, &data);
+        if Self::holds_owner_slot(&data) {
+            // The slot moves with the subscription, even past the limit
+            Self::release_owner_slot(&env, &data.merchant, &old_owner);
+            let count =
+                Self::get_owner_sub_count(env.clone(), data.merchant.clone(), data.owner.clone());
+            env.storage().persistent().set(
+                &MerchantKey::OwnerSubs(data.merchant.clone(), data.owner.clone()),
+                &(count + 1),
+            );
+        }
+
+        if let Some(mut joint) = Self::get_joint_owners(env.clone(), sub_id) {
+            if let Some(i) = joint.owners.first_index_of(&old_owner) {
+                if joint.owners.contains(&pending.new_owner) {
+                    joint.owners.remove(i);
+                } else {
+                    joint.owners.set(i, pending.new_owner.clone());
+                }
+                if joint.threshold > joint.owners.len() {
+                    joint.threshold = joint.owners.len();
+                }
+                env.storage()
+                    .persistent()
+                    .set(&SubKey::JointOwners(sub_id), &joint);
+            }
+        }
+
+        OwnerRecovered {
+            sub_id,
+            old_owner,
+            new_owner: pending.new_owner,
+        }
+        .publish(&env);
+    }
+
+    // ── Approval management ───────────────────────────────────────
+
+    /// Create a renewal approval for a subscription
+    pub fn approve_renewal(
+        env: Env,
+        sub_id: u64,
+        approval_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+    ) {
+        Self::approve_renewal_impl(env, sub_id, approval_id, max_spend, expires_at, None);
+    }
+
+    /// Internal helper – the body of `approve_renewal`, skipping the
+    /// owner's auth if they are the `signer` of the enclosing call.
+    fn approve_renewal_impl(
+        env: Env,
+        sub_id: u64,
+        approval_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+        signer: Option<&Address>,
+    ) {
+        let data = Self::get_sub(env.clone(), sub_id);
+
+        if env.storage().persistent().has(&SubKey::JointOwners(sub_id)) {
+            panic!("Joint subscription: use approve_renewal_joint");
+        }
+        Self::require_auth_of(&data.owner, signer);
+        Self::create_approval(&env, sub_id, approval_id, max_spend, expires_at);
+    }
+
+    /// Create a renewal approval for a joint subscription, signed by the
+    /// threshold of co-owners.
+    pub fn approve_renewal_joint(
+        env: Env,
+        sub_id: u64,
+        signers: Vec<Address>,
+        approval_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+    ) {
+        if !env.storage().persistent().has(&SubKey::JointOwners(sub_id)) {
+            panic!("Subscription is not jointly owned");
+        }
+        Self::require_owners(&env, sub_id, &signers);
+        Self::create_approval(&env, sub_id, approval_id, max_spend, expires_at);
+    }
+
+    /// Create approvals across several of `owner`'s subscriptions under one
+    /// signature, at most `MAX_APPROVAL_BATCH`. A request that can't be
+    /// granted is reported and skipped rather than failing the rest.
+    /// Returns one result per request, in order.
+    pub fn approve_renewals(
+        env: Env,
+        owner: Address,
+        requests: Vec<ApprovalRequest>,
+    ) -> Vec<ApprovalResult> {
+        if requests.len() > MAX_APPROVAL_BATCH {
+            panic!("Too many approval requests");
+        }
+        owner.require_auth();
+
+        let mut results = Vec::new(&env);
+        for request in requests.iter() {
+            let sub_id = request.sub_id;
+            let result = match Self::load_sub(&env, sub_id) {
+                None => ApprovalResult::NotFound,
+                Some(data) if data.owner != owner => ApprovalResult::NotOwner,
+                Some(_) if env.storage().persistent().has(&SubKey::JointOwners(sub_id)) => {
+                    ApprovalResult::JointlyOwned
+                }
+                Some(_) if env.storage().persistent().has(&SubKey::Frozen(sub_id)) => {
+                    ApprovalResult::Frozen
+                }
+                Some(_) => {
+                    let stored = Self::create_approval(
+                        &env,
+                        sub_id,
+                        request.approval_id,
+                        request.max_spend,
+                        request.expires_at,
+                    );
+                    if stored {
+                        ApprovalResult::Approved
+                    } else {
+                        ApprovalResult::AwaitingGuardian
+                    }
+                }
+            };
+            results.push_back(result);
+        }
+        results
+    }
+
+    /// Internal helper – stores a new approval once the owners consented,
+    /// or parks it for the guardian if the co-sign rule covers it. Returns
+    /// whether the approval is usable now.
+    fn create_approval(
+        env: &Env,
+        sub_id: u64,
+        approval_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+    ) -> bool {
+        Self::require_not_frozen(env, sub_id);
+        let data = Self::get_sub(env.clone(), sub_id);
+        Self::require_sane_amount(env, &data.token, max_spend);
+
+        let approval = RenewalApproval {
+            sub_id,
+            merchant: data.merchant,
+            token: data.token,
+            max_spend,
+            expires_at,
+            used: false,
+        };
+
+        if let Some(rule) = Self::get_cosign_rule(env.clone(), sub_id) {
+            if max_spend > rule.min_amount {
+                env.storage()
+                    .persistent()
+                    .set(&CosignKey::Pending(sub_id, approval_id), &approval);
+                ApprovalPending {
+                    sub_id,
+                    approval_id,
+                    max_spend,
+                    guardian: rule.guardian,
+                }
+                .publish(env);
+                return false;
+            }
+        }
+        Self::store_approval(env, approval_id, approval);
+        true
+    }
+
+    /// Internal helper – makes `approval` usable under `approval_id`.
+    fn store_approval(env: &Env, approval_id: u64, approval: RenewalApproval) {
+        let RenewalApproval {
+            sub_id,
+            max_spend,
+            expires_at,
+            ..
+        } = approval;
+
+        let key = ApprovalKey {
+            sub_id,
+            approval_id,
+        };
+        env.storage().persistent().set(&key, &approval);
+        // A re-issued approval starts unused
+        env.storage()
+            .persistent()
+            .remove(&SubKey::ApprovalUsage(sub_id, approval_id));
+
+        ApprovalCreated {
+            sub_id,
+            approval_id,
+            max_spend,
+            expires_at,
+        }
+        .publish(env);
+    }
+
+    /// Require `rule.guardian` to countersign approvals above
+    /// `rule.min_amount`, or drop the requirement with `None`. Set by the
+    /// owner; once a rule exists its guardian must also sign any change.
+    pub fn set_cosign_rule(env: Env, sub_id: u64, rule: Option<CosignRule>) {
+        let data = Self::get_sub(env.clone(), sub_id);
+        data.owner.require_auth();
+        if let Some(current) = Self::get_cosign_rule(env.clone(), sub_id) {
+            current.guardian.require_auth();
+        }
+
+        let key = CosignKey::Rule(sub_id);
+        match rule {
+            Some(rule) => {
+                if rule.min_amount < 0 {
+                    panic!("Invalid co-sign threshold");
+                }
+                env.storage().persistent().set(&key, &rule);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Co-sign rule of a subscription, if any.
+    pub fn get_cosign_rule(env: Env, sub_id: u64) -> Option<CosignRule> {
+        env.storage().persistent().get(&CosignKey::Rule(sub_id))
+    }
+
+    /// Approval waiting for the guardian's countersignature, if any.
+    pub fn get_pending_approval(
+        env: Env,
+        sub_id: u64,
+        approval_id: u64,
+    ) -> Option<RenewalApproval> {
+        Self::load_approval(&env, &CosignKey::Pending(sub_id, approval_id))
+    }
+
+    /// Countersign a pending approval, making it usable. Guardian only.
+    pub fn countersign_approval(env: Env, sub_id: u64, approval_id: u64) {
+        let rule = Self::get_cosign_rule(env.clone(), sub_id).expect("No co-sign rule");
+        rule.guardian.require_auth();
+        Self::require_not_frozen(&env, sub_id);
+
+        let key = CosignKey::Pending(sub_id, approval_id);
+        let approval = Self::load_approval(&env, &key).expect("No pending approval");
+        env.storage().persistent().remove(&key);
+        Self::store_approval(&env, approval_id, approval);
+    }
+
+    /// Delete a pending approval that expired before the guardian
+    /// countersigned it. Anyone may call.
+    pub fn expire_pending_approval(env: Env, sub_id: u64, approval_id: u64) {
+        let key = CosignKey::Pending(sub_id, approval_id);
+        let approval = Self::load_approval(&env, &key).expect("No pending approval");
+        if env.ledger().sequence() <= approval.expires_at {
+            panic!("Approval not expired");
+        }
+        env.storage().persistent().remove(&key);
+        PendingApprovalExpired {
+            sub_id,
+            approval_id,
+        }
+        .publish(&env);
+    }
+
+    /// Get a renewal approval
+    pub fn get_approval(env: Env, sub_id: u64, approval_id: u64) -> Option<RenewalApproval> {
+        let key = ApprovalKey {
+            sub_id,
+            approval_id,
+        };
+        Self::load_approval(&env, &key)
+    }
+
+    /// What approval `approval_id` of `sub_id` has been charged and can
+    /// still authorize, if it exists.
+    pub fn get_approval_usage(env: Env, sub_id: u64, approval_id: u64) -> Option<ApprovalUsage> {
+        let approval = Self::get_approval(env.clone(), sub_id, approval_id)?;
+        let mut usage: ApprovalUsage = env
+            .storage()
+            .persistent()
+            .get(&SubKey::ApprovalUsage(sub_id, approval_id))
+            .unwrap_or_default();
+        if !approval.used && env.ledger().sequence() <= approval.expires_at {
+            usage.remaining = approval.max_spend;
+        }
+        Some(usage)
+    }
+
+    /// Internal helper – reads an approval. Approvals stored before they
+    /// were bound to a merchant and token are treated as missing, so they
+    /// can't pay a subscription whose terms changed; the owner re-approves.
+    fn load_approval<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> Option<RenewalApproval> {
+        let raw: Val = env.storage().persistent().get(key)?;
+        let fields: Map<Symbol, Val> = raw.into_val(env);
+        if !fields.contains_key(symbol_short!("merchant")) {
+            return None;
+        }
+        Some(raw.into_val(env))
+    }
+
+    /// Internal helper – first phase of spending an approval: validates it
+    /// for `amount` without marking it used. Returns `None` (and emits
+    /// `ApprovalRejected`) if the approval is missing, already used,
+    /// expired, below the requested amount, or was given for another
+    /// merchant or token than `data` now has. The renewal then either
+    /// commits the approval or releases it.
+    fn reserve_approval(
+        env: &Env,
+        data: &SubscriptionData,
+        sub_id: u64,
+        approval_id: u64,
+        amount: i128,
+    ) -> Option<RenewalApproval> {
+        let key = ApprovalKey {
+            sub_id,
+            approval_id,
+        };
+
+        match Self::check_approval(env, data, &key, amount) {
+            Ok(approval) => Some(approval),
+            Err(reason) => {
+                ApprovalRejected {
+                    sub_id,
+                    approval_id,
+                    reason: reason as u32,
+                }
+                .publish(env);
+                None
+            }
+        }
+    }
+
+    /// Internal helper – why the approval at `key` can't pay `amount` for
+    /// `data`, if it can't.
+    fn check_approval(
+        env: &Env,
+        data: &SubscriptionData,
+        key: &ApprovalKey,
+        amount: i128,
+    ) -> Result<RenewalApproval, ApprovalRejection> {
+        let approval = Self::load_approval(env, key).ok_or(ApprovalRejection::NotFound)?;
+
+        if approval.merchant != data.merchant || approval.token != data.token {
+            return Err(ApprovalRejection::TermsChanged);
+        }
+
+        if approval.used {
+            return Err(ApprovalRejection::Used);
+        }
+
+        if env.ledger().sequence() > approval.expires_at {
+            return Err(ApprovalRejection::Expired);
+        }
+
+        if amount > approval.max_spend {
+            return Err(ApprovalRejection::AmountExceeded);
+        }
+
+        Ok(approval)
+    }
+
+    /// Internal helper – marks a reserved approval used once the renewal
+    /// it was reserved for succeeded, recording the `charged` amount.
+    fn commit_approval(
+        env: &Env,
+        sub_id: u64,
+        approval_id: u64,
+        mut approval: RenewalApproval,
+        charged: i128,
+    ) {
+        let key = ApprovalKey {
+            sub_id,
+            approval_id,
+        };
+
+        #[cfg(any(test, feature = "invariants"))]
+        let previous = approval.clone();
+
+        approval.used = true;
+        env.storage().persistent().set(&key, &approval);
+        Self::update_approval_usage(env, sub_id, approval_id, |usage| {
+            usage.attempts += 1;
+            usage.charged = charged;
+            usage.unspent = approval.max_spend - charged;
+        });
+
+        #[cfg(any(test, feature = "invariants"))]
+        invariants::check_consumed_approval(env, &key, &previous);
+    }
+
+    /// Internal helper – applies `update` to the stored usage of an
+    /// approval.
+    fn update_approval_usage(
+        env: &Env,
+        sub_id: u64,
+        approval_id: u64,
+        update: impl FnOnce(&mut ApprovalUsage),
+    ) {
+        let key = SubKey::ApprovalUsage(sub_id, approval_id);
+        let mut usage: ApprovalUsage = env.storage().persistent().get(&key).unwrap_or_default();
+        update(&mut usage);
+        env.storage().persistent().set(&key, &usage);
+    }
+
+    /// Internal helper – hands a reserved approval back after a failed
+    /// renewal, so a transient payment failure doesn't burn it and the
+    /// retry can use it again.
+    fn release_approval(env: &Env, sub_id: u64, approval_id: u64) {
+        Self::update_approval_usage(env, sub_id, approval_id, |usage| {
+            usage.attempts += 1;
+            usage.failures += 1;
+        });
+        ApprovalReleased {
+            sub_id,
+            approval_id,
+        }
+        .publish(env);
+    }
+
+    // ── Webhook keys ──────────────────────────────────────────────
+
+    /// Register the hash of the secret `merchant`'s webhooks are signed
+    /// with, replacing any earlier key. Returns the new key's version, which
+    /// the webhook bridge includes in each delivery so the merchant knows
+    /// which secret to verify it with. Merchant only.
+    pub fn set_webhook_key(env: Env, merchant: Address, key_hash: BytesN<32>) -> u32 {
+        merchant.require_auth();
+        let version =
+            Self::get_webhook_key(env.clone(), merchant.clone()).map_or(1, |key| key.version + 1);
+
+        env.storage().persistent().set(
+            &MerchantKey::WebhookKey(merchant.clone()),
+            &WebhookKey {
+                key_hash: key_hash.clone(),
+                version,
+                set_at: env.ledger().sequence(),
+            },
+        );
+        WebhookKeySet {
+            merchant,
+            version,
+            key_hash,
+        }
+        .publish(&env);
+        version
+    }
+
+    /// Get the merchant's current webhook signing key, if it registered one.
+    pub fn get_webhook_key(env: Env, merchant: Address) -> Option<WebhookKey> {
+        env.storage()
+            .persistent()
+            .get(&MerchantKey::WebhookKey(merchant))
+    }
+
+    // ── Fee management ────────────────────────────────────────────
+
+    /// Set the protocol fee (in basis points) and its recipient. Admin only.
+    pub fn set_fee_config(env: Env, percentage: u32, recipient: Address) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_fee_config(env.clone());
+        let config = Self::check_fee_config(percentage, &recipient);
+        env.storage()
+            .instance()
+            .set(&ContractKey::FeeConfig, &config);
+
+        FeeConfigUpdated {
+            percentage,
+            recipient,
+        }
+        .publish(&env);
+        Self::admin_action(&env, symbol_short!("fee_cfg"), admin, old, Some(config));
+    }
+
+    /// Dry run of `set_fee_config`: validates the new fee and returns what
+    /// the call would change, without storing it.
+    pub fn simulate_fee_config(env: Env, percentage: u32, recipient: Address) -> AdminEffect {
+        let old = Self::get_fee_config(env.clone());
+        let config = Self::check_fee_config(percentage, &recipient);
+        Self::admin_effect(&env, symbol_short!("fee_cfg"), old, Some(config))
+    }
+
+    /// Internal helper – the config `set_fee_config` would store.
+    fn check_fee_config(percentage: u32, recipient: &Address) -> FeeConfig {
+        if percentage > MAX_FEE_BPS {
+            panic!("Fee percentage exceeds 100%");
+        }
+        FeeConfig {
+            percentage,
+            recipient: recipient.clone(),
+        }
+    }
+
+    /// Get the current fee configuration, if any.
+    pub fn get_fee_config(env: Env) -> Option<FeeConfig> {
+        env.storage().instance().get(&ContractKey::FeeConfig)
+    }
+
+    // ── Renewal logic ─────────────────────────────────────────────
+
+    /// Internal helper – whether the owner's early retry for the cycle
+    /// `data` is renewing is still unused.
+    fn cooldown_bypass_available(env: &Env, sub_id: u64, data: &SubscriptionData) -> bool {
+        env.storage()
+            .persistent()
+            .get(&SubKey::CooldownBypass(sub_id))
+            != Some(data.renewal_seq)
+    }
+
+    /// Internal helper – spends the owner's early retry for the cycle, if
+    /// it is still unused.
+    fn take_cooldown_bypass(env: &Env, sub_id: u64, data: &SubscriptionData) -> bool {
+        if !Self::cooldown_bypass_available(env, sub_id, data) {
+            return false;
+        }
+        env.storage()
+            .persistent()
+            .set(&SubKey::CooldownBypass(sub_id), &data.renewal_seq);
+        CooldownBypassed { sub_id }.publish(env);
+        true
+    }
+
+    /// Run the checks `renew` makes for `caller` renewing `sub_id` with
+    /// `approval_id` and `amount`, without changing anything. The
+    /// subscription's retry policy, if any, overrides `cooldown_ledgers` as
+    /// it does in `renew`. The compliance screen isn't simulated.
+    pub fn simulate_renew(
+        env: Env,
+        caller: Address,
+        sub_id: u64,
+        approval_id: u64,
+        amount: i128,
+        cooldown_ledgers: u32,
+    ) -> RenewDiagnostics {
+        let data = Self::get_sub(env.clone(), sub_id);
+        let schedule = Self::get_schedule(env.clone(), sub_id);
+        let now = env.ledger().sequence();
+        let amount = Self::quote_for(&env, sub_id).unwrap_or(amount);
+
+        let executor: Option<Address> = env.storage().persistent().get(&ExecutorKey { sub_id });
+        let caller_allowed = caller == data.owner
+            || Some(caller.clone()) == executor
+            || Self::is_active_keeper(&env, &caller);
+
+        let cycle = match &schedule {
+            None => CycleStatus::Unscheduled,
+            Some(schedule) if now > schedule.next_due + schedule.window_ledgers => {
+                CycleStatus::Missed
+            }
+            Some(schedule) if now >= schedule.next_due => CycleStatus::Due,
+            Some(schedule)
+                if caller == data.owner
+                    && now + schedule.interval_ledgers >= schedule.next_due
+                    && Self::get_early_discount(env.clone(), data.merchant.clone()).is_some() =>
+            {
+                CycleStatus::Early
+            }
+            Some(_) => CycleStatus::NotDue,
+        };
+
+        let cooldown_ledgers = Self::get_retry_policy(env.clone(), sub_id)
+            .map_or(cooldown_ledgers, |policy| policy.cooldown_ledgers);
+        let cooldown_remaining = if data.failure_count > 0 {
+            (data.last_attempt_ledger.saturating_add(cooldown_ledgers)).saturating_sub(now)
+        } else {
+            0
+        };
+
+        // Same charge as `renew`: a prepaid cycle covers the base price, and
+        // without overage on top no approval is spent at all
+        let (_, overage) = Self::overage_for(&env, sub_id);
+        let prepaid = schedule
+            .as_ref()
+            .is_some_and(|s| s.prepaid_cycles > 0 && now >= s.next_due);
+        let needs_approval = !(prepaid && overage == 0 && data.state != SubscriptionState::Failed);
+        let base = if prepaid { 0 } else { amount };
+        let discount_bps = match cycle {
+            CycleStatus::Early => {
+                Self::get_early_discount(env.clone(), data.merchant.clone()).unwrap_or(0)
+            }
+            _ => 0,
+        };
+        let charge = if needs_approval {
+            rounding::split_bps(base, discount_bps)
+                .1
+                .saturating_add(overage)
+        } else {
+            0
+        };
+
+        let key = ApprovalKey {
+            sub_id,
+            approval_id,
+        };
+        let (approval_rejection, approval_headroom) = if needs_approval {
+            (
+                Self::check_approval(&env, &data, &key, charge)
+                    .err()
+                    .map(|reason| reason as u32),
+                Self::load_approval(&env, &key).map(|approval| approval.max_spend - charge),
+            )
+        } else {
+            (None, None)
+        };
+        let volume_headroom = Self::volume_headroom(&env, &data.token);
+        let balance_ok = Self::payment_readiness(&env, &data, charge).ready;
+
+        let mut diagnostics = RenewDiagnostics {
+            protocol_paused: Self::is_paused(env.clone()),
+            sub_paused: Self::is_sub_paused(env.clone(), sub_id),
+            frozen: Self::get_freeze(env.clone(), sub_id).is_some(),
+            starts_at: Self::get_start_ledger(env.clone(), sub_id).filter(|start| now < *start),
+            merchant_suspended: Self::merchant_suspended(&env, &data.merchant),
+            state: data.state,
+            caller_allowed,
+            cycle,
+            cooldown_remaining,
+            charge,
+            approval_rejection,
+            approval_headroom,
+            volume_headroom,
+            balance_ok,
+            ok: false,
+        };
+        diagnostics.ok = !diagnostics.protocol_paused
+            && !diagnostics.sub_paused
+            && !diagnostics.frozen
+            && diagnostics.starts_at.is_none()
+            && !diagnostics.merchant_suspended
+            && matches!(
+                data.state,
+                SubscriptionState::Active | SubscriptionState::Retrying
+            )
+            && caller_allowed
+            && matches!(
+                cycle,
+                CycleStatus::Unscheduled | CycleStatus::Early | CycleStatus::Due
+            )
+            && (cooldown_remaining == 0
+                || caller == data.owner && Self::cooldown_bypass_available(&env, sub_id, &data))
+            && approval_rejection.is_none()
+            && volume_headroom.is_none_or(|headroom| headroom >= charge)
+            && balance_ok;
+        diagnostics
+    }
+
+    /// Attempt to renew the subscription.
+    /// Callable by owner, assigned executor or an active keeper. The owner
+    /// may retry once per cycle before the cooldown ends. Keepers can't
+    /// report a failure, renew on the stored retry terms and earn the keeper
+    /// fee out of the collected charge.
+    /// Returns true if renewal is successful (simulated), false if it failed and retry logic was triggered.
+    /// limits: max retries allowed, counted per `get_retry_counting`.
+    /// cooldown: min ledgers between retries.
+    #[allow(clippy::too_many_arguments)]
+    pub fn renew(
+        env: Env,
+        caller: Address,
+        sub_id: u64,
+        approval_id: u64,
+        amount: i128,
+        max_retries: u32,
+        cooldown_ledgers: u32,
+        succeed: bool,
+    ) -> bool {
+        Self::renew_impl(
+            env,
+            caller,
+            sub_id,
+            approval_id,
+            amount,
+            max_retries,
+            cooldown_ledgers,
+            succeed,
+            None,
+        )
+    }
+
+    /// Internal helper – the body of `renew`, skipping the caller's auth if
+    /// they are the `signer` of the enclosing call.
+    #[allow(clippy::too_many_arguments)]
+    fn renew_impl(
+        env: Env,
+        caller: Address,
+        sub_id: u64,
+        approval_id: u64,
+        amount: i128,
+        max_retries: u32,
+        cooldown_ledgers: u32,
+        succeed: bool,
+        signer: Option<&Address>,
+    ) -> bool {
+        let before = Self::load_sub(&env, sub_id);
+        let (renewed, _) = Self::renew_sub(
+            env.clone(),
+            caller.clone(),
+            sub_id,
+            approval_id,
+            amount,
+            max_retries,
+            cooldown_ledgers,
+            succeed,
+            false,
+            signer,
+        );
+        Self::record_keeper_attempt(&env, &caller, renewed);
+        if let Some(before) = before {
+            Self::pay_retry_subsidy(&env, &caller, sub_id, &before);
+        }
+        renewed
+    }
+
+    /// `renew` for callers that may send the same call twice, such as
+    /// off-chain job queues retrying on timeouts. The first call with
+    /// `idempotency_key` from `caller` for `sub_id` renews as usual;
+    /// repeats within `IDEMPOTENCY_TTL_LEDGERS` return its result without
+    /// charging or counting a retry again. Reusing a key with other
+    /// arguments panics. A call that traps isn't remembered.
+    pub fn renew_idempotent(
+        env: Env,
+        caller: Address,
+        op: RenewOp,
+        idempotency_key: BytesN<32>,
+    ) -> bool {
+        let key = SubKey::Idempotency(op.sub_id, caller.clone(), idempotency_key);
+        if let Some((stored, renewed)) = env.storage().temporary().get::<_, (RenewOp, bool)>(&key) {
+            if stored != op {
+                panic!("Idempotency key reused with other arguments");
+            }
+            return renewed;
+        }
+        let renewed = Self::renew(
+            env.clone(),
+            caller,
+            op.sub_id,
+            op.approval_id,
+            op.amount,
+            op.max_retries,
+            op.cooldown_ledgers,
+            op.succeed,
+        );
+        env.storage().temporary().set(&key, &(op, renewed));
+        env.storage().temporary().extend_ttl(
+            &key,
+            IDEMPOTENCY_TTL_LEDGERS,
+            IDEMPOTENCY_TTL_LEDGERS,
+        );
+        renewed
+    }
+
+    /// Run `ops` in order in one invocation, e.g. approve-then-renew.
+    /// All or nothing: if any step panics, none of them take effect.
+    /// `caller` signs once for the whole batch; steps that need someone
+    /// else's auth still ask for it. Returns one entry per step: `renew`'s
+    /// result for `Renew`, true otherwise.
+    pub fn multicall(env: Env, caller: Address, ops: Vec<UserOp>) -> Vec<bool> {
+        caller.require_auth();
+        let signer = Some(&caller);
 
-#[contractimpl]
-impl SubscriptionRenewalContract {
-    // ── Admin / Pause management ──────────────────────────────────
+        let mut results = Vec::new(&env);
+        for op in ops.iter() {
+            let result = match op {
+                UserOp::Approve(op) => {
+                    Self::approve_renewal_impl(
+                        env.clone(),
+                        op.sub_id,
+                        op.approval_id,
+                        op.max_spend,
+                        op.expires_at,
+                        signer,
+                    );
+                    true
+                }
+                UserOp::Renew(op) => Self::renew_impl(
+                    env.clone(),
+                    caller.clone(),
+                    op.sub_id,
+                    op.approval_id,
+                    op.amount,
+                    op.max_retries,
+                    op.cooldown_ledgers,
+                    op.succeed,
+                    signer,
+                ),
+                UserOp::PrepayCycles(sub_id, cycles, amount_per_cycle) => {
+                    Self::prepay_cycles_impl(env.clone(), sub_id, cycles, amount_per_cycle, signer);
+                    true
+                }
+                UserOp::SetExecutor(sub_id, executor) => {
+                    Self::set_executor_impl(env.clone(), sub_id, executor, signer);
+                    true
+                }
+                UserOp::RemoveExecutor(sub_id) => {
+                    Self::remove_executor_impl(env.clone(), sub_id, signer);
+                    true
+                }
+            };
+            results.push_back(result);
+        }
+        results
+    }
 
-    /// Initialize the contract admin. Can only be called once.
-    pub fn init(env: Env, admin: Address) {
-        if env.storage().instance().has(&ContractKey::Admin) {
-            panic!("Already initialized");
+    /// Internal helper – `who.require_auth()`, unless `who` is the `signer`
+    /// who already authorized the enclosing call, e.g. a `multicall`.
+    fn require_auth_of(who: &Address, signer: Option<&Address>) {
+        if signer != Some(who) {
+            who.require_auth();
         }
-        env.storage().instance().set(&ContractKey::Admin, &admin);
-        env.storage().instance().set(&ContractKey::Paused, &false);
     }
 
-    /// Internal helper – loads admin and calls `require_auth`.
-    fn require_admin(env: &Env) {
-        let admin: Address = env
-            .storage()
-            .instance()
-            .get(&ContractKey::Admin)
-            .expect("Contract not initialized");
-        admin.require_auth();
+    /// Enable permissionless cranking with a fixed `bounty` per renewal, or
+    /// disable it with `None`. Admin only.
+    pub fn set_crank_bounty(env: Env, bounty: Option<i128>) {
+        let admin = Self::require_admin(&env);
+        if bounty.is_some_and(|bounty| bounty < 0) {
+            panic!("Invalid bounty");
+        }
+        let old = Self::get_crank_bounty(env.clone());
+        match bounty {
+            Some(bounty) => env
+                .storage()
+                .instance()
+                .set(&ContractKey::CrankBounty, &bounty),
+            None => env.storage().instance().remove(&ContractKey::CrankBounty),
+        }
+        Self::admin_action(&env, symbol_short!("bounty"), admin, old, bounty);
     }
 
-    /// Pause or unpause all renewal execution. Admin only.
-    pub fn set_paused(env: Env, paused: bool) {
-        Self::require_admin(&env);
-        env.storage().instance().set(&ContractKey::Paused, &paused);
-        PauseToggled { paused }.publish(&env);
+    /// Bounty paid per crank, if permissionless cranking is enabled.
+    pub fn get_crank_bounty(env: Env) -> Option<i128> {
+        env.storage().instance().get(&ContractKey::CrankBounty)
     }
 
-    /// Query the current pause state.
-    pub fn is_paused(env: Env) -> bool {
+    /// Require cranks to go through `commit_crank` / `reveal_crank`, so a
+    /// crank seen in flight can't be copied in the same ledger to snipe the
+    /// bounty. Admin only.
+    pub fn set_crank_commit_reveal(env: Env, enabled: bool) {
+        let admin = Self::require_admin(&env);
+        let old = Self::get_crank_commit_reveal(env.clone());
         env.storage()
             .instance()
-            .get(&ContractKey::Paused)
-            .unwrap_or(false)
+            .set(&ContractKey::CrankCommitReveal, &enabled);
+        Self::admin_action(&env, symbol_short!("crank_cr"), admin, old, enabled);
     }
 
-    // ── Subscription logic ────────────────────────────────────────
-
-    /// Initialize a subscription
-    pub fn init_sub(env: Env, info: Address, sub_id: u64) {
-        let key = sub_id;
-        let data = SubscriptionData {
-            owner: info,
-            state: SubscriptionState::Active,
-            failure_count: 0,
-            last_attempt_ledger: 0,
-        };
-        env.storage().persistent().set(&key, &data);
+    /// Whether cranks must be committed before they are revealed.
+    pub fn get_crank_commit_reveal(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&ContractKey::CrankCommitReveal)
+            .unwrap_or(false)
     }
 
-    // ── Executor management ───────────────────────────────────────
-
-    /// Assign executor for subscription (owner only)
-    pub fn set_executor(env: Env, sub_id: u64, executor: Address) {
-        let data: SubscriptionData = env
-            .storage()
-            .persistent()
-            .get(&sub_id)
-            .expect("Subscription not found");
-
-        data.owner.require_auth();
-
-        let key = ExecutorKey { sub_id };
-        env.storage().persistent().set(&key, &executor);
-
-        ExecutorAssigned { sub_id, executor }.publish(&env);
+    /// Renew a due, priced subscription on anyone's behalf. The caller earns
+    /// the crank bounty out of the collected payment, at most once per
+    /// cycle; the owner's approval still bounds the charge. Only
+    /// subscriptions paying by `Allowance` or `Escrow` can be cranked.
+    pub fn crank(env: Env, caller: Address, sub_id: u64, approval_id: u64) -> bool {
+        if Self::get_crank_commit_reveal(env.clone()) {
+            panic!("Cranks must be committed first");
+        }
+        Self::crank_sub(env, caller, sub_id, approval_id)
     }
 
-    /// Remove executor (owner only)
-    pub fn remove_executor(env: Env, sub_id: u64) {
-        let data: SubscriptionData = env
-            .storage()
-            .persistent()
-            .get(&sub_id)
-            .expect("Subscription not found");
-
-        data.owner.require_auth();
-
-        let key = ExecutorKey { sub_id };
-        env.storage().persistent().remove(&key);
-
-        ExecutorRemoved { sub_id }.publish(&env);
+    /// Hash committed by `commit_crank`: sha256 over the caller's XDR, the
+    /// big-endian `sub_id` and `salt`.
+    pub fn crank_commitment(
+        env: Env,
+        caller: Address,
+        sub_id: u64,
+        salt: BytesN<32>,
+    ) -> BytesN<32> {
+        let mut preimage = caller.to_xdr(&env);
+        preimage.extend_from_array(&sub_id.to_be_bytes());
+        preimage.append(&salt.into());
+        env.crypto().sha256(&preimage).to_bytes()
     }
 
-    /// Get executor for subscription
-    pub fn get_executor(env: Env, sub_id: u64) -> Option<Address> {
-        let key = ExecutorKey { sub_id };
-        env.storage().persistent().get(&key)
+    /// Seal a claim to crank a subscription without revealing which one.
+    pub fn commit_crank(env: Env, caller: Address, hash: BytesN<32>) {
+        caller.require_auth();
+        let key = CrankCommitKey::Commitment(hash);
+        if env.storage().persistent().has(&key) {
+            panic!("Commitment already exists");
+        }
+        env.storage().persistent().set(
+            &key,
+            &CrankCommitment {
+                committer: caller,
+                ledger: env.ledger().sequence(),
+            },
+        );
     }
 
-    // ── Approval management ───────────────────────────────────────
-
-    /// Create a renewal approval for a subscription
-    pub fn approve_renewal(
+    /// Crank `sub_id` against a commitment made in an earlier ledger.
+    pub fn reveal_crank(
         env: Env,
+        caller: Address,
         sub_id: u64,
         approval_id: u64,
-        max_spend: i128,
-        expires_at: u32,
-    ) {
-        let sub_key = sub_id;
-        let data: SubscriptionData = env
+        salt: BytesN<32>,
+    ) -> bool {
+        let hash = Self::crank_commitment(env.clone(), caller.clone(), sub_id, salt);
+        let key = CrankCommitKey::Commitment(hash);
+        let commitment: CrankCommitment = env
             .storage()
             .persistent()
-            .get(&sub_key)
-            .expect("Subscription not found");
-
-        data.owner.require_auth();
-
-        let approval = RenewalApproval {
-            sub_id,
-            max_spend,
-            expires_at,
-            used: false,
-        };
+            .get(&key)
+            .expect("No matching commitment");
+        if commitment.ledger >= env.ledger().sequence() {
+            panic!("Commitment too recent");
+        }
+        env.storage().persistent().remove(&key);
+        Self::crank_sub(env, caller, sub_id, approval_id)
+    }
 
-        let key = ApprovalKey {
-            sub_id,
-            approval_id,
-        };
-        env.storage().persistent().set(&key, &approval);
+    /// Internal helper – the body of `crank` and `reveal_crank`.
+    fn crank_sub(env: Env, caller: Address, sub_id: u64, approval_id: u64) -> bool {
+        if Self::get_crank_bounty(env.clone()).is_none() {
+            panic!("Permissionless cranking disabled");
+        }
+        let due = Self::get_schedule(env.clone(), sub_id)
+            .expect("Subscription has no schedule")
+            .next_due;
+        if env.ledger().sequence() < due {
+            panic!("Renewal not yet due");
+        }
+        let cranked_key = SubKey::CrankedDue(sub_id);
+        if env.storage().persistent().get(&cranked_key) == Some(due) {
+            panic!("Cycle already cranked");
+        }
+        let amount = Self::get_amount(env.clone(), sub_id).expect("Subscription has no pricing");
+        // The bounty comes out of the collected payment, which only
+        // Allowance and Escrow collect without the owner's signature
+        if !matches!(
+            Self::get_payment_mode(env.clone(), sub_id),
+            PaymentMode::Allowance | PaymentMode::Escrow
+        ) {
+            panic!("Cranking needs Allowance or Escrow payments");
+        }
 
-        ApprovalCreated {
+        let (renewed, bounty) = Self::renew_sub(
+            env.clone(),
+            caller.clone(),
             sub_id,
             approval_id,
-            max_spend,
-            expires_at,
-        }
-
-        let config = FeeConfig { percentage, recipient: recipient.clone() };
-        env.storage().instance().set(&ContractKey::FeeConfig, &config);
-
-        FeeConfigUpdated {
-            percentage,
-            recipient,
+            amount,
+            0,
+            0,
+            true,
+            true,
+            None,
+        );
+        if renewed {
+            env.storage().persistent().set(&cranked_key, &due);
+            if bounty > 0 {
+                CrankBountyPaid {
+                    cranker: caller,
+                    sub_id,
+                    due,
+                    bounty,
+                }
+                .publish(&env);
+            }
         }
-        .publish(&env);
+        renewed
     }
 
-    // ── Renewal logic ─────────────────────────────────────────────
-
-    /// Attempt to renew the subscription.
-    /// Callable by owner or assigned executor.
-    /// Returns true if renewal is successful (simulated), false if it failed and retry logic was triggered.
-    /// limits: max retries allowed.
-    /// cooldown: min ledgers between retries.
-    pub fn renew(
+    /// Internal helper – the body of `renew` and `crank`. Returns whether
+    /// the subscription renewed and the caller's cut of the charge: the
+    /// crank bounty, or the keeper fee. `permissionless` skips the
+    /// owner/executor/keeper check and pays the caller the crank bounty.
+    #[allow(clippy::too_many_arguments)]
+    fn renew_sub(
         env: Env,
         caller: Address,
         sub_id: u64,
@@ -273,33 +5243,153 @@ impl SubscriptionRenewalContract {
         max_retries: u32,
         cooldown_ledgers: u32,
         succeed: bool,
-    ) -> bool {
+        permissionless: bool,
+        signer: Option<&Address>,
+    ) -> (bool, i128) {
         // Check global pause
         if Self::is_paused(env.clone()) {
             panic!("Protocol is paused");
         }
 
+        Self::require_not_frozen(&env, sub_id);
+        if Self::is_sub_paused(env.clone(), sub_id) {
+            panic!("Subscription is paused");
+        }
+        Self::require_started(&env, sub_id);
+
         let key = sub_id;
-        let mut data: SubscriptionData = env
-            .storage()
-            .persistent()
-            .get(&key)
-            .expect("Subscription not found");
+        let mut data = Self::get_sub(env.clone(), sub_id);
+        if Self::merchant_suspended(&env, &data.merchant) {
+            panic_with_error!(env, Error::MerchantSuspended);
+        }
 
-        // Verify caller is owner or executor
-        caller.require_auth();
+        // Verify caller is owner, executor or keeper
+        Self::require_auth_of(&caller, signer);
         let executor_key = ExecutorKey { sub_id };
         let executor: Option<Address> = env.storage().persistent().get(&executor_key);
-        
-        if caller != data.owner && Some(caller.clone()) != executor {
-            panic!("Unauthorized: caller must be owner or executor");
+        let delegated = caller == data.owner || Some(caller.clone()) == executor;
+
+        if !permissionless && !delegated && !Self::is_active_keeper(&env, &caller) {
+            panic!("Unauthorized: caller must be owner or executor, or an active keeper");
+        }
+        // Keepers and cranks may only attempt the charge; a failure they
+        // could report would push someone else's subscription into Failed
+        if !delegated && !succeed {
+            panic!("Only the owner or executor can report a failed renewal");
+        }
+
+        // Template terms take precedence over the caller's retry arguments,
+        // and only the owner and executor choose their own
+        let (max_retries, cooldown_ledgers) = match Self::get_retry_policy(env.clone(), sub_id) {
+            Some(policy) => (policy.max_retries, policy.cooldown_ledgers),
+            None if delegated => (max_retries, cooldown_ledgers),
+            None => (
+                DEFAULT_RETRY_POLICY.max_retries,
+                DEFAULT_RETRY_POLICY.cooldown_ledgers,
+            ),
+        };
+        if data.state == SubscriptionState::PendingAcceptance {
+            panic!("Subscription not accepted");
         }
+        if data.state == SubscriptionState::Completed {
+            panic!("Subscription is completed");
+        }
+
+        Self::settle_plan_migration(&env, sub_id);
+        let mut schedule = Self::get_schedule(env.clone(), sub_id);
+
+        #[cfg(any(test, feature = "invariants"))]
+        let previous = data.clone();
+        #[cfg(any(test, feature = "invariants"))]
+        let previous_schedule = schedule.clone();
+
+        let (overage_units, overage) = Self::overage_for(&env, sub_id);
+        let mut discount_bps = 0;
+        let mut base_prepaid = false;
+        if let Some(schedule) = schedule.as_mut() {
+            discount_bps = Self::early_discount_bps(&env, &caller, &data, schedule);
+
+            // Past the renewal window the cycle is missed rather than charged late
+            if data.state != SubscriptionState::Failed
+                && Self::skip_missed_cycles(&env, sub_id, schedule)
+            {
+                env.storage()
+                    .persistent()
+                    .set(&SubKey::Schedule(sub_id), schedule);
+
+                #[cfg(any(test, feature = "invariants"))]
+                invariants::check_schedule(&env, sub_id, previous_schedule.as_ref());
+
+                return (false, 0);
+            }
+            if data.state != SubscriptionState::Failed {
+                Self::require_billable_cycle(&env, sub_id, schedule);
+            }
+
+            // A prepaid cycle covers this due date without a transfer, unless
+            // there is overage to bill on top of it
+            let now = env.ledger().sequence();
+            base_prepaid = schedule.prepaid_cycles > 0 && now >= schedule.next_due;
+            if base_prepaid && overage == 0 && data.state != SubscriptionState::Failed {
+                schedule.prepaid_cycles -= 1;
+                PrepaidCycleUsed {
+                    sub_id,
+                    due: schedule.next_due,
+                    prepaid_cycles: schedule.prepaid_cycles,
+                }
+                .publish(&env);
+                let pricing = Self::get_pricing(env.clone(), sub_id);
+                let charge = Charge {
+                    unit_price: pricing.as_ref().map_or(0, |p| p.unit_price),
+                    quantity: pricing.as_ref().map_or(0, |p| p.quantity),
+                    discount: 0,
+                    overage_units: 0,
+                    overage: 0,
+                    charged: 0,
+                    credit_applied: 0,
+                };
+                Self::record_success(&env, sub_id, &mut data, Some(schedule), &charge);
+
+                #[cfg(any(test, feature = "invariants"))]
+                invariants::check_sub(&env, sub_id, Some(&previous));
+                #[cfg(any(test, feature = "invariants"))]
+                invariants::check_schedule(&env, sub_id, previous_schedule.as_ref());
 
-        // Validate and consume approval
-        if !Self::consume_approval(&env, sub_id, approval_id, amount) {
-            panic!("Invalid or expired approval");
+                return (true, 0);
+            }
         }
 
+        // A merchant quote replaces the caller's amount; the approval and
+        // volume limits still bound it
+        let (amount, pricing) = match Self::quote_for(&env, sub_id) {
+            Some(quote) => (
+                quote,
+                Pricing {
+                    unit_price: quote,
+                    quantity: 1,
+                },
+            ),
+            None => (amount, Self::pricing_for(&env, sub_id, amount)),
+        };
+        let base = if base_prepaid { 0 } else { amount };
+        let (discount, net) = rounding::split_bps(base, discount_bps);
+        let mut charge = Charge {
+            unit_price: pricing.unit_price,
+            quantity: pricing.quantity,
+            discount,
+            overage_units,
+            overage,
+            charged: net.checked_add(overage).expect("Amount overflow"),
+            credit_applied: 0,
+        };
+        let charged = charge.charged;
+
+        // Reserve the approval; it is only spent if the renewal succeeds
+        let approval = match Self::reserve_approval(&env, &data, sub_id, approval_id, charged) {
+            Some(approval) => approval,
+            None => panic!("Invalid or expired approval"),
+        };
+
         // If already failed, we can't renew
         if data.state == SubscriptionState::Failed {
             panic!("Subscription is in FAILED state");
@@ -307,42 +5397,87 @@ impl SubscriptionRenewalContract {
 
         let current_ledger = env.ledger().sequence();
 
-        // Check cooldown
-        if data.failure_count > 0 && current_ledger < data.last_attempt_ledger + cooldown_ledgers {
+        // Check cooldown; an owner who topped up needn't wait out a keeper's
+        // backoff, once per cycle
+        if data.failure_count > 0
+            && current_ledger < data.last_attempt_ledger + cooldown_ledgers
+            && !(caller == data.owner && Self::take_cooldown_bypass(&env, sub_id, &data))
+        {
             panic!("Cooldown period active");
         }
 
-        if succeed {
-            // Simulated success - renewal successful
-            data.state = SubscriptionState::Active;
-            data.failure_count = 0;
-            data.last_attempt_ledger = current_ledger;
-            env.storage().persistent().set(&key, &data);
+        Self::screen_payment(&env, &data, charged);
 
-            // Emit renewal success event
-            RenewalSuccess {
-                sub_id,
-                owner: data.owner.clone(),
+        let mut cut_paid = 0;
+        let renewed = if succeed {
+            // Simulated success - renewal successful
+            Self::record_volume(&env, &data.token, charged);
+            // Credit pays first, expiring promo credit before account
+            // credit; only the remainder comes from the wallet
+            let promo = Self::apply_promo_credit(&env, sub_id, &data, charged);
+            charge.credit_applied =
+                promo + Self::apply_credit(&env, sub_id, &data, charged - promo);
+            let wallet_amount = charged - charge.credit_applied;
+            let gateway = gateway::for_mode(&Self::get_payment_mode(env.clone(), sub_id));
+            gateway.preflight(&env, &data, wallet_amount);
+            let mut held = charge.credit_applied;
+            if wallet_amount > 0 {
+                held += gateway.collect(&env, &data, wallet_amount);
             }
-            .publish(&env);
+            let cut = if permissionless {
+                Self::get_crank_bounty(env.clone()).map(|bounty| Cut::Crank(&caller, bounty))
+            } else if !delegated {
+                Self::get_keeper_fee(env.clone()).map(|fee| Cut::Keeper(&caller, fee))
+            } else {
+                None
+            };
+            cut_paid = Self::pay_charge(&env, sub_id, &data, held, cut);
+            if let (true, Some(schedule)) = (base_prepaid, schedule.as_mut()) {
+                schedule.prepaid_cycles -= 1;
+                PrepaidCycleUsed {
+                    sub_id,
+                    due: schedule.next_due,
+                    prepaid_cycles: schedule.prepaid_cycles,
+                }
+                .publish(&env);
+            }
+            Self::record_installment(&env, sub_id, base);
+            Self::record_success(&env, sub_id, &mut data, schedule.as_mut(), &charge);
+            Self::commit_approval(&env, sub_id, approval_id, approval, charged);
+
+            #[cfg(any(test, feature = "invariants"))]
+            invariants::check_sub(&env, sub_id, Some(&previous));
+            #[cfg(any(test, feature = "invariants"))]
+            invariants::check_schedule(&env, sub_id, previous_schedule.as_ref());
 
             true
         } else {
             // Simulated failure - renewal failed, apply retry logic
+            Self::release_approval(&env, sub_id, approval_id);
             data.failure_count += 1;
             data.last_attempt_ledger = current_ledger;
 
+            let exhausted =
+                Self::get_retry_counting(env.clone()).exhausted(data.failure_count, max_retries);
+
             // Emit renewal failure event
+            Self::note_renewal_event(&env, sub_id);
             RenewalFailed {
                 sub_id,
                 failure_count: data.failure_count,
                 ledger: current_ledger,
+                next_attempt_after: if exhausted {
+                    0
+                } else {
+                    current_ledger.saturating_add(cooldown_ledgers)
+                },
             }
             .publish(&env);
 
             // Determine new state based on retry count
-            if data.failure_count > max_retries {
+            if exhausted {
                 data.state = SubscriptionState::Failed;
+                Self::release_owner_slot(&env, &data.merchant, &data.owner);
                 StateTransition {
                     sub_id,
                     new_state: SubscriptionState::Failed,
@@ -358,14 +5493,317 @@ impl SubscriptionRenewalContract {
             }
 
             env.storage().persistent().set(&key, &data);
+
+            #[cfg(any(test, feature = "invariants"))]
+            invariants::check_sub(&env, sub_id, Some(&previous));
+
             false
+        };
+        Self::record_merchant_outcome(&env, &data.merchant, sub_id, renewed);
+        (renewed, cut_paid)
+    }
+
+    /// Internal helper – books a successful renewal: resets retry state,
+    /// bumps `renewal_seq`, moves the schedule to the next cycle and emits
+    /// the `RenewalSuccess` receipt.
+    fn record_success(
+        env: &Env,
+        sub_id: u64,
+        data: &mut SubscriptionData,
+        schedule: Option<&mut Schedule>,
+        charge: &Charge,
+    ) {
+        data.state = SubscriptionState::Active;
+        data.failure_count = 0;
+        data.last_attempt_ledger = env.ledger().sequence();
+        data.renewal_seq += 1;
+        env.storage().persistent().set(&sub_id, data);
+        // Metered usage is billed per cycle
+        env.storage().persistent().remove(&SubKey::Usage(sub_id));
+        let schedule_set = schedule.is_some();
+        if let Some(schedule) = schedule {
+            schedule.next_due += schedule.interval_ledgers;
+            env.storage()
+                .persistent()
+                .set(&SubKey::Schedule(sub_id), schedule);
+        }
+
+        Self::record_charge(env, data, sub_id, charge.charged, charge.credit_applied);
+        Self::record_settlement(env, sub_id, data, charge);
+        Self::note_renewal_event(env, sub_id);
+        Self::extend_renewal_ttl(env, sub_id, schedule_set);
+
+        // Emit renewal success event
+        RenewalSuccess {
+            sub_id,
+            owner: data.owner.clone(),
+            renewal_seq: data.renewal_seq,
+            unit_price: charge.unit_price,
+            quantity: charge.quantity,
+            discount: charge.discount,
+            overage_units: charge.overage_units,
+            overage: charge.overage,
+            charged: charge.charged,
+            credit_applied: charge.credit_applied,
+        }
+        .publish(env);
+        Self::complete_if_done(env, sub_id, data);
+    }
+
+    /// Internal helper – extends the TTL of what the next renewal reads to
+    /// `RENEWAL_TTL_LEDGERS`, reporting each bump when events are verbose.
+    /// The threshold equals the target, so every call bumps and
+    /// `new_live_until` is exact.
+    fn extend_renewal_ttl(env: &Env, sub_id: u64, schedule_set: bool) {
+        let storage = env.storage();
+        storage
+            .instance()
+            .extend_ttl(RENEWAL_TTL_LEDGERS, RENEWAL_TTL_LEDGERS);
+        storage
+            .persistent()
+            .extend_ttl(&sub_id, RENEWAL_TTL_LEDGERS, RENEWAL_TTL_LEDGERS);
+        if schedule_set {
+            storage.persistent().extend_ttl(
+                &SubKey::Schedule(sub_id),
+                RENEWAL_TTL_LEDGERS,
+                RENEWAL_TTL_LEDGERS,
+            );
+        }
+
+        if !Self::get_verbose_events(env.clone()) {
+            return;
+        }
+        let new_live_until = env.ledger().sequence() + RENEWAL_TTL_LEDGERS;
+        let mut extended = Vec::from_array(
+            env,
+            [
+                (TtlKeyKind::Instance, 0),
+                (TtlKeyKind::Subscription, sub_id),
+            ],
+        );
+        if schedule_set {
+            extended.push_back((TtlKeyKind::Schedule, sub_id));
+        }
+        for (key_kind, id) in extended {
+            TtlExtended {
+                key_kind,
+                id,
+                new_live_until,
+            }
+            .publish(env);
+        }
+    }
+
+    /// Internal helper – adds a charge to the subscription's economics and
+    /// the owner's spending. Fees are booked by `pay_charge` as they are
+    /// paid.
+    fn record_charge(
+        env: &Env,
+        data: &SubscriptionData,
+        sub_id: u64,
+        charged: i128,
+        credit_applied: i128,
+    ) {
+        if charged == 0 {
+            return;
+        }
+        Self::record_spending(env, data, charged);
+        Self::update_economics(env, sub_id, |economics| {
+            economics.charged += charged;
+            economics.credit_applied += credit_applied;
+        });
+    }
+
+    /// Internal helper – applies `update` to the stored economics.
+    fn update_economics(env: &Env, sub_id: u64, update: impl FnOnce(&mut SubEconomics)) {
+        let mut economics = Self::get_sub_economics(env.clone(), sub_id);
+        update(&mut economics);
+        env.storage()
+            .persistent()
+            .set(&SubKey::Economics(sub_id), &economics);
+    }
+
+    /// Latest renewal sequence and the ledger of the latest renewal event
+    /// of `sub_id`, for clients catching up on missed events.
+    pub fn get_last_event_seq(env: Env, sub_id: u64) -> EventCursor {
+        EventCursor {
+            renewal_seq: Self::get_sub(env.clone(), sub_id).renewal_seq,
+            ledger: env
+                .storage()
+                .persistent()
+                .get(&SubKey::LastEventLedger(sub_id))
+                .unwrap_or(0),
+        }
+    }
+
+    /// Internal helper – records that a renewal event of `sub_id` was
+    /// emitted in this ledger.
+    fn note_renewal_event(env: &Env, sub_id: u64) {
+        env.storage()
+            .persistent()
+            .set(&SubKey::LastEventLedger(sub_id), &env.ledger().sequence());
+    }
+
+    /// Internal helper – adds `charged` to the owner's spending in the
+    /// current window.
+    fn record_spending(env: &Env, data: &SubscriptionData, charged: i128) {
+        let mut spending =
+            Self::get_owner_spending(env.clone(), data.owner.clone(), data.token.clone(), None);
+        spending.total += charged;
+        let merchant_total = spending.by_merchant.get(data.merchant.clone()).unwrap_or(0);
+        spending
+            .by_merchant
+            .set(data.merchant.clone(), merchant_total + charged);
+        env.storage().persistent().set(
+            &SpendingKey::Window(data.owner.clone(), data.token.clone(), spending.window),
+            &spending,
+        );
+    }
+
+    /// What `owner` was charged in `token` during `window`, or the current
+    /// window with `None`, in total and per merchant. See
+    /// `SPENDING_WINDOW_LEDGERS`.
+    pub fn get_owner_spending(
+        env: Env,
+        owner: Address,
+        token: Address,
+        window: Option<u32>,
+    ) -> OwnerSpending {
+        let window = window.unwrap_or(env.ledger().sequence() / SPENDING_WINDOW_LEDGERS);
+        env.storage()
+            .persistent()
+            .get(&SpendingKey::Window(owner, token, window))
+            .unwrap_or(OwnerSpending {
+                window,
+                total: 0,
+                by_merchant: Map::new(&env),
+            })
+    }
+
+    /// Internal helper – books a successful renewal into the merchant's
+    /// current settlement period and extends the period's hash chain.
+    fn record_settlement(env: &Env, sub_id: u64, data: &SubscriptionData, charge: &Charge) {
+        let now = env.ledger().sequence();
+        let period = now / SETTLEMENT_PERIOD_LEDGERS;
+        let mut summary = Self::get_settlement_period(env.clone(), data.merchant.clone(), period);
+        let receipt = SettlementReceipt {
+            sub_id,
+            renewal_seq: data.renewal_seq,
+            ledger: now,
+            token: data.token.clone(),
+            charged: charge.charged,
+            credit_applied: charge.credit_applied,
+        };
+        let mut preimage: Bytes = summary.hash.into();
+        preimage.append(&receipt.clone().to_xdr(env));
+        summary.hash = env.crypto().sha256(&preimage).to_bytes();
+
+        let storage = env.storage().persistent();
+        storage.set(
+            &SettlementKey::Receipt(data.merchant.clone(), period, summary.count),
+            &receipt,
+        );
+        summary.count += 1;
+        storage.set(
+            &SettlementKey::Period(data.merchant.clone(), period),
+            &summary,
+        );
+    }
+
+    /// Receipt count and hash chain of one of `merchant`'s settlement
+    /// periods. See `SETTLEMENT_PERIOD_LEDGERS`.
+    pub fn get_settlement_period(env: Env, merchant: Address, period: u32) -> SettlementPeriod {
+        env.storage()
+            .persistent()
+            .get(&SettlementKey::Period(merchant, period))
+            .unwrap_or(SettlementPeriod {
+                count: 0,
+                hash: BytesN::from_array(&env, &[0; 32]),
+            })
+    }
+
+    /// The renewal receipts of a merchant's settlement period from
+    /// `cursor`, at most `MAX_SETTLEMENT_PAGE`, serialized for accounting
+    /// exports. Hashing every page's receipts through the chain described
+    /// on `SettlementPeriod` reproduces `period.hash`.
+    pub fn export_settlement(
+        env: Env,
+        merchant: Address,
+        cursor: SettlementCursor,
+    ) -> SettlementExport {
+        let period = Self::get_settlement_period(env.clone(), merchant.clone(), cursor.period);
+        let end = period
+            .count
+            .min(cursor.index.saturating_add(MAX_SETTLEMENT_PAGE));
+        let mut receipts = Bytes::new(&env);
+        for index in cursor.index..end {
+            let receipt: SettlementReceipt = env
+                .storage()
+                .persistent()
+                .get(&SettlementKey::Receipt(
+                    merchant.clone(),
+                    cursor.period,
+                    index,
+                ))
+                .expect("Settlement receipt missing");
+            receipts.append(&receipt.to_xdr(&env));
+        }
+        SettlementExport {
+            receipts,
+            count: end.saturating_sub(cursor.index),
+            next_index: (end < period.count).then_some(end),
+            period,
         }
     }
 
+    /// Cumulative charges, fees and refunds of a subscription.
+    pub fn get_sub_economics(env: Env, sub_id: u64) -> SubEconomics {
+        env.storage()
+            .persistent()
+            .get(&SubKey::Economics(sub_id))
+            .unwrap_or_default()
+    }
+
     /// Set the logging contract address. Admin only.
     pub fn set_logging_contract(env: Env, address: Address) {
-        Self::require_admin(&env);
+        let admin = Self::require_admin(&env);
+        let old = Self::get_logging_contract(env.clone());
         env.storage()
             .instance()
             .set(&ContractKey::LoggingContract, &address);
-    }
\ No newline at end of file
+        Self::admin_action(&env, symbol_short!("logging"), admin, old, Some(address));
+    }
+
+    /// Get the logging contract address, if set.
+    pub fn get_logging_contract(env: Env) -> Option<Address> {
+        env.storage().instance().get(&ContractKey::LoggingContract)
+    }
+
+    /// Get subscription data. Entries stored in an older layout are
+    /// upgraded on read; the upgraded form is persisted by the next write.
+    pub fn get_sub(env: Env, sub_id: u64) -> SubscriptionData {
+        Self::load_sub(&env, sub_id).expect("Subscription not found")
+    }
+
+    /// Internal helper – reads a subscription in any known layout.
+    fn load_sub(env: &Env, sub_id: u64) -> Option<SubscriptionData> {
+        // Decoding a struct from a map of another shape traps, so look at
+        // the stored fields first.
+        let raw: Val = env.storage().persistent().get(&sub_id)?;
+        let fields: Map<Symbol, Val> = raw.into_val(env);
+        if fields.contains_key(symbol_short!("version")) {
+            return Some(raw.into_val(env));
+        }
+        let old: SubscriptionDataV0 = raw.into_val(env);
+        Some(old.into())
+    }
+}
+
+#[cfg(test)]
+mod differential;
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod test;
@@ -0,0 +1,95 @@
+//! Page sizes for calls that walk a list, derived from what each item
+//! costs so no page can run past the network's per-transaction limits and
+//! trap halfway.
+
+/// Ledger entries touched and CPU instructions spent.
+pub(crate) struct Cost {
+    /// Entries in the footprint, read or written.
+    pub entries: u32,
+    pub writes: u32,
+    pub instructions: u64,
+}
+
+/// What a call may use, with headroom under the protocol 23 limits (100
+/// footprint entries, 50 written, 100M instructions) for auth entries and
+/// the wasm overhead native measurements don't see.
+pub(crate) const TX_BUDGET: Cost = Cost {
+    entries: 80,
+    writes: 40,
+    instructions: 40_000_000,
+};
+
+/// Measured cost of a paged call: `fixed` once, plus `item` per entry
+/// walked. The `*_page_cost` tests check these against the host's meter.
+pub(crate) struct PageCost {
+    pub fixed: Cost,
+    pub item: Cost,
+}
+
+impl PageCost {
+    /// Most items a page can hold within `TX_BUDGET`.
+    pub(crate) const fn max_items(&self) -> u32 {
+        let by_entries = (TX_BUDGET.entries - self.fixed.entries) / self.item.entries;
+        let by_writes = match self.item.writes {
+            0 => u32::MAX,
+            writes => (TX_BUDGET.writes - self.fixed.writes) / writes,
+        };
+        let by_cpu = (TX_BUDGET.instructions - self.fixed.instructions) / self.item.instructions;
+        let mut items = by_entries;
+        if by_writes < items {
+            items = by_writes;
+        }
+        if by_cpu < items as u64 {
+            items = by_cpu as u32;
+        }
+        items
+    }
+}
+
+/// `migrate_subs_to_plan`: reads the list entry, current plan and pending
+/// changes of each subscriber, writes its migration, price change and new
+/// list entry.
+pub(crate) const MIGRATION: PageCost = PageCost {
+    fixed: Cost {
+        entries: 8,
+        writes: 2,
+        instructions: 1_000_000,
+    },
+    item: Cost {
+        entries: 5,
+        writes: 3,
+        instructions: 400_000,
+    },
+};
+
+/// `export_settlement`: reads the period summary once and each receipt,
+/// writing nothing.
+pub(crate) const SETTLEMENT_EXPORT: PageCost = PageCost {
+    fixed: Cost {
+        entries: 4,
+        writes: 0,
+        instructions: 1_000_000,
+    },
+    item: Cost {
+        entries: 1,
+        writes: 0,
+        instructions: 200_000,
+    },
+};
+
+/// `approve_renewals`: writes the owner's auth nonce once; reads each
+/// subscription, its joint owners, freeze and co-sign rule and its token's
+/// decimals (querying and caching them the first time), writes the
+/// approval and clears its usage.
+pub(crate) const APPROVAL_BATCH: PageCost = PageCost {
+    fixed: Cost {
+        entries: 4,
+        writes: 1,
+        instructions: 1_000_000,
+    },
+    item: Cost {
+        entries: 8,
+        writes: 3,
+        instructions: 400_000,
+    },
+};
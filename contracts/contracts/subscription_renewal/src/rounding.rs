@@ -0,0 +1,20 @@
+//! Rounding for basis-point splits of token amounts. A share taken out of
+//! an amount (a protocol fee, a discount) is rounded toward zero, so any
+//! residue below one unit stays with the merchant's side and the two
+//! parts always add back up to the original amount.
+
+/// Basis points in a whole.
+pub(crate) const BPS_DENOMINATOR: i128 = 10_000;
+
+/// The `bps` share of `amount`, rounded toward zero. `bps` is at most
+/// `BPS_DENOMINATOR`.
+pub(crate) fn bps_share(amount: i128, bps: u32) -> i128 {
+    amount.checked_mul(bps as i128).expect("Amount overflow") / BPS_DENOMINATOR
+}
+
+/// Splits `amount` into the `bps` share taken from it and what is left
+/// for the merchant, which carries the rounding residue.
+pub(crate) fn split_bps(amount: i128, bps: u32) -> (i128, i128) {
+    let share = bps_share(amount, bps);
+    (share, amount - share)
+}
@@ -1,98 +1,5495 @@
-#![no_std]
+extern crate std;
 
+use super::*;
+use soroban_sdk::testutils::storage::Persistent as _;
+use soroban_sdk::testutils::{Address as _, Events, IssuerFlags, Ledger};
+use soroban_sdk::xdr::{AccountId, PublicKey, ScAddress, ToXdr, Uint256};
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contractevent,
-    Address, Env, Symbol, Vec
+    contract, contractimpl, symbol_short, vec, Address, Bytes, BytesN, Env, IntoVal, Map, Symbol,
+    TryFromVal, Val, Vec,
 };
 
-#[contracttype]
-#[derive(Clone)]
-pub struct Subscription {
-    pub subscriber: Address,
-    pub plan_id: Symbol,
-    pub next_payment_time: u64,
-    pub active: bool,
+fn setup(env: &Env) -> (SubscriptionRenewalContractClient<'_>, Address) {
+    env.mock_all_auths();
+    let contract_id = env.register(SubscriptionRenewalContract, ());
+    let client = SubscriptionRenewalContractClient::new(env, &contract_id);
+    let admin = Address::generate(env);
+    client.init(&admin);
+    (client, admin)
 }
 
-#[contracttype]
-#[derive(Clone)]
-pub enum DataKey {
-    Subscription(Address),
+fn new_sub(env: &Env, client: &SubscriptionRenewalContractClient, sub_id: u64) -> Address {
+    let owner = Address::generate(env);
+    let merchant = Address::generate(env);
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(env, &token).mint(&owner, &1_000_000);
+    client.init_sub(&owner, &sub_id, &merchant, &token, &None, &None);
+    owner
 }
 
-#[contractevent]
-pub struct SubscriptionCreated {
-    pub subscriber: Address,
-    pub plan_id: Symbol,
+// ── Admin / Pause ─────────────────────────────────────────────────
+
+#[test]
+fn test_default_not_paused() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    assert!(!client.is_paused());
+}
+
+#[test]
+fn test_admin_can_pause() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    client.set_paused(&true);
+    assert!(client.is_paused());
+}
+
+#[test]
+fn test_admin_can_unpause() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    client.set_paused(&true);
+    client.set_paused(&false);
+    assert!(!client.is_paused());
+}
+
+#[test]
+#[should_panic(expected = "Already initialized")]
+fn test_cannot_init_twice() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    client.init(&admin);
+}
+
+#[test]
+#[should_panic(expected = "Protocol is paused")]
+fn test_renew_blocked_when_paused() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 100);
+    client.approve_renewal(&100, &1, &1000, &100);
+    client.set_paused(&true);
+    client.renew(&owner, &100, &1, &500, &3, &10, &true);
+}
+
+#[test]
+fn test_renew_works_after_unpause() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 101);
+    client.approve_renewal(&101, &1, &1000, &100);
+    client.set_paused(&true);
+    client.set_paused(&false);
+    assert!(client.renew(&owner, &101, &1, &500, &3, &10, &true));
+}
+
+#[test]
+fn test_version_and_interfaces() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+
+    assert_eq!(
+        client.get_version(),
+        soroban_sdk::String::from_str(&env, env!("CARGO_PKG_VERSION"))
+    );
+    assert!(client.supports(&Symbol::new(&env, INTERFACE_ID)));
+    for feature in FEATURES {
+        assert!(client.supports(&Symbol::new(&env, feature)));
+    }
+    assert!(!client.supports(&Symbol::new(&env, "syncro_renewal_v2")));
+}
+
+// ── Renewal / Retry ───────────────────────────────────────────────
+
+#[test]
+fn test_renewal_success() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 123);
+    client.approve_renewal(&123, &1, &1000, &100);
+
+    assert!(client.renew(&owner, &123, &1, &500, &3, &10, &true));
+
+    let data = client.get_sub(&123);
+    assert_eq!(data.state, SubscriptionState::Active);
+    assert_eq!(data.failure_count, 0);
+}
+
+#[test]
+fn test_retry_logic() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 456);
+    client.approve_renewal(&456, &1, &1000, &1000);
+    client.approve_renewal(&456, &2, &1000, &1000);
+    client.approve_renewal(&456, &3, &1000, &1000);
+
+    assert!(!client.renew(&owner, &456, &1, &500, &2, &10, &false));
+    assert_eq!(client.get_sub(&456).state, SubscriptionState::Retrying);
+
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    assert!(!client.renew(&owner, &456, &2, &500, &2, &10, &false));
+    assert_eq!(client.get_sub(&456).state, SubscriptionState::Retrying);
+
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    assert!(!client.renew(&owner, &456, &3, &500, &2, &10, &false));
+
+    let data = client.get_sub(&456);
+    assert_eq!(data.state, SubscriptionState::Failed);
+    assert_eq!(data.failure_count, 3);
+}
+
+#[test]
+#[should_panic(expected = "Cooldown period active")]
+fn test_cooldown_enforcement() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 789);
+    client.approve_renewal(&789, &1, &1000, &1000);
+    client.approve_renewal(&789, &2, &1000, &1000);
+
+    client.approve_renewal(&789, &3, &1000, &1000);
+
+    client.renew(&owner, &789, &1, &500, &3, &10, &false);
+    // The owner's one early retry this cycle
+    env.ledger().with_mut(|li| li.sequence_number += 5);
+    client.renew(&owner, &789, &2, &500, &3, &10, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 5);
+    client.renew(&owner, &789, &3, &500, &3, &10, &false);
+}
+
+#[test]
+fn test_owner_retries_early_once_per_cycle() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let executor = Address::generate(&env);
+    client.set_executor(&1, &executor);
+    for approval_id in 1..=4 {
+        client.approve_renewal(&1, &approval_id, &1000, &1000);
+    }
+
+    client.renew(&executor, &1, &1, &500, &5, &100, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 1);
+    assert!(client
+        .try_renew(&executor, &1, &2, &500, &5, &100, &true)
+        .is_err());
+    assert!(client.renew(&owner, &1, &2, &500, &5, &100, &true));
+    assert_eq!(
+        event_field::<u64>(&env, &client, "cooldown_bypassed", "sub_id"),
+        1
+    );
+
+    // A new cycle brings a new early retry
+    client.renew(&executor, &1, &3, &500, &5, &100, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 1);
+    client.renew(&owner, &1, &3, &500, &5, &100, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 1);
+    assert!(client
+        .try_renew(&owner, &1, &4, &500, &5, &100, &true)
+        .is_err());
+}
+
+#[test]
+fn test_multiple_failures_then_success() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 222);
+    client.approve_renewal(&222, &1, &1000, &1000);
+    client.approve_renewal(&222, &2, &1000, &1000);
+    client.approve_renewal(&222, &3, &1000, &1000);
+
+    client.renew(&owner, &222, &1, &500, &3, &10, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    client.renew(&owner, &222, &2, &500, &3, &10, &false);
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    assert!(client.renew(&owner, &222, &3, &500, &3, &10, &true));
+
+    let data = client.get_sub(&222);
+    assert_eq!(data.state, SubscriptionState::Active);
+    assert_eq!(data.failure_count, 0);
+}
+
+#[test]
+#[should_panic(expected = "Subscription is in FAILED state")]
+fn test_cannot_renew_failed_subscription() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 333);
+    client.approve_renewal(&333, &1, &1000, &1000);
+    client.approve_renewal(&333, &2, &1000, &1000);
+
+    client.renew(&owner, &333, &1, &500, &0, &10, &false);
+    assert_eq!(client.get_sub(&333).state, SubscriptionState::Failed);
+
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    client.renew(&owner, &333, &2, &500, &0, &10, &true);
+}
+
+#[test]
+fn test_zero_max_retries() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 111);
+    client.approve_renewal(&111, &1, &1000, &100);
+
+    assert!(!client.renew(&owner, &111, &1, &500, &0, &10, &false));
+    assert_eq!(client.get_sub(&111).state, SubscriptionState::Failed);
+}
+
+#[test]
+fn test_event_emission_on_success() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 999);
+    client.approve_renewal(&999, &1, &1000, &100);
+
+    client.renew(&owner, &999, &1, &500, &3, &10, &true);
+    assert!(!env.events().all().is_empty());
+}
+
+#[test]
+fn test_renewal_seq_counts_successes() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    for approval_id in 1..=3 {
+        client.approve_renewal(&1, &approval_id, &1000, &100);
+    }
+    assert_eq!(client.get_sub(&1).renewal_seq, 0);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    client.renew(&owner, &1, &2, &500, &3, &0, &false);
+    client.renew(&owner, &1, &3, &500, &3, &0, &true);
+
+    let (_, _, data) = env.events().all().last().unwrap();
+    let data: Map<Symbol, Val> = data.into_val(&env);
+    let seq: u64 = data
+        .get(Symbol::new(&env, "renewal_seq"))
+        .unwrap()
+        .into_val(&env);
+    assert_eq!(seq, 2);
+    assert_eq!(client.get_sub(&1).renewal_seq, 2);
+}
+
+#[test]
+fn test_last_event_seq_tracks_renewal_events() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    for approval_id in 1..=2 {
+        client.approve_renewal(&1, &approval_id, &1000, &100);
+    }
+    assert_eq!(
+        client.get_last_event_seq(&1),
+        EventCursor {
+            renewal_seq: 0,
+            ledger: 0
+        }
+    );
+
+    env.ledger().with_mut(|li| li.sequence_number = 10);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    env.ledger().with_mut(|li| li.sequence_number = 20);
+    client.renew(&owner, &1, &2, &500, &3, &0, &false);
+
+    // The failure moved the ledger but not the sequence
+    assert_eq!(
+        client.get_last_event_seq(&1),
+        EventCursor {
+            renewal_seq: 1,
+            ledger: 20
+        }
+    );
+}
+
+#[test]
+fn test_renewal_extends_ttl() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    env.ledger().with_mut(|li| li.sequence_number = 10);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    assert_eq!(
+        event_field::<TtlKeyKind>(&env, &client, "ttl_extended", "key_kind"),
+        TtlKeyKind::Subscription
+    );
+    assert_eq!(
+        event_field::<u32>(&env, &client, "ttl_extended", "new_live_until"),
+        10 + RENEWAL_TTL_LEDGERS
+    );
+    let ttl = env.as_contract(&client.address, || {
+        env.storage().persistent().get_ttl(&1u64)
+    });
+    assert_eq!(ttl, RENEWAL_TTL_LEDGERS);
+}
+
+#[test]
+fn test_quiet_events_still_extend_ttl() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    assert!(client.get_verbose_events());
+    client.set_verbose_events(&false);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    let emitted: std::vec::Vec<Symbol> = env
+        .events()
+        .all()
+        .iter()
+        .map(|(_, topics, _)| topics.get(0).unwrap().into_val(&env))
+        .collect();
+    assert!(emitted.contains(&Symbol::new(&env, "renewal_success")));
+    assert!(!emitted.contains(&Symbol::new(&env, "ttl_extended")));
+    let ttl = env.as_contract(&client.address, || {
+        env.storage().persistent().get_ttl(&1u64)
+    });
+    assert_eq!(ttl, RENEWAL_TTL_LEDGERS);
+}
+
+/// Fail renewals with `max_retries = 2` until the subscription gives up;
+/// returns how many failures that took.
+fn failures_until_failed(env: &Env, client: &SubscriptionRenewalContractClient) -> u32 {
+    let owner = new_sub(env, client, 1);
+    for attempt in 1..=5 {
+        client.approve_renewal(&1, &attempt, &1000, &100);
+        client.renew(&owner, &1, &attempt, &500, &2, &0, &false);
+        if client.get_sub(&1).state == SubscriptionState::Failed {
+            return attempt as u32;
+        }
+    }
+    panic!("subscription never failed");
+}
+
+#[test]
+fn test_failure_event_hints_next_attempt() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    env.ledger().with_mut(|li| li.sequence_number = 40);
+
+    client.renew(&owner, &1, &1, &500, &3, &15, &false);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "renewal_failed", "next_attempt_after"),
+        55
+    );
+}
+
+#[test]
+fn test_final_failure_has_no_next_attempt() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&owner, &1, &1, &500, &0, &15, &false);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "renewal_failed", "next_attempt_after"),
+        0
+    );
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Failed);
+}
+
+#[test]
+fn test_retry_counting_defaults_to_retries() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    assert_eq!(client.get_retry_counting(), RetryCounting::Retries);
+    // The first attempt plus two retries.
+    assert_eq!(failures_until_failed(&env, &client), 3);
+}
+
+#[test]
+fn test_retry_counting_attempts() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    client.set_retry_counting(&RetryCounting::Attempts);
+    // Two attempts in total.
+    assert_eq!(failures_until_failed(&env, &client), 2);
+}
+
+// ── Renewal window ────────────────────────────────────────────────
+
+/// Subscription 1 due at ledger 100, every 100 ledgers, with a 10-ledger
+/// renewal window, and approvals 1..=5.
+fn scheduled_sub(env: &Env, client: &SubscriptionRenewalContractClient) -> Address {
+    let owner = new_sub(env, client, 1);
+    client.set_schedule(&1, &100, &100, &10);
+    for approval_id in 1..=5 {
+        client.approve_renewal(&1, &approval_id, &1000, &1000);
+    }
+    owner
+}
+
+#[test]
+fn test_renew_within_window_advances_due_date() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    env.ledger().with_mut(|li| li.sequence_number = 110);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 200);
+}
+
+#[test]
+#[should_panic(expected = "Renewal not yet due")]
+fn test_renew_before_due_date_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_renew_after_window_marks_cycles_missed() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    // Windows for the cycles due at 100 and 200 have both closed.
+    env.ledger().with_mut(|li| li.sequence_number = 250);
+    assert!(!client.renew(&owner, &1, &1, &500, &3, &0, &true));
+
+    let schedule = client.get_schedule(&1).unwrap();
+    assert_eq!(schedule.missed_cycles, 2);
+    assert_eq!(schedule.next_due, 300);
+    assert_eq!(client.get_sub(&1).renewal_seq, 0);
+    // Nothing was charged, so the approval is still available.
+    assert!(!client.get_approval(&1, &1).unwrap().used);
+
+    env.ledger().with_mut(|li| li.sequence_number = 300);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_failed_renewal_keeps_due_date_for_retry() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(!client.renew(&owner, &1, &1, &500, &3, &0, &false));
+    env.ledger().with_mut(|li| li.sequence_number = 105);
+    assert!(client.renew(&owner, &1, &2, &500, &3, &0, &true));
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 200);
+}
+
+#[test]
+fn test_current_cycle_derived_from_start() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    assert_eq!(client.current_cycle(&1), Some(0));
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert_eq!(client.current_cycle(&1), Some(1));
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+
+    // Two cycles later, the missed one doesn't shift the numbering
+    env.ledger().with_mut(|li| li.sequence_number = 305);
+    assert_eq!(client.current_cycle(&1), Some(3));
+    assert_eq!(client.current_cycle(&2), None);
+}
+
+#[test]
+fn test_entitlement_runs_to_end_of_window() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+
+    // Before the first due date, e.g. a trial
+    assert!(client.is_entitled(&1));
+    env.ledger().with_mut(|li| li.sequence_number = 110);
+    assert!(client.is_entitled(&1));
+    env.ledger().with_mut(|li| li.sequence_number = 111);
+    assert!(!client.is_entitled(&1));
+
+    env.ledger().with_mut(|li| li.sequence_number = 105);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    env.ledger().with_mut(|li| li.sequence_number = 210);
+    assert!(client.is_entitled(&1));
+    client.prepay_cycles(&1, &1, &500);
+    env.ledger().with_mut(|li| li.sequence_number = 310);
+    assert!(client.is_entitled(&1));
+    env.ledger().with_mut(|li| li.sequence_number = 311);
+    assert!(!client.is_entitled(&1));
+    assert!(!client.is_entitled(&2));
+}
+
+#[test]
+fn test_entitlement_by_state() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    assert!(client.is_entitled(&1));
+
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.renew(&owner, &1, &1, &500, &2, &0, &false);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Retrying);
+    assert!(client.is_entitled(&1));
+    client.renew(&owner, &1, &1, &500, &2, &0, &false);
+    client.renew(&owner, &1, &1, &500, &2, &0, &false);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Failed);
+    assert!(!client.is_entitled(&1));
+
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+    assert!(!client.is_entitled(&1));
+}
+
+#[test]
+fn test_no_entitlement_while_paused_or_frozen() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+
+    client.pause_sub(&owner, &1);
+    assert!(!client.is_entitled(&1));
+    client.resume_sub(&1, &vec![&env, owner]);
+    assert!(client.is_entitled(&1));
+
+    client.freeze_sub(&admin, &1, &symbol_short!("legal"));
+    assert!(!client.is_entitled(&1));
+    client.unfreeze_sub(&admin, &1);
+    assert!(client.is_entitled(&1));
+}
+
+#[test]
+#[should_panic(expected = "Cycle mismatch")]
+fn test_renew_rejects_due_date_off_cycle() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    let mut schedule = client.get_schedule(&1).unwrap();
+    schedule.next_due = 150;
+    env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .set(&SubKey::Schedule(1), &schedule)
+    });
+
+    env.ledger().with_mut(|li| li.sequence_number = 150);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+/// Field `name` of the last event's data map.
+fn last_event_field<T: TryFromVal<Env, Val>>(env: &Env, name: &str) -> T {
+    let (_, _, data) = env.events().all().last().unwrap();
+    let data: Map<Symbol, Val> = data.into_val(env);
+    T::try_from_val(env, &data.get(Symbol::new(env, name)).unwrap()).unwrap()
+}
+
+/// Field `name` of the last `event` the contract emitted in the last call.
+fn event_field<T: TryFromVal<Env, Val>>(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    event: &str,
+    name: &str,
+) -> T {
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, topics, _)| {
+            *contract == client.address
+                && Symbol::try_from_val(env, &topics.get(0).unwrap()) == Ok(Symbol::new(env, event))
+        })
+        .last()
+        .unwrap();
+    let data: Map<Symbol, Val> = data.into_val(env);
+    T::try_from_val(env, &data.get(Symbol::new(env, name)).unwrap()).unwrap()
+}
+
+/// Topic `index` of the last `event` the contract emitted in the last
+/// call; topic 0 is the event's name.
+fn event_topic<T: TryFromVal<Env, Val>>(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    event: &str,
+    index: u32,
+) -> T {
+    let (_, topics, _) = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, topics, _)| {
+            *contract == client.address
+                && Symbol::try_from_val(env, &topics.get(0).unwrap()) == Ok(Symbol::new(env, event))
+        })
+        .last()
+        .unwrap();
+    T::try_from_val(env, &topics.get(index).unwrap()).unwrap()
+}
+
+#[test]
+fn test_early_renewal_discount() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_early_discount(&client.get_sub(&1).merchant, &200);
+
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 490);
+    assert_eq!(last_event_field::<i128>(&env, "discount"), 10);
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 200);
+}
+
+#[test]
+fn test_on_time_renewal_has_no_discount() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_early_discount(&client.get_sub(&1).merchant, &200);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 500);
+    assert_eq!(last_event_field::<i128>(&env, "discount"), 0);
+}
+
+#[test]
+#[should_panic(expected = "Renewal not yet due")]
+fn test_early_renewal_only_one_cycle_ahead() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_early_discount(&client.get_sub(&1).merchant, &200);
+
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    client.renew(&owner, &1, &2, &500, &3, &0, &true);
+}
+
+#[test]
+#[should_panic(expected = "Renewal not yet due")]
+fn test_executor_cannot_renew_early() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    scheduled_sub(&env, &client);
+    client.set_early_discount(&client.get_sub(&1).merchant, &200);
+    let executor = Address::generate(&env);
+    client.set_executor(&1, &executor);
+
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+    client.renew(&executor, &1, &1, &500, &3, &0, &true);
+}
+
+// ── Pricing ───────────────────────────────────────────────────────
+
+#[test]
+fn test_pricing_amount_and_receipt() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_pricing(&1, &150, &4);
+    client.approve_renewal(&1, &1, &1000, &100);
+    assert_eq!(client.get_amount(&1), Some(600));
+
+    assert!(client.renew(&owner, &1, &1, &600, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "unit_price"), 150);
+    assert_eq!(last_event_field::<u32>(&env, "quantity"), 4);
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 600);
+}
+
+#[test]
+#[should_panic(expected = "Amount does not match pricing")]
+fn test_renew_amount_must_match_pricing() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_pricing(&1, &150, &4);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_unpriced_receipt_is_single_unit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    assert_eq!(client.get_amount(&1), None);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "unit_price"), 500);
+    assert_eq!(last_event_field::<u32>(&env, "quantity"), 1);
+}
+
+// ── Templates ─────────────────────────────────────────────────────
+
+/// Template: 500 per 100 ledgers, 10-ledger window, 50-ledger trial, one
+/// retry with a 5-ledger cooldown.
+fn template(env: &Env, client: &SubscriptionRenewalContractClient) -> u64 {
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    client.create_template(&SubscriptionTemplate {
+        merchant: Address::generate(env),
+        token,
+        pricing: Pricing {
+            unit_price: 500,
+            quantity: 1,
+        },
+        interval_ledgers: 100,
+        window_ledgers: 10,
+        trial_ledgers: 50,
+        retry: RetryPolicy {
+            max_retries: 1,
+            cooldown_ledgers: 5,
+        },
+    })
+}
+
+#[test]
+fn test_subscribe_from_template_copies_terms() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let terms = client.get_template(&template_id).unwrap();
+    let owner = Address::generate(&env);
+    env.ledger().with_mut(|li| li.sequence_number = 10);
+
+    client.subscribe_from_template(&owner, &1, &template_id, &None, &None);
+    let sub = client.get_sub(&1);
+    assert_eq!(sub.owner, owner);
+    assert_eq!(sub.merchant, terms.merchant);
+    assert_eq!(sub.token, terms.token);
+    assert_eq!(client.get_pricing(&1), Some(terms.pricing));
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 60);
+    assert_eq!(client.get_sub_template(&1), Some(template_id));
+}
+
+#[test]
+fn test_subscribe_and_approve_in_one_call() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let owner = Address::generate(&env);
+
+    client.subscribe_and_approve(
+        &owner,
+        &1,
+        &template_id,
+        &ApprovalTerms {
+            approval_id: 1,
+            max_spend: 500,
+            expires_at: 1000,
+            cycles: 3,
+        },
+    );
+
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(client.get_sub_template(&1), Some(template_id));
+    for approval_id in 1..=3 {
+        assert_eq!(
+            client.get_approval(&1, &approval_id).unwrap().max_spend,
+            500
+        );
+    }
+    assert_eq!(client.get_approval(&1, &4), None);
+
+    let token = client.get_sub(&1).token;
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &500);
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+    assert!(client.renew(&owner, &1, &1, &500, &0, &0, &true));
+}
+
+#[test]
+#[should_panic(expected = "Invalid cycle count")]
+fn test_subscribe_and_approve_needs_a_cycle() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+
+    client.subscribe_and_approve(
+        &Address::generate(&env),
+        &1,
+        &template_id,
+        &ApprovalTerms {
+            approval_id: 1,
+            max_spend: 500,
+            expires_at: 1000,
+            cycles: 0,
+        },
+    );
+}
+
+#[test]
+fn test_first_charge_at_signup() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let mut terms = client.get_template(&template(&env, &client)).unwrap();
+    terms.trial_ledgers = 0;
+    let template_id = client.create_template(&terms);
+    let owner = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &terms.token).mint(&owner, &500);
+    env.ledger().with_mut(|li| li.sequence_number = 10);
+
+    client.subscribe_from_template(
+        &owner,
+        &1,
+        &template_id,
+        &Some(FirstCharge {
+            approval_id: 1,
+            amount: 500,
+        }),
+        &None,
+    );
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+    assert!(client.get_approval(&1, &1).unwrap().used);
+    // The next charge is a full interval after sign-up
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 110);
+    assert_eq!(client.get_sub_economics(&1).charged, 500);
+}
+
+#[test]
+#[should_panic(expected = "Template has a trial period")]
+fn test_first_charge_conflicts_with_trial() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+
+    client.subscribe_from_template(
+        &Address::generate(&env),
+        &1,
+        &template_id,
+        &Some(FirstCharge {
+            approval_id: 1,
+            amount: 500,
+        }),
+        &None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Subscription follows its template's terms")]
+fn test_template_sub_pricing_is_fixed() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    client.subscribe_from_template(&Address::generate(&env), &1, &template_id, &None, &None);
+
+    client.set_pricing(&1, &1, &1);
+}
+
+#[test]
+fn test_template_sub_schedule_and_overage_are_fixed() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    client.subscribe_from_template(&Address::generate(&env), &1, &template_id, &None, &None);
+    let schedule = client.get_schedule(&1);
+
+    assert!(client.try_set_schedule(&1, &0, &1_000, &0).is_err());
+    assert!(client
+        .try_set_overage_pricing(
+            &1,
+            &Some(OveragePricing {
+                included_usage: 0,
+                overage_price: 0,
+            }),
+        )
+        .is_err());
+    assert_eq!(client.get_schedule(&1), schedule);
+    assert_eq!(client.get_overage_pricing(&1), None);
+}
+
+#[test]
+fn test_init_sub_needs_owner_auth() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(env.auths()[0].0, owner);
+}
+
+#[test]
+fn test_init_sub_with_first_charge() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &300);
+
+    client.init_sub(
+        &owner,
+        &1,
+        &Address::generate(&env),
+        &token,
+        &Some(FirstCharge {
+            approval_id: 7,
+            amount: 300,
+        }),
+        &None,
+    );
+    // The owner's one signature on init_sub covers the first renewal
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(env.auths()[0].0, owner);
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+    assert_eq!(client.get_sub_economics(&1).charged, 300);
+    assert!(client
+        .try_init_sub(
+            &owner,
+            &2,
+            &Address::generate(&env),
+            &token,
+            &Some(FirstCharge {
+                approval_id: 1,
+                amount: 0,
+            }),
+            &None,
+        )
+        .is_err());
+}
+
+#[test]
+fn test_renewal_rejected_before_start_ledger() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &500);
+    client.init_sub(
+        &owner,
+        &1,
+        &Address::generate(&env),
+        &token,
+        &None,
+        &Some(100),
+    );
+    client.approve_renewal(&1, &1, &500, &1000);
+    assert_eq!(client.get_start_ledger(&1), Some(100));
+
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    assert_eq!(
+        client.try_renew(&owner, &1, &1, &500, &3, &0, &true),
+        Err(Ok(Error::NotStarted.into()))
+    );
+    let diagnostics = client.simulate_renew(&owner, &1, &1, &500, &0);
+    assert_eq!(diagnostics.starts_at, Some(100));
+    assert!(!diagnostics.ok);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.simulate_renew(&owner, &1, &1, &500, &0).ok);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_template_schedule_begins_at_start_ledger() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    env.ledger().with_mut(|li| li.sequence_number = 10);
+
+    client.subscribe_from_template(
+        &Address::generate(&env),
+        &1,
+        &template_id,
+        &None,
+        &Some(200),
+    );
+    // The 50-ledger trial runs from the start ledger
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 250);
+    assert_eq!(client.get_start_ledger(&1), Some(200));
+
+    // A start that has already passed is no deferral
+    client.subscribe_from_template(&Address::generate(&env), &2, &template_id, &None, &Some(5));
+    assert_eq!(client.get_schedule(&2).unwrap().next_due, 60);
+    assert_eq!(client.get_start_ledger(&2), None);
+}
+
+#[test]
+fn test_template_retry_policy_overrides_call() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let owner = Address::generate(&env);
+    client.subscribe_from_template(&owner, &1, &template_id, &None, &None);
+    for approval_id in 1..=2 {
+        client.approve_renewal(&1, &approval_id, &1000, &1000);
+    }
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+
+    // The call asks for 5 retries; the template allows one
+    client.renew(&owner, &1, &1, &500, &5, &0, &false);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "renewal_failed", "next_attempt_after"),
+        55
+    );
+    env.ledger().with_mut(|li| li.sequence_number = 55);
+    client.renew(&owner, &1, &2, &500, &5, &0, &false);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Failed);
+}
+
+#[test]
+#[should_panic(expected = "Subscription already exists")]
+fn test_template_cannot_overwrite_subscription() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    new_sub(&env, &client, 1);
+
+    client.subscribe_from_template(&Address::generate(&env), &1, &template_id, &None, &None);
+}
+
+#[test]
+fn test_plan_migration_after_notice() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let old_plan = template(&env, &client);
+    let terms = client.get_template(&old_plan).unwrap();
+    let new_plan = client.create_template(&SubscriptionTemplate {
+        pricing: Pricing {
+            unit_price: 700,
+            quantity: 1,
+        },
+        retry: RetryPolicy {
+            max_retries: 4,
+            cooldown_ledgers: 1,
+        },
+        ..terms
+    });
+    for sub_id in 1..=3 {
+        client.subscribe_from_template(&Address::generate(&env), &sub_id, &old_plan, &None, &None);
+    }
+
+    // Two batches: the cursor resumes where the first stopped
+    assert_eq!(client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &2), 2);
+    assert_eq!(client.migrate_subs_to_plan(&old_plan, &new_plan, &2, &2), 3);
+    assert_eq!(client.get_subscriber_count(&new_plan), 3);
+
+    assert_eq!(client.get_sub_template(&3), Some(old_plan));
+    assert_eq!(client.get_amount(&3), Some(500));
+    env.ledger()
+        .with_mut(|li| li.sequence_number = PLAN_MIGRATION_NOTICE_LEDGERS);
+    assert_eq!(client.get_sub_template(&3), Some(new_plan));
+    assert_eq!(client.get_amount(&3), Some(700));
+    assert_eq!(client.get_retry_policy(&3).unwrap().max_retries, 4);
+}
+
+#[test]
+fn test_plan_migration_moves_schedule_and_retries() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let old_plan = template(&env, &client);
+    let terms = client.get_template(&old_plan).unwrap();
+    let new_plan = client.create_template(&SubscriptionTemplate {
+        interval_ledgers: 1_000,
+        window_ledgers: 20,
+        retry: RetryPolicy {
+            max_retries: 4,
+            cooldown_ledgers: 1,
+        },
+        ..terms.clone()
+    });
+    let owner = Address::generate(&env);
+    token::StellarAssetClient::new(&env, &terms.token).mint(&owner, &1_000);
+    client.subscribe_from_template(&owner, &1, &old_plan, &None, &None);
+    for approval_id in 1..=2 {
+        client.approve_renewal(&1, &approval_id, &1000, &u32::MAX);
+    }
+    client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &10);
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number = PLAN_MIGRATION_NOTICE_LEDGERS);
+    let schedule = client.get_schedule(&1).unwrap();
+    assert_eq!(
+        (schedule.interval_ledgers, schedule.window_ledgers),
+        (1_000, 20)
+    );
+    assert_eq!(schedule.next_due, 50);
+
+    // Cycles missed under the notice are counted on the new interval
+    assert!(!client.renew(&owner, &1, &1, &500, &0, &0, &true));
+    let due = client.get_schedule(&1).unwrap().next_due;
+    assert_eq!(due, 121_050);
+    env.ledger().with_mut(|li| li.sequence_number = due);
+    client.renew(&owner, &1, &1, &500, &0, &0, &false);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "renewal_failed", "next_attempt_after"),
+        due + 1
+    );
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Retrying);
+
+    env.ledger().with_mut(|li| li.sequence_number = due + 1);
+    assert!(client.renew(&owner, &1, &2, &500, &0, &0, &true));
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, due + 1_000);
+}
+
+#[test]
+fn test_plan_migration_skips_pending_price_change() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let old_plan = template(&env, &client);
+    let new_plan = client.create_template(&client.get_template(&old_plan).unwrap());
+    client.subscribe_from_template(&Address::generate(&env), &1, &old_plan, &None, &None);
+    client.subscribe_from_template(&Address::generate(&env), &2, &old_plan, &None, &None);
+    client.schedule_price_change(&1, &600, &1, &1_000);
+
+    assert_eq!(
+        client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &10),
+        2
+    );
+    assert_eq!(client.get_plan_migration(&1), None);
+    assert_eq!(client.get_price_change(&1).unwrap().pricing.unit_price, 600);
+    assert!(client.get_plan_migration(&2).is_some());
+
+    // Once the change applied, the subscriber can move
+    env.ledger().with_mut(|li| li.sequence_number = 1_000);
+    client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &10);
+    assert!(client.get_plan_migration(&1).is_some());
+    assert_eq!(client.get_subscriber_count(&new_plan), 2);
+}
+
+#[test]
+fn test_plan_migration_skips_already_scheduled() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let old_plan = template(&env, &client);
+    let terms = client.get_template(&old_plan).unwrap();
+    let new_plan = client.create_template(&terms);
+    client.subscribe_from_template(&Address::generate(&env), &1, &old_plan, &None, &None);
+
+    client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &10);
+    client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &10);
+    assert_eq!(client.get_subscriber_count(&new_plan), 1);
+}
+
+#[test]
+#[should_panic(expected = "Plans must share merchant and token")]
+fn test_plan_migration_across_merchants_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let old_plan = template(&env, &client);
+    let other = template(&env, &client);
+
+    client.migrate_subs_to_plan(&old_plan, &other, &0, &10);
+}
+
+/// Template with `count` subscribers and an identical plan to move them to.
+fn plan_with_subscribers(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    count: u64,
+) -> (u64, u64) {
+    let old_plan = template(env, client);
+    let new_plan = client.create_template(&client.get_template(&old_plan).unwrap());
+    for sub_id in 1..=count {
+        client.subscribe_from_template(&Address::generate(env), &sub_id, &old_plan, &None, &None);
+    }
+    (old_plan, new_plan)
+}
+
+#[test]
+fn test_plan_migration_page_clamped() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (old_plan, new_plan) = plan_with_subscribers(&env, &client, MAX_MIGRATION_PAGE as u64 + 1);
+
+    let cursor = client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &u32::MAX);
+    assert_eq!(cursor, MAX_MIGRATION_PAGE);
+    assert_eq!(client.get_subscriber_count(&new_plan), MAX_MIGRATION_PAGE);
+    let cursor = client.migrate_subs_to_plan(&old_plan, &new_plan, &cursor, &u32::MAX);
+    assert_eq!(cursor, MAX_MIGRATION_PAGE + 1);
+}
+
+#[test]
+fn test_migration_page_cost() {
+    let cost = &crate::paging::MIGRATION;
+    for items in [1, MAX_MIGRATION_PAGE] {
+        let env = Env::default();
+        let (client, _) = setup(&env);
+        let (old_plan, new_plan) = plan_with_subscribers(&env, &client, items as u64);
+        client.migrate_subs_to_plan(&old_plan, &new_plan, &0, &items);
+
+        let metered = env.cost_estimate().resources();
+        assert!(
+            metered.memory_read_entries + metered.disk_read_entries
+                <= cost.fixed.entries + items * cost.item.entries
+        );
+        assert!(metered.write_entries <= cost.fixed.writes + items * cost.item.writes);
+        assert!(
+            metered.instructions as u64
+                <= cost.fixed.instructions + items as u64 * cost.item.instructions
+        );
+    }
+}
+
+// ── Invitations ───────────────────────────────────────────────────
+
+/// Merchant offers subscription 1 to a funded owner, open until ledger 50.
+fn offered_sub(env: &Env, client: &SubscriptionRenewalContractClient) -> Address {
+    let owner = Address::generate(env);
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(env, &token).mint(&owner, &1_000);
+    client.offer_sub(&Address::generate(env), &1, &owner, &token, &50);
+    owner
+}
+
+#[test]
+fn test_accepted_offer_becomes_active() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = offered_sub(&env, &client);
+    assert_eq!(
+        client.get_sub(&1).state,
+        SubscriptionState::PendingAcceptance
+    );
+
+    client.accept_sub(&1, &1, &1000, &100);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Active);
+    assert_eq!(client.get_offer_expiry(&1), None);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+#[should_panic(expected = "Subscription not accepted")]
+fn test_pending_offer_cannot_renew() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = offered_sub(&env, &client);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+#[should_panic(expected = "Offer expired")]
+fn test_expired_offer_cannot_be_accepted() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+    env.ledger().with_mut(|li| li.sequence_number = 51);
+
+    client.accept_sub(&1, &1, &1000, &100);
+}
+
+#[test]
+fn test_declined_offer_is_deleted() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+
+    client.decline_sub(&1);
+    assert!(client.try_get_sub(&1).is_err());
+    assert_eq!(client.get_offer_expiry(&1), None);
+}
+
+#[test]
+fn test_anyone_can_expire_stale_offer() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+    env.ledger().with_mut(|li| li.sequence_number = 51);
+
+    client.expire_offer(&1);
+    assert_eq!(last_event_field::<u32>(&env, "expired_at"), 50);
+    assert!(client.try_get_sub(&1).is_err());
+    assert_eq!(client.get_offer_expiry(&1), None);
+}
+
+#[test]
+#[should_panic(expected = "Offer not expired")]
+fn test_open_offer_cannot_be_expired() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+
+    client.expire_offer(&1);
+}
+
+#[test]
+#[should_panic(expected = "Subscription is not pending acceptance")]
+fn test_accepted_subscription_cannot_be_expired() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    offered_sub(&env, &client);
+    client.accept_sub(&1, &1, &1000, &100);
+    env.ledger().with_mut(|li| li.sequence_number = 51);
+
+    client.expire_offer(&1);
+}
+
+// ── Scheduled changes ─────────────────────────────────────────────
+
+#[test]
+fn test_price_change_applies_at_effective_ledger() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_pricing(&1, &500, &1);
+    client.schedule_price_change(&1, &600, &1, &100);
+    client.approve_renewal(&1, &1, &1000, &1000);
+    client.approve_renewal(&1, &2, &1000, &1000);
+
+    assert_eq!(client.get_amount(&1), Some(500));
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert_eq!(client.get_amount(&1), Some(600));
+    assert!(client.renew(&owner, &1, &2, &600, &3, &0, &true));
+}
+
+#[test]
+fn test_change_notice_once_per_window() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_pricing(&1, &500, &1);
+    client.schedule_price_change(&1, &600, &1, &50_000);
+
+    client.emit_change_notice(&1);
+    assert_eq!(last_event_field::<i128>(&env, "new_amount"), 600);
+    assert_eq!(last_event_field::<u32>(&env, "effective_at"), 50_000);
+    assert!(client.try_emit_change_notice(&1).is_err());
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number = CHANGE_NOTICE_LEDGERS);
+    client.emit_change_notice(&1);
+}
+
+#[test]
+#[should_panic(expected = "No upcoming change")]
+fn test_no_notice_once_change_applies() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.schedule_price_change(&1, &600, &1, &100);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.emit_change_notice(&1);
+}
+
+#[test]
+fn test_pending_changes_lists_queued_changes() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    recoverable_sub(&env, &client);
+    assert_eq!(client.get_pending_changes(&1), vec![&env]);
+
+    client.set_pricing(&1, &500, &1);
+    client.schedule_price_change(&1, &600, &1, &50_000);
+    let new_owner = Address::generate(&env);
+    client.request_recovery(&1, &new_owner);
+
+    let recovery = PendingRecovery {
+        new_owner,
+        executable_at: 100,
+    };
+    assert_eq!(
+        client.get_pending_changes(&1),
+        vec![
+            &env,
+            PendingChange::PriceChange(client.get_price_change(&1).unwrap()),
+            PendingChange::Transfer(recovery.clone()),
+        ]
+    );
+
+    // The price change applies; the recovery waits to be executed
+    env.ledger().with_mut(|li| li.sequence_number = 50_000);
+    assert_eq!(
+        client.get_pending_changes(&1),
+        vec![&env, PendingChange::Transfer(recovery)]
+    );
+}
+
+// ── Metered usage ─────────────────────────────────────────────────
+
+/// Threshold percentages of the `UsageThresholdReached` events emitted by
+/// the last call.
+fn usage_thresholds(env: &Env) -> std::vec::Vec<u32> {
+    env.events()
+        .all()
+        .iter()
+        .filter(|(_, topics, _)| {
+            let name: Symbol = topics.get(0).unwrap().into_val(env);
+            name == Symbol::new(env, "usage_threshold_reached")
+        })
+        .map(|(_, _, data)| {
+            let data: Map<Symbol, Val> = data.into_val(env);
+            data.get(Symbol::new(env, "threshold_pct"))
+                .unwrap()
+                .into_val(env)
+        })
+        .collect()
+}
+
+#[test]
+fn test_usage_threshold_events() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_usage_budget(&1, &Some(100));
+
+    assert_eq!(client.record_usage(&1, &40), 40);
+    assert!(usage_thresholds(&env).is_empty());
+    assert_eq!(client.record_usage(&1, &45), 45);
+    assert_eq!(usage_thresholds(&env), [50, 80]);
+    assert_eq!(client.record_usage(&1, &15), 15);
+    assert_eq!(usage_thresholds(&env), [100]);
+}
+
+#[test]
+fn test_quiet_events_skip_usage_thresholds() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_usage_budget(&1, &Some(100));
+    client.set_verbose_events(&false);
+
+    assert_eq!(client.record_usage(&1, &100), 100);
+    assert!(usage_thresholds(&env).is_empty());
+}
+
+#[test]
+fn test_usage_accrual_stops_at_budget() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_usage_budget(&1, &Some(100));
+
+    assert_eq!(client.record_usage(&1, &90), 90);
+    assert_eq!(client.record_usage(&1, &50), 10);
+    assert_eq!(client.record_usage(&1, &5), 0);
+    assert_eq!(client.get_usage(&1), 100);
+}
+
+#[test]
+fn test_usage_resets_each_cycle() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.record_usage(&1, &70);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(client.get_usage(&1), 0);
+}
+
+// ── Overage billing ───────────────────────────────────────────────
+
+fn overage(included_usage: u64, overage_price: i128) -> Option<OveragePricing> {
+    Some(OveragePricing {
+        included_usage,
+        overage_price,
+    })
+}
+
+#[test]
+fn test_overage_billed_on_top_of_base() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_overage_pricing(&1, &overage(100, 3));
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.record_usage(&1, &130);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<u64>(&env, "overage_units"), 30);
+    assert_eq!(last_event_field::<i128>(&env, "overage"), 90);
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 590);
+}
+
+#[test]
+fn test_usage_within_allowance_has_no_overage() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_overage_pricing(&1, &overage(100, 3));
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.record_usage(&1, &100);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "overage"), 0);
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 500);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_overage_bounded_by_approval() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_overage_pricing(&1, &overage(100, 3));
+    client.approve_renewal(&1, &1, &550, &100);
+    client.record_usage(&1, &130);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+// ── Prepaid cycles ────────────────────────────────────────────────
+
+/// Scheduled subscription 1 paying a real Stellar asset, with the owner
+/// funded with `balance`. Returns (owner, token client).
+fn prepay_sub<'a>(
+    env: &'a Env,
+    client: &SubscriptionRenewalContractClient,
+    balance: i128,
+) -> (Address, token::Client<'a>) {
+    let owner = Address::generate(env);
+    let merchant = Address::generate(env);
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(env, &token).mint(&owner, &balance);
+
+    client.init_sub(&owner, &1, &merchant, &token, &None, &None);
+    client.set_schedule(&1, &100, &100, &10);
+    (owner, token::Client::new(env, &token))
+}
+
+#[test]
+fn test_prepay_cycles_charges_with_discount() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, token) = prepay_sub(&env, &client, 10_000);
+    let merchant = client.get_sub(&1).merchant;
+    client.set_prepay_discount(&merchant, &1_500);
+
+    assert_eq!(client.prepay_cycles(&1, &12, &500), 5_100);
+    assert_eq!(token.balance(&owner), 4_900);
+    assert_eq!(token.balance(&merchant), 5_100);
+    assert_eq!(client.get_schedule(&1).unwrap().prepaid_cycles, 12);
+}
+
+#[test]
+fn test_prepaid_cycles_consumed_without_approval() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, token) = prepay_sub(&env, &client, 10_000);
+    client.prepay_cycles(&1, &2, &500);
+    let after_prepay = token.balance(&owner);
+
+    for due in [100, 200] {
+        env.ledger().with_mut(|li| li.sequence_number = due);
+        // No approval 1 exists; the prepaid cycle covers the renewal.
+        assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    }
+    let schedule = client.get_schedule(&1).unwrap();
+    assert_eq!(schedule.prepaid_cycles, 0);
+    assert_eq!(schedule.next_due, 300);
+    assert_eq!(client.get_sub(&1).renewal_seq, 2);
+    assert_eq!(token.balance(&owner), after_prepay);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_renewal_needs_approval_once_prepaid_cycles_run_out() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, _) = prepay_sub(&env, &client, 10_000);
+    client.prepay_cycles(&1, &1, &500);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    env.ledger().with_mut(|li| li.sequence_number = 200);
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_prepaid_cycle_still_bills_overage() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, _) = prepay_sub(&env, &client, 10_000);
+    client.prepay_cycles(&1, &1, &500);
+    client.set_overage_pricing(&1, &overage(0, 2));
+    client.approve_renewal(&1, &1, &100, &1000);
+    client.record_usage(&1, &40);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 80);
+    assert_eq!(client.get_schedule(&1).unwrap().prepaid_cycles, 0);
+    assert!(client.get_approval(&1, &1).unwrap().used);
+}
+
+#[test]
+#[should_panic(expected = "Subscription has no schedule")]
+fn test_prepay_requires_schedule() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.prepay_cycles(&1, &12, &500);
+}
+
+// ── Account credit ────────────────────────────────────────────────
+
+/// Subscription 1 on a real token, with `granter` funded to grant credit.
+fn credit_sub<'a>(
+    env: &'a Env,
+    client: &SubscriptionRenewalContractClient,
+    granter: &Address,
+) -> (Address, token::Client<'a>) {
+    let owner = Address::generate(env);
+    let merchant = Address::generate(env);
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(env, &token).mint(granter, &10_000);
+    token::StellarAssetClient::new(env, &token).mint(&owner, &1_000);
+
+    client.init_sub(&owner, &1, &merchant, &token, &None, &None);
+    client.approve_renewal(&1, &1, &1000, &100);
+    (owner, token::Client::new(env, &token))
+}
+
+#[test]
+fn test_grant_credit_is_held_and_not_rescuable() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+
+    client.grant_credit(&granter, &owner, &token.address, &300);
+    client.grant_credit(&granter, &owner, &token.address, &200);
+
+    assert_eq!(client.get_credit(&owner, &token.address), 500);
+    assert_eq!(token.balance(&client.address), 500);
+    assert_eq!(client.get_tracked_balance(&token.address), 500);
+}
+
+#[test]
+fn test_renew_consumes_credit_before_wallet() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    client.grant_credit(&granter, &owner, &token.address, &300);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "charged"), 500);
+    assert_eq!(last_event_field::<i128>(&env, "credit_applied"), 300);
+    assert_eq!(client.get_credit(&owner, &token.address), 0);
+    assert_eq!(token.balance(&merchant), 300);
+    assert_eq!(client.get_tracked_balance(&token.address), 0);
+}
+
+#[test]
+fn test_credit_larger_than_charge_keeps_remainder() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    client.grant_credit(&granter, &owner, &token.address, &800);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "credit_applied", "balance"),
+        300
+    );
+    assert_eq!(client.get_credit(&owner, &token.address), 300);
+}
+
+#[test]
+fn test_failed_renewal_keeps_credit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    client.grant_credit(&granter, &owner, &token.address, &300);
+
+    assert!(!client.renew(&owner, &1, &1, &500, &3, &0, &false));
+    assert_eq!(client.get_credit(&owner, &token.address), 300);
+}
+
+#[test]
+#[should_panic(expected = "Credit amount must be positive")]
+fn test_grant_credit_rejects_non_positive() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+
+    client.grant_credit(&granter, &owner, &token.address, &0);
+}
+
+/// Leaves `amount` of `token` claimable by `merchant` by renewing a
+/// direct-mode subscription while withdrawals are frozen.
+fn hold_for_merchant(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) {
+    let payer = Address::generate(env);
+    token::StellarAssetClient::new(env, token).mint(&payer, &amount);
+    client.init_sub(&payer, &99, merchant, token, &None, &None);
+    client.set_payment_mode(&99, &PaymentMode::Direct);
+    client.approve_renewal(&99, &1, &amount, &100);
+    client.set_withdrawals_frozen(&true);
+    assert!(client.renew(&payer, &99, &1, &amount, &3, &0, &true));
+    client.set_withdrawals_frozen(&false);
+}
+
+#[test]
+fn test_promo_credit_applies_before_account_credit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    hold_for_merchant(&env, &client, &merchant, &token.address, 1_000);
+    client.grant_credit(&granter, &owner, &token.address, &300);
+    client.grant_promo_credit(&merchant, &owner, &token.address, &400, &50);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "credit_applied"), 500);
+    assert_eq!(
+        client.get_promo_credit(&merchant, &owner, &token.address),
+        None
+    );
+    assert_eq!(client.get_credit(&owner, &token.address), 200);
+    // 400 of the merchant's own promo came back, plus 100 of account credit
+    assert_eq!(token.balance(&merchant), 500);
+    assert_eq!(client.get_claimable(&merchant, &token.address), 600);
+}
+
+#[test]
+fn test_promo_credit_is_merchant_specific() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let other = Address::generate(&env);
+    hold_for_merchant(&env, &client, &other, &token.address, 1_000);
+    client.grant_promo_credit(&other, &owner, &token.address, &400, &50);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "credit_applied"), 0);
+    assert_eq!(
+        client
+            .get_promo_credit(&other, &owner, &token.address)
+            .unwrap()
+            .amount,
+        400
+    );
+}
+
+#[test]
+fn test_expired_promo_credit_is_reclaimed() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    hold_for_merchant(&env, &client, &merchant, &token.address, 400);
+    client.grant_promo_credit(&merchant, &owner, &token.address, &400, &50);
+
+    env.ledger().with_mut(|li| li.sequence_number = 51);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "credit_applied"), 0);
+
+    assert_eq!(
+        client.reclaim_promo_credit(&merchant, &owner, &token.address),
+        400
+    );
+    assert_eq!(token.balance(&merchant), 400);
+    assert_eq!(client.get_tracked_balance(&token.address), 0);
+}
+
+#[test]
+fn test_promo_credit_is_funded_from_claimable() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    hold_for_merchant(&env, &client, &merchant, &token.address, 1_000);
+    assert_eq!(client.get_tracked_balance(&token.address), 1_000);
+
+    client.grant_promo_credit(&merchant, &owner, &token.address, &400, &50);
+    assert_eq!(client.get_claimable(&merchant, &token.address), 600);
+    assert_eq!(client.get_tracked_balance(&token.address), 1_000);
+
+    client.grant_promo_credit(&merchant, &owner, &token.address, &600, &50);
+    assert_eq!(client.get_claimable(&merchant, &token.address), 0);
+    assert_eq!(
+        client
+            .get_promo_credit(&merchant, &owner, &token.address)
+            .unwrap()
+            .amount,
+        1_000
+    );
+}
+
+#[test]
+#[should_panic(expected = "Insufficient claimable balance")]
+fn test_promo_credit_beyond_claimable_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    token::StellarAssetClient::new(&env, &token.address).mint(&merchant, &1_000);
+    hold_for_merchant(&env, &client, &merchant, &token.address, 300);
+
+    client.grant_promo_credit(&merchant, &owner, &token.address, &400, &50);
+}
+
+#[test]
+#[should_panic(expected = "Promo credit not expired")]
+fn test_reclaim_unexpired_promo_credit_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    hold_for_merchant(&env, &client, &merchant, &token.address, 400);
+    client.grant_promo_credit(&merchant, &owner, &token.address, &400, &50);
+
+    client.reclaim_promo_credit(&merchant, &owner, &token.address);
+}
+
+#[test]
+fn test_refund_to_credit_funds_next_renewal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    token::StellarAssetClient::new(&env, &token.address).mint(&merchant, &1_000);
+
+    client.refund(&1, &500, &true);
+    assert!(last_event_field::<bool>(&env, "to_credit"));
+    assert_eq!(token.balance(&owner), 1_000);
+    assert_eq!(client.get_credit(&owner, &token.address), 500);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(last_event_field::<i128>(&env, "credit_applied"), 500);
+    assert_eq!(token.balance(&merchant), 1_000);
+}
+
+#[test]
+fn test_refund_to_wallet() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    let merchant = client.get_sub(&1).merchant;
+    token::StellarAssetClient::new(&env, &token.address).mint(&merchant, &1_000);
+
+    client.refund(&1, &300, &false);
+    assert!(!last_event_field::<bool>(&env, "to_credit"));
+    assert_eq!(token.balance(&owner), 1_300);
+    assert_eq!(token.balance(&merchant), 700);
+    assert_eq!(client.get_credit(&owner, &token.address), 0);
+}
+
+// ── Merchant payouts ──────────────────────────────────────────────
+
+#[test]
+fn test_delivered_payout_emits_sent() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    client.grant_credit(&granter, &owner, &token.address, &500);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "payout_sent", "amount"),
+        500
+    );
+    assert_eq!(token.balance(&client.get_sub(&1).merchant), 500);
+}
+
+#[test]
+fn test_undeliverable_payout_becomes_claimable() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    // An account without a trustline for the token can't receive it
+    let merchant = Address::try_from_val(
+        &env,
+        &ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([9; 32])))),
+    )
+    .unwrap();
+    let owner = Address::generate(&env);
+    let granter = Address::generate(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    token::StellarAssetClient::new(&env, &token).mint(&granter, &500);
+    client.init_sub(&owner, &1, &merchant, &token, &None, &None);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.grant_credit(&granter, &owner, &token, &500);
+
+    // The renewal still goes through
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "payout_deferred", "claimable"),
+        500
+    );
+    assert_eq!(client.get_claimable(&merchant, &token), 500);
+    assert_eq!(client.get_tracked_balance(&token), 500);
+
+    let to = Address::generate(&env);
+    assert_eq!(client.claim_payout(&merchant, &token, &to), 500);
+    assert_eq!(token::Client::new(&env, &token).balance(&to), 500);
+    assert_eq!(client.get_claimable(&merchant, &token), 0);
+    assert_eq!(client.get_tracked_balance(&token), 0);
+}
+
+#[test]
+#[should_panic(expected = "Nothing to claim")]
+fn test_claim_without_balance_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+
+    client.claim_payout(&Address::generate(&env), &token, &Address::generate(&env));
+}
+
+// ── Webhook keys ──────────────────────────────────────────────────
+
+#[test]
+fn test_webhook_key_rotation_bumps_version() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let merchant = Address::generate(&env);
+    assert_eq!(client.get_webhook_key(&merchant), None);
+
+    let first = BytesN::from_array(&env, &[1; 32]);
+    assert_eq!(client.set_webhook_key(&merchant, &first), 1);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "webhook_key_set", "version"),
+        1
+    );
+
+    env.ledger().with_mut(|li| li.sequence_number = 40);
+    let second = BytesN::from_array(&env, &[2; 32]);
+    assert_eq!(client.set_webhook_key(&merchant, &second), 2);
+    assert_eq!(
+        client.get_webhook_key(&merchant),
+        Some(WebhookKey {
+            key_hash: second,
+            version: 2,
+            set_at: 40,
+        })
+    );
+    assert_eq!(client.get_webhook_key(&Address::generate(&env)), None);
+}
+
+#[test]
+#[should_panic]
+fn test_webhook_key_requires_merchant_auth() {
+    let env = Env::default();
+    let contract_id = env.register(SubscriptionRenewalContract, ());
+    let client = SubscriptionRenewalContractClient::new(&env, &contract_id);
+    client.set_webhook_key(
+        &Address::generate(&env),
+        &BytesN::from_array(&env, &[1; 32]),
+    );
+}
+
+// ── Payment modes ─────────────────────────────────────────────────
+
+#[test]
+fn test_payment_mode_defaults_to_external() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert_eq!(client.get_payment_mode(&1), PaymentMode::External);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    let token = token::Client::new(&env, &client.get_sub(&1).token);
+    assert_eq!(token.balance(&owner), 1_000_000);
+}
+
+#[test]
+fn test_direct_mode_transfers_to_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+    assert_eq!(token.balance(&owner), 999_500);
+    assert_eq!(token.balance(&data.merchant), 500);
+    assert_eq!(token.balance(&client.address), 0);
+}
+
+#[test]
+fn test_allowance_mode_spends_allowance() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_payment_mode(&1, &PaymentMode::Allowance);
+    client.approve_renewal(&1, &1, &1000, &100);
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+
+    assert_eq!(
+        client.try_renew(&owner, &1, &1, &500, &3, &0, &true),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+
+    token.approve(&owner, &client.address, &500, &1000);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(token.balance(&owner), 999_500);
+    assert_eq!(token.balance(&data.merchant), 500);
+    assert_eq!(token.allowance(&owner, &client.address), 0);
+}
+
+#[test]
+fn test_escrow_mode_pays_only_from_credit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_payment_mode(&1, &PaymentMode::Escrow);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.approve_renewal(&1, &2, &1000, &100);
+    let data = client.get_sub(&1);
+
+    assert_eq!(
+        client.try_renew(&owner, &1, &1, &500, &3, &0, &true),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+
+    client.grant_credit(&owner, &owner, &data.token, &500);
+    assert!(client.renew(&owner, &1, &2, &500, &3, &0, &true));
+    let token = token::Client::new(&env, &data.token);
+    assert_eq!(token.balance(&owner), 999_500);
+    assert_eq!(token.balance(&data.merchant), 500);
+    assert_eq!(client.get_credit(&owner, &data.token), 0);
+}
+
+#[test]
+fn test_collected_charges_pay_protocol_fee() {
+    for mode in [
+        PaymentMode::Direct,
+        PaymentMode::Allowance,
+        PaymentMode::Escrow,
+    ] {
+        let env = Env::default();
+        let (client, _) = setup(&env);
+        let owner = new_sub(&env, &client, 1);
+        let recipient = Address::generate(&env);
+        client.set_fee_config(&1_000, &recipient);
+        client.set_payment_mode(&1, &mode);
+        client.approve_renewal(&1, &1, &1000, &100);
+        let data = client.get_sub(&1);
+        let token = token::Client::new(&env, &data.token);
+        match mode {
+            PaymentMode::Allowance => token.approve(&owner, &client.address, &500, &1000),
+            PaymentMode::Escrow => client.grant_credit(&owner, &owner, &data.token, &500),
+            _ => {}
+        }
+
+        assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+        assert_eq!(
+            event_field::<i128>(&env, &client, "protocol_fee_paid", "amount"),
+            50
+        );
+        assert_eq!(token.balance(&owner), 999_500);
+        assert_eq!(token.balance(&recipient), 50);
+        assert_eq!(token.balance(&data.merchant), 450);
+        assert_eq!(token.balance(&client.address), 0);
+    }
+}
+
+#[test]
+fn test_undeliverable_protocol_fee_is_deferred() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    // An account without a trustline for the token can't receive it
+    let recipient = Address::try_from_val(
+        &env,
+        &ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([8; 32])))),
+    )
+    .unwrap();
+    client.set_fee_config(&1_000, &recipient);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(
+        event_topic::<Address>(&env, &client, "payout_deferred", 1),
+        recipient
+    );
+    assert_eq!(
+        event_field::<i128>(&env, &client, "payout_deferred", "claimable"),
+        50
+    );
+    let data = client.get_sub(&1);
+    assert_eq!(client.get_claimable(&recipient, &data.token), 50);
+    assert_eq!(
+        token::Client::new(&env, &data.token).balance(&data.merchant),
+        450
+    );
+}
+
+#[test]
+fn test_external_charges_pay_no_fee() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let recipient = Address::generate(&env);
+    client.set_fee_config(&1_000, &recipient);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    // The caller settles External payments, so nothing passes through
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    let token = token::Client::new(&env, &client.get_sub(&1).token);
+    assert_eq!(token.balance(&owner), 1_000_000);
+    assert_eq!(token.balance(&recipient), 0);
+}
+
+// ── Wallet preflight ──────────────────────────────────────────────
+
+#[test]
+fn test_renew_rejects_insufficient_balance() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let issuer = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &499);
+    client.init_sub(&owner, &1, &Address::generate(&env), &token, &None, &None);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert_eq!(
+        client.try_renew(&owner, &1, &1, &500, &3, &0, &true),
+        Err(Ok(Error::InsufficientBalance.into()))
+    );
+    // Nothing was recorded, so the approval is still usable
+    assert!(!client.get_approval(&1, &1).unwrap().used);
+}
+
+#[test]
+fn test_renew_rejects_missing_trustline() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::try_from_val(
+        &env,
+        &ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([7; 32])))),
+    )
+    .unwrap();
+    let issuer = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    client.init_sub(&owner, &1, &Address::generate(&env), &token, &None, &None);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert_eq!(
+        client.try_renew(&owner, &1, &1, &500, &3, &0, &true),
+        Err(Ok(Error::TrustlineMissing.into()))
+    );
+}
+
+#[test]
+fn test_payment_readiness_reports_ready_wallet() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_pricing(&1, &500, &1);
+
+    assert_eq!(
+        client.check_payment_readiness(&1),
+        PaymentReadiness {
+            trustline: true,
+            authorized: true,
+            balance: 1_000_000,
+            next_charge: 500,
+            credit: 0,
+            ready: true,
+        }
+    );
+}
+
+#[test]
+fn test_payment_readiness_flags_deauthorized_owner() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let sac = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    sac.issuer().set_flag(IssuerFlags::RevocableFlag);
+    let token = sac.address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &1_000);
+    client.init_sub(&owner, &1, &Address::generate(&env), &token, &None, &None);
+    client.set_pricing(&1, &500, &1);
+    token::StellarAssetClient::new(&env, &token).set_authorized(&owner, &false);
+
+    let readiness = client.check_payment_readiness(&1);
+    assert!(readiness.trustline);
+    assert!(!readiness.authorized);
+    assert!(!readiness.ready);
+}
+
+#[test]
+fn test_payment_readiness_flags_missing_trustline() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::try_from_val(
+        &env,
+        &ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([7; 32])))),
+    )
+    .unwrap();
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    client.init_sub(&owner, &1, &Address::generate(&env), &token, &None, &None);
+    client.set_pricing(&1, &500, &1);
+
+    let readiness = client.check_payment_readiness(&1);
+    assert!(!readiness.trustline);
+    assert!(!readiness.authorized);
+    assert!(!readiness.ready);
+}
+
+#[test]
+fn test_payment_readiness_counts_credit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    client.set_pricing(&1, &500, &1);
+    token.burn(&owner, &1_000);
+    assert!(!client.check_payment_readiness(&1).ready);
+
+    client.grant_credit(&granter, &owner, &token.address, &500);
+    let readiness = client.check_payment_readiness(&1);
+    assert_eq!(readiness.credit, 500);
+    assert!(readiness.ready);
+}
+
+#[test]
+fn test_credit_covers_charge_without_wallet_balance() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let granter = Address::generate(&env);
+    let (owner, token) = credit_sub(&env, &client, &granter);
+    token::Client::new(&env, &token.address).burn(&owner, &1_000);
+    client.grant_credit(&granter, &owner, &token.address, &500);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+// ── Renewal diagnostics ───────────────────────────────────────────
+
+#[test]
+fn test_simulate_renew_ready() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    env.ledger().with_mut(|li| li.sequence_number = 105);
+
+    let diagnostics = client.simulate_renew(&owner, &1, &1, &500, &0);
+    assert_eq!(
+        diagnostics,
+        RenewDiagnostics {
+            protocol_paused: false,
+            sub_paused: false,
+            frozen: false,
+            starts_at: None,
+            merchant_suspended: false,
+            state: SubscriptionState::Active,
+            caller_allowed: true,
+            cycle: CycleStatus::Due,
+            cooldown_remaining: 0,
+            charge: 500,
+            approval_rejection: None,
+            approval_headroom: Some(500),
+            volume_headroom: None,
+            balance_ok: true,
+            ok: true,
+        }
+    );
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_simulate_renew_reports_blockers() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_volume_limit(
+        &None,
+        &Some(VolumeLimit {
+            max_amount: 800,
+            window_ledgers: 1000,
+        }),
+    );
+    client.set_paused(&true);
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+
+    let diagnostics = client.simulate_renew(&Address::generate(&env), &1, &9, &2000, &0);
+    assert!(diagnostics.protocol_paused);
+    assert!(!diagnostics.caller_allowed);
+    assert_eq!(diagnostics.cycle, CycleStatus::NotDue);
+    assert_eq!(
+        diagnostics.approval_rejection,
+        Some(ApprovalRejection::NotFound as u32)
+    );
+    assert_eq!(diagnostics.approval_headroom, None);
+    assert_eq!(diagnostics.volume_headroom, Some(800));
+    assert!(!diagnostics.ok);
+
+    let diagnostics = client.simulate_renew(&owner, &1, &1, &2000, &0);
+    assert_eq!(
+        diagnostics.approval_rejection,
+        Some(ApprovalRejection::AmountExceeded as u32)
+    );
+    assert_eq!(diagnostics.approval_headroom, Some(-1000));
+}
+
+#[test]
+fn test_simulate_renew_reports_cooldown() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    let executor = Address::generate(&env);
+    client.set_executor(&1, &executor);
+    env.ledger().with_mut(|li| li.sequence_number = 102);
+    client.renew(&owner, &1, &1, &500, &3, &5, &false);
+
+    env.ledger().with_mut(|li| li.sequence_number = 104);
+    let diagnostics = client.simulate_renew(&executor, &1, &1, &500, &5);
+    assert_eq!(diagnostics.state, SubscriptionState::Retrying);
+    assert_eq!(diagnostics.cooldown_remaining, 3);
+    assert!(!diagnostics.ok);
+    assert!(client
+        .try_renew(&executor, &1, &1, &500, &3, &5, &true)
+        .is_err());
+    // The owner may retry early once
+    assert!(client.simulate_renew(&owner, &1, &1, &500, &5).ok);
+
+    env.ledger().with_mut(|li| li.sequence_number = 107);
+    assert!(client.simulate_renew(&executor, &1, &1, &500, &5).ok);
+    assert!(client.renew(&executor, &1, &1, &500, &3, &5, &true));
+}
+
+// ── Approvals ─────────────────────────────────────────────────────
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_renewal_without_approval_fails() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 501);
+    client.renew(&owner, &501, &1, &500, &3, &10, &true);
+}
+
+#[test]
+fn test_approval_required_for_renewal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 500);
+    client.approve_renewal(&500, &1, &1000, &100);
+
+    assert!(client.renew(&owner, &500, &1, &500, &3, &10, &true));
+    assert!(client.get_approval(&500, &1).unwrap().used);
+}
+
+#[test]
+fn test_approval_usage_tracks_charges() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    assert_eq!(client.get_approval_usage(&1, &1), None);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.approve_renewal(&1, &2, &1000, &100);
+
+    // Failed renewals hand the approval back, and are counted
+    client.renew(&owner, &1, &1, &500, &3, &0, &false);
+    client.renew(&owner, &1, &1, &500, &3, &0, &false);
+    assert_eq!(
+        client.get_approval_usage(&1, &1),
+        Some(ApprovalUsage {
+            attempts: 2,
+            failures: 2,
+            charged: 0,
+            unspent: 0,
+            remaining: 1000,
+        })
+    );
+
+    client.renew(&owner, &1, &1, &400, &3, &0, &true);
+    assert_eq!(
+        client.get_approval_usage(&1, &1),
+        Some(ApprovalUsage {
+            attempts: 3,
+            failures: 2,
+            charged: 400,
+            unspent: 600,
+            remaining: 0,
+        })
+    );
+    assert_eq!(
+        client.get_approval_usage(&1, &2),
+        Some(ApprovalUsage {
+            remaining: 1000,
+            ..Default::default()
+        })
+    );
+
+    env.ledger().with_mut(|li| li.sequence_number = 101);
+    assert_eq!(client.get_approval_usage(&1, &2).unwrap().remaining, 0);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_approval_cannot_be_reused() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 502);
+    client.approve_renewal(&502, &1, &1000, &100);
+
+    client.renew(&owner, &502, &1, &500, &3, &10, &true);
+    client.renew(&owner, &502, &1, &500, &3, &10, &true);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_expired_approval_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 503);
+    client.approve_renewal(&503, &1, &1000, &100);
+
+    env.ledger().with_mut(|li| li.sequence_number = 101);
+    client.renew(&owner, &503, &1, &500, &3, &10, &true);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_amount_exceeds_max_spend() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 504);
+    client.approve_renewal(&504, &1, &1000, &100);
+
+    client.renew(&owner, &504, &1, &1001, &3, &10, &true);
+}
+
+#[test]
+fn test_failed_renewal_releases_approval() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 506);
+    client.approve_renewal(&506, &1, &1000, &100);
+
+    assert!(!client.renew(&owner, &506, &1, &500, &3, &0, &false));
+    assert_eq!(
+        event_field::<u64>(&env, &client, "approval_released", "approval_id"),
+        1
+    );
+    assert!(!client.get_approval(&506, &1).unwrap().used);
+
+    // The retry spends the same approval
+    assert!(client.renew(&owner, &506, &1, &500, &3, &0, &true));
+    assert!(client.get_approval(&506, &1).unwrap().used);
+}
+
+#[test]
+fn test_approval_bound_to_merchant_and_token() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &1000);
+    client.offer_sub(&merchant, &507, &owner, &token, &50);
+    client.approve_renewal(&507, &1, &1000, &100);
+    let data = client.get_sub(&507);
+    let approval = client.get_approval(&507, &1).unwrap();
+    assert_eq!(
+        (approval.merchant, approval.token),
+        (data.merchant, data.token.clone())
+    );
+
+    // Re-using the declined offer's id with another merchant voids the
+    // approval
+    client.decline_sub(&507);
+    client.init_sub(
+        &owner,
+        &507,
+        &Address::generate(&env),
+        &data.token,
+        &None,
+        &None,
+    );
+    assert_eq!(
+        ApprovalRejection::from_code(5),
+        Some(ApprovalRejection::TermsChanged)
+    );
+    assert!(client
+        .try_renew(&owner, &507, &1, &500, &3, &10, &true)
+        .is_err());
+    assert!(!client.get_approval(&507, &1).unwrap().used);
+}
+
+#[test]
+fn test_unbound_approval_layout_treated_as_missing() {
+    #[contracttype]
+    struct UnboundApproval {
+        sub_id: u64,
+        max_spend: i128,
+        expires_at: u32,
+        used: bool,
+    }
+
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 508);
+    env.as_contract(&client.address, || {
+        env.storage().persistent().set(
+            &ApprovalKey {
+                sub_id: 508,
+                approval_id: 1,
+            },
+            &UnboundApproval {
+                sub_id: 508,
+                max_spend: 1000,
+                expires_at: 100,
+                used: false,
+            },
+        )
+    });
+
+    assert_eq!(client.get_approval(&508, &1), None);
+    assert!(client
+        .try_renew(&owner, &508, &1, &500, &3, &10, &true)
+        .is_err());
+}
+
+#[test]
+fn test_multiple_approvals_for_same_subscription() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 505);
+    client.approve_renewal(&505, &1, &1000, &100);
+    client.approve_renewal(&505, &2, &2000, &200);
+
+    assert!(client.renew(&owner, &505, &1, &500, &3, &10, &true));
+    assert!(client.renew(&owner, &505, &2, &1500, &3, &10, &true));
+}
+
+// ── Idempotent renewals ───────────────────────────────────────────
+
+/// `renew` arguments charging 500, with no cooldown.
+fn renew_args(sub_id: u64, approval_id: u64, succeed: bool) -> RenewOp {
+    RenewOp {
+        sub_id,
+        approval_id,
+        amount: 500,
+        max_retries: 3,
+        cooldown_ledgers: 0,
+        succeed,
+    }
+}
+
+#[test]
+fn test_renew_idempotent_charges_once() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.approve_renewal(&1, &2, &1000, &100);
+    let key = BytesN::from_array(&env, &[7; 32]);
+
+    assert!(client.renew_idempotent(&owner, &renew_args(1, 1, true), &key));
+    // The approval is spent, so a plain repeat would trap
+    assert!(client.renew_idempotent(&owner, &renew_args(1, 1, true), &key));
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+    assert_eq!(client.get_sub_economics(&1).charged, 500);
+
+    // Keys are per subscription and per job
+    let other = BytesN::from_array(&env, &[8; 32]);
+    assert!(client.renew_idempotent(&owner, &renew_args(1, 2, true), &other));
+    assert_eq!(client.get_sub(&1).renewal_seq, 2);
+}
+
+#[test]
+fn test_renew_idempotent_replays_failures_until_key_expires() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100_000);
+    let key = BytesN::from_array(&env, &[7; 32]);
+
+    assert!(!client.renew_idempotent(&owner, &renew_args(1, 1, false), &key));
+    assert!(!client.renew_idempotent(&owner, &renew_args(1, 1, false), &key));
+    assert_eq!(client.get_sub(&1).failure_count, 1);
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number += IDEMPOTENCY_TTL_LEDGERS + 1);
+    assert!(client.renew_idempotent(&owner, &renew_args(1, 1, true), &key));
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+}
+
+#[test]
+#[should_panic(expected = "Idempotency key reused with other arguments")]
+fn test_renew_idempotent_rejects_changed_arguments() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    let key = BytesN::from_array(&env, &[7; 32]);
+
+    client.renew_idempotent(&owner, &renew_args(1, 1, false), &key);
+    client.renew_idempotent(&owner, &renew_args(1, 1, true), &key);
+}
+
+#[test]
+fn test_renew_idempotent_keys_are_per_caller() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let executor = Address::generate(&env);
+    client.set_executor(&1, &executor);
+    client.approve_renewal(&1, &1, &1000, &100);
+    let key = BytesN::from_array(&env, &[7; 32]);
+
+    assert!(!client.renew_idempotent(&owner, &renew_args(1, 1, false), &key));
+    // The executor's job with the same key still runs
+    assert!(client.renew_idempotent(&executor, &renew_args(1, 1, true), &key));
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+}
+
+// ── Multicall ─────────────────────────────────────────────────────
+
+fn approve_op(sub_id: u64, approval_id: u64) -> UserOp {
+    UserOp::Approve(ApproveOp {
+        sub_id,
+        approval_id,
+        max_spend: 1000,
+        expires_at: 100,
+    })
+}
+
+fn renew_op(sub_id: u64, approval_id: u64, amount: i128) -> UserOp {
+    UserOp::Renew(RenewOp {
+        sub_id,
+        approval_id,
+        amount,
+        max_retries: 3,
+        cooldown_ledgers: 10,
+        succeed: true,
+    })
+}
+
+#[test]
+fn test_multicall_approve_then_renew() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let executor = Address::generate(&env);
+
+    let results = client.multicall(
+        &owner,
+        &vec![
+            &env,
+            approve_op(1, 1),
+            renew_op(1, 1, 500),
+            UserOp::SetExecutor(1, executor.clone()),
+        ],
+    );
+
+    assert_eq!(results, vec![&env, true, true, true]);
+    // The owner signs the batch once
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, owner);
+    assert_eq!(client.get_sub(&1).renewal_seq, 1);
+    assert_eq!(client.get_executor(&1), Some(executor));
+}
+
+#[test]
+fn test_multicall_is_all_or_nothing() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+
+    // The renewal exceeds the approval's max_spend, so the approval goes too
+    let result = client.try_multicall(&owner, &vec![&env, approve_op(1, 1), renew_op(1, 1, 1001)]);
+
+    assert!(result.is_err());
+    assert_eq!(client.get_approval(&1, &1), None);
+    assert_eq!(client.get_sub(&1).renewal_seq, 0);
+}
+
+#[test]
+fn test_multicall_step_for_other_owner_needs_their_auth() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let other = new_sub(&env, &client, 2);
+
+    client.multicall(&owner, &vec![&env, UserOp::RemoveExecutor(2)]);
+
+    let signers: std::vec::Vec<Address> = env.auths().into_iter().map(|(a, _)| a).collect();
+    assert_eq!(signers, std::vec![owner, other]);
+}
+
+// ── Batched approvals ─────────────────────────────────────────────
+
+/// Subscriptions 1..=`count` with one owner, who is returned.
+fn owned_subs(env: &Env, client: &SubscriptionRenewalContractClient, count: u64) -> Address {
+    let owner = Address::generate(env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(env))
+        .address();
+    for sub_id in 1..=count {
+        client.init_sub(
+            &owner,
+            &sub_id,
+            &Address::generate(env),
+            &token,
+            &None,
+            &None,
+        );
+    }
+    owner
+}
+
+fn approval_request(sub_id: u64, approval_id: u64) -> ApprovalRequest {
+    ApprovalRequest {
+        sub_id,
+        approval_id,
+        max_spend: 1000,
+        expires_at: 100,
+    }
+}
+
+#[test]
+fn test_approve_renewals_reports_each_request() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let owner = owned_subs(&env, &client, 3);
+    new_sub(&env, &client, 4);
+    client.freeze_sub(&admin, &3, &symbol_short!("legal"));
+    client.set_cosign_rule(&2, &cosign_rule(&Address::generate(&env), 600));
+
+    let results = client.approve_renewals(
+        &owner,
+        &vec![
+            &env,
+            approval_request(1, 1),
+            approval_request(1, 2),
+            approval_request(2, 1),
+            approval_request(3, 1),
+            approval_request(4, 1),
+            approval_request(9, 1),
+        ],
+    );
+
+    assert_eq!(
+        results,
+        vec![
+            &env,
+            ApprovalResult::Approved,
+            ApprovalResult::Approved,
+            ApprovalResult::AwaitingGuardian,
+            ApprovalResult::Frozen,
+            ApprovalResult::NotOwner,
+            ApprovalResult::NotFound,
+        ]
+    );
+    // One signature covers the batch
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, owner);
+    assert!(client.get_approval(&1, &2).is_some());
+    assert_eq!(client.get_approval(&2, &1), None);
+    assert_eq!(client.get_approval(&4, &1), None);
+}
+
+#[test]
+fn test_approve_renewals_rejects_oversized_batch() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = owned_subs(&env, &client, 1);
+    let mut requests = Vec::new(&env);
+    for approval_id in 0..=MAX_APPROVAL_BATCH as u64 {
+        requests.push_back(approval_request(1, approval_id));
+    }
+
+    assert!(client.try_approve_renewals(&owner, &requests).is_err());
+    assert_eq!(client.get_approval(&1, &0), None);
+}
+
+#[test]
+fn test_approve_renewals_page_cost() {
+    let cost = &crate::paging::APPROVAL_BATCH;
+    for items in [1, MAX_APPROVAL_BATCH] {
+        let env = Env::default();
+        let (client, _) = setup(&env);
+        let owner = Address::generate(&env);
+        let mut requests = Vec::new(&env);
+        // A token per subscription, so each item queries and caches its
+        // token's decimals
+        for sub_id in 1..=items as u64 {
+            let token = env
+                .register_stellar_asset_contract_v2(Address::generate(&env))
+                .address();
+            client.init_sub(
+                &owner,
+                &sub_id,
+                &Address::generate(&env),
+                &token,
+                &None,
+                &None,
+            );
+            requests.push_back(approval_request(sub_id, 1));
+        }
+        client.approve_renewals(&owner, &requests);
+
+        let metered = env.cost_estimate().resources();
+        assert!(
+            metered.memory_read_entries + metered.disk_read_entries
+                <= cost.fixed.entries + items * cost.item.entries
+        );
+        assert!(metered.write_entries <= cost.fixed.writes + items * cost.item.writes);
+        assert!(
+            metered.instructions as u64
+                <= cost.fixed.instructions + items as u64 * cost.item.instructions
+        );
+    }
+}
+
+// ── Joint ownership ───────────────────────────────────────────────
+
+/// Subscription 1 jointly owned by three addresses with threshold 2.
+fn joint_sub(env: &Env, client: &SubscriptionRenewalContractClient) -> Vec<Address> {
+    let owner = new_sub(env, client, 1);
+    let owners = vec![
+        env,
+        owner.clone(),
+        Address::generate(env),
+        Address::generate(env),
+    ];
+    client.set_joint_owners(&1, &vec![env, owner], &owners, &2);
+    owners
+}
+
+#[test]
+fn test_joint_approval_with_threshold() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+    let signers = vec![&env, owners.get(1).unwrap(), owners.get(2).unwrap()];
+
+    client.approve_renewal_joint(&1, &signers, &1, &1000, &100);
+    assert!(client.renew(&owners.get(0).unwrap(), &1, &1, &500, &3, &0, &true));
+    assert_eq!(client.get_joint_owners(&1).unwrap().threshold, 2);
+}
+
+#[test]
+#[should_panic(expected = "Not enough co-owner signatures")]
+fn test_joint_approval_below_threshold() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+
+    client.approve_renewal_joint(&1, &vec![&env, owners.get(0).unwrap()], &1, &1000, &100);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate signer")]
+fn test_joint_approval_rejects_repeated_signer() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+    let owner = owners.get(0).unwrap();
+
+    client.approve_renewal_joint(&1, &vec![&env, owner.clone(), owner], &1, &1000, &100);
+}
+
+#[test]
+#[should_panic(expected = "Joint subscription: use approve_renewal_joint")]
+fn test_single_owner_approval_rejected_when_joint() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    joint_sub(&env, &client);
+
+    client.approve_renewal(&1, &1, &1000, &100);
+}
+
+#[test]
+#[should_panic(expected = "Subscription is paused")]
+fn test_any_co_owner_can_pause() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+    let signers = vec![&env, owners.get(0).unwrap(), owners.get(1).unwrap()];
+    client.approve_renewal_joint(&1, &signers, &1, &1000, &100);
+
+    client.pause_sub(&owners.get(2).unwrap(), &1);
+    assert!(client.is_sub_paused(&1));
+    client.renew(&owners.get(0).unwrap(), &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_resume_needs_threshold() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+    client.pause_sub(&owners.get(2).unwrap(), &1);
+
+    let one = vec![&env, owners.get(2).unwrap()];
+    assert!(client.try_resume_sub(&1, &one).is_err());
+    let two = vec![&env, owners.get(1).unwrap(), owners.get(2).unwrap()];
+    client.resume_sub(&1, &two);
+    assert!(!client.is_sub_paused(&1));
+}
+
+// ── Pause limits ──────────────────────────────────────────────────
+
+/// Subscriptions 1 and 2 of one owner with a merchant that allows 100
+/// paused ledgers per window and one pause at a time.
+fn pause_limited_subs(env: &Env, client: &SubscriptionRenewalContractClient) -> (Address, Address) {
+    let owner = Address::generate(env);
+    let merchant = Address::generate(env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(env))
+        .address();
+    client.set_max_owner_subs(&merchant, &2);
+    client.init_sub(&owner, &1, &merchant, &token, &None, &None);
+    client.init_sub(&owner, &2, &merchant, &token, &None, &None);
+    client.set_pause_limits(
+        &merchant,
+        &Some(PauseLimits {
+            max_paused_ledgers: 100,
+            max_concurrent: 1,
+        }),
+    );
+    (owner, merchant)
+}
+
+#[test]
+fn test_pause_quota_exhausted() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, _) = pause_limited_subs(&env, &client);
+
+    client.pause_sub(&owner, &1);
+    assert_eq!(client.get_pause_deadline(&1), Some(100));
+    env.ledger().with_mut(|li| li.sequence_number = 60);
+    client.resume_sub(&1, &vec![&env]);
+    assert_eq!(client.get_pause_deadline(&1), None);
+
+    client.pause_sub(&owner, &1);
+    assert_eq!(client.get_pause_deadline(&1), Some(100));
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.resume_sub(&1, &vec![&env]);
+    assert_eq!(
+        client.try_pause_sub(&owner, &1),
+        Err(Ok(Error::PauseQuotaExhausted.into()))
+    );
+
+    // The allowance renews with the next window
+    env.ledger()
+        .with_mut(|li| li.sequence_number = PAUSE_QUOTA_WINDOW_LEDGERS);
+    client.pause_sub(&owner, &1);
+    assert!(client.is_sub_paused(&1));
+}
+
+#[test]
+fn test_concurrent_pause_limit() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, _) = pause_limited_subs(&env, &client);
+
+    client.pause_sub(&owner, &1);
+    assert_eq!(
+        client.try_pause_sub(&owner, &2),
+        Err(Ok(Error::TooManyPauses.into()))
+    );
+    // Pausing again doesn't take another slot
+    client.pause_sub(&owner, &1);
+
+    client.resume_sub(&1, &vec![&env]);
+    client.pause_sub(&owner, &2);
+    assert!(client.is_sub_paused(&2));
+}
+
+#[test]
+fn test_merchant_ends_overdue_pause() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, merchant) = pause_limited_subs(&env, &client);
+    client.pause_sub(&owner, &1);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.try_end_overdue_pause(&merchant, &1).is_err());
+
+    env.ledger().with_mut(|li| li.sequence_number = 101);
+    client.end_overdue_pause(&merchant, &1);
+    assert!(!client.is_sub_paused(&1));
+    assert_eq!(
+        client.try_pause_sub(&owner, &1),
+        Err(Ok(Error::PauseQuotaExhausted.into()))
+    );
+    client.pause_sub(&owner, &2);
+}
+
+#[test]
+fn test_unlimited_merchant_pauses_freely() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (owner, merchant) = pause_limited_subs(&env, &client);
+    client.set_pause_limits(&merchant, &None);
+
+    client.pause_sub(&owner, &1);
+    client.pause_sub(&owner, &2);
+    assert_eq!(client.get_pause_deadline(&1), None);
+    assert!(client.try_end_overdue_pause(&merchant, &1).is_err());
+}
+
+// ── Owner recovery ────────────────────────────────────────────────
+
+/// Subscription 1 with a recovery guardian and a 100-ledger delay.
+fn recoverable_sub(env: &Env, client: &SubscriptionRenewalContractClient) -> Address {
+    new_sub(env, client, 1);
+    let guardian = Address::generate(env);
+    client.set_recovery_guardian(
+        &1,
+        &Some(RecoveryGuardian {
+            guardian: guardian.clone(),
+            delay_ledgers: 100,
+        }),
+    );
+    guardian
+}
+
+#[test]
+fn test_guardian_recovers_owner_after_delay() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    recoverable_sub(&env, &client);
+    let new_owner = Address::generate(&env);
+
+    client.request_recovery(&1, &new_owner);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.execute_recovery(&1);
+
+    assert_eq!(client.get_sub(&1).owner, new_owner);
+    assert_eq!(client.get_pending_recovery(&1), None);
+    let token = client.get_sub(&1).token;
+    token::StellarAssetClient::new(&env, &token).mint(&new_owner, &500);
+    client.approve_renewal(&1, &1, &1000, &200);
+    assert!(client.renew(&new_owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+#[should_panic(expected = "Recovery delay not elapsed")]
+fn test_recovery_waits_for_delay() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    recoverable_sub(&env, &client);
+
+    client.request_recovery(&1, &Address::generate(&env));
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    client.execute_recovery(&1);
+}
+
+#[test]
+#[should_panic(expected = "No pending recovery")]
+fn test_owner_cancels_recovery() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    recoverable_sub(&env, &client);
+    let owner = client.get_sub(&1).owner;
+
+    client.request_recovery(&1, &Address::generate(&env));
+    client.cancel_recovery(&1);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert_eq!(client.get_sub(&1).owner, owner);
+    client.execute_recovery(&1);
+}
+
+#[test]
+fn test_recovery_rotates_joint_owner() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owners = joint_sub(&env, &client);
+    let guardian = Address::generate(&env);
+    client.set_recovery_guardian(
+        &1,
+        &Some(RecoveryGuardian {
+            guardian,
+            delay_ledgers: 0,
+        }),
+    );
+    let new_owner = Address::generate(&env);
+
+    client.request_recovery(&1, &new_owner);
+    client.execute_recovery(&1);
+    let joint = client.get_joint_owners(&1).unwrap();
+    assert_eq!(joint.owners.get(0), Some(new_owner));
+    assert!(!joint.owners.contains(owners.get(0).unwrap()));
+}
+
+// ── Co-signed approvals ───────────────────────────────────────────
+
+fn cosign_rule(guardian: &Address, min_amount: i128) -> Option<CosignRule> {
+    Some(CosignRule {
+        guardian: guardian.clone(),
+        min_amount,
+    })
+}
+
+#[test]
+fn test_high_value_approval_waits_for_guardian() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let guardian = Address::generate(&env);
+    client.set_cosign_rule(&1, &cosign_rule(&guardian, 600));
+
+    client.approve_renewal(&1, &1, &1000, &100);
+    assert_eq!(client.get_approval(&1, &1), None);
+    assert_eq!(client.get_pending_approval(&1, &1).unwrap().max_spend, 1000);
+
+    client.countersign_approval(&1, &1);
+    assert_eq!(client.get_pending_approval(&1, &1), None);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_low_value_approval_needs_no_guardian() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_cosign_rule(&1, &cosign_rule(&Address::generate(&env), 600));
+
+    client.approve_renewal(&1, &1, &600, &100);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_pending_approval_cannot_renew() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_cosign_rule(&1, &cosign_rule(&Address::generate(&env), 600));
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_changing_cosign_rule_needs_guardian() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    let guardian = Address::generate(&env);
+    client.set_cosign_rule(&1, &cosign_rule(&guardian, 600));
+
+    client.set_cosign_rule(&1, &None);
+    let auths = env.auths();
+    assert!(auths.iter().any(|(address, _)| *address == guardian));
+    assert_eq!(client.get_cosign_rule(&1), None);
+}
+
+#[test]
+fn test_stale_pending_approval_is_cleaned_up() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_cosign_rule(&1, &cosign_rule(&Address::generate(&env), 600));
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert!(client.try_expire_pending_approval(&1, &1).is_err());
+    env.ledger().with_mut(|li| li.sequence_number = 101);
+    client.expire_pending_approval(&1, &1);
+    assert_eq!(client.get_pending_approval(&1, &1), None);
+}
+
+// ── Labels ────────────────────────────────────────────────────────
+
+#[test]
+fn test_labels_round_trip() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    assert!(client.get_labels(&1).is_empty());
+
+    let work = BytesN::from_array(&env, b"work\0\0\0\0");
+    let personal = BytesN::from_array(&env, b"personal");
+    let labels = vec![&env, work.clone(), personal];
+    client.set_labels(&1, &labels);
+    assert_eq!(client.get_labels(&1), labels);
+
+    client.set_labels(&1, &vec![&env, work.clone()]);
+    assert_eq!(client.get_labels(&1), vec![&env, work]);
+    client.set_labels(&1, &vec![&env]);
+    assert!(client.get_labels(&1).is_empty());
+}
+
+#[test]
+fn test_labels_bounded_and_distinct() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+
+    let mut labels = Vec::new(&env);
+    for i in 0..=MAX_LABELS as u8 {
+        labels.push_back(BytesN::from_array(&env, &[i; 8]));
+    }
+    assert!(client.try_set_labels(&1, &labels).is_err());
+    labels.pop_back();
+    client.set_labels(&1, &labels);
+
+    let label = BytesN::from_array(&env, &[9; 8]);
+    assert!(client
+        .try_set_labels(&1, &vec![&env, label.clone(), label])
+        .is_err());
+    assert_eq!(client.get_labels(&1).len(), MAX_LABELS);
+}
+
+// ── Notification preferences ──────────────────────────────────────
+
+#[test]
+fn test_notification_prefs_gate_reminders() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    client.set_pricing(&1, &500, &1);
+    client.schedule_price_change(&1, &600, &1, &50_000);
+    assert!(client.get_notification_prefs(&1).reminders);
+
+    client.set_notification_prefs(
+        &1,
+        &NotificationPrefs {
+            reminders: false,
+            usage_alert_pct: 0,
+            preview_min_amount: 0,
+        },
+    );
+    assert!(client.try_emit_change_notice(&1).is_err());
+
+    client.set_notification_prefs(
+        &1,
+        &NotificationPrefs {
+            reminders: true,
+            usage_alert_pct: 0,
+            preview_min_amount: 0,
+        },
+    );
+    client.emit_change_notice(&1);
+}
+
+#[test]
+fn test_notification_prefs_filter_alerts_and_previews() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    quoted_sub(&env, &client);
+    client.set_schedule(&1, &100, &100, &10);
+    client.set_preview_lead(&client.get_sub(&1).merchant, &Some(20));
+    client.set_usage_budget(&1, &Some(100));
+    client.set_notification_prefs(
+        &1,
+        &NotificationPrefs {
+            reminders: true,
+            usage_alert_pct: 80,
+            preview_min_amount: 200,
+        },
+    );
+
+    client.record_usage(&1, &90);
+    assert_eq!(usage_thresholds(&env), [80]);
+
+    // The quote of 110 is under the owner's threshold
+    env.ledger().with_mut(|li| li.sequence_number = 80);
+    assert!(client.try_emit_charge_preview(&1).is_err());
+}
+
+// ── Term limits ───────────────────────────────────────────────────
+
+#[test]
+fn test_sub_completes_after_max_cycles() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let owner = Address::generate(&env);
+    client.subscribe_from_template(&owner, &1, &template_id, &None, &None);
+    token::StellarAssetClient::new(&env, &client.get_sub(&1).token).mint(&owner, &1500);
+    for approval_id in 1..=3 {
+        client.approve_renewal(&1, &approval_id, &500, &1000);
+    }
+    client.set_max_cycles(&1, &Some(2));
+
+    env.ledger().with_mut(|li| li.sequence_number = 50);
+    assert!(client.renew(&owner, &1, &1, &500, &0, &0, &true));
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Active);
+    env.ledger().with_mut(|li| li.sequence_number = 150);
+    assert!(client.renew(&owner, &1, &2, &500, &0, &0, &true));
+    assert_eq!(
+        event_field::<u32>(&env, &client, "subscription_completed", "cycles"),
+        2
+    );
+
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Completed);
+    assert!(!client.is_entitled(&1));
+    assert_eq!(client.get_schedule(&1), None);
+    assert_eq!(client.get_sub_template(&1), None);
+    env.ledger().with_mut(|li| li.sequence_number = 250);
+    assert!(client
+        .try_renew(&owner, &1, &3, &500, &0, &0, &true)
+        .is_err());
+}
+
+#[test]
+fn test_max_cycles_leave_room_for_prepaid() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    scheduled_sub(&env, &client);
+    client.prepay_cycles(&1, &2, &500);
+
+    assert!(client.try_set_max_cycles(&1, &Some(1)).is_err());
+    client.set_max_cycles(&1, &Some(3));
+    assert_eq!(client.get_max_cycles(&1), Some(3));
+    client.prepay_cycles(&1, &1, &500);
+    assert!(client.try_prepay_cycles(&1, &1, &500).is_err());
+
+    client.set_max_cycles(&1, &None);
+    assert_eq!(client.get_max_cycles(&1), None);
+    client.prepay_cycles(&1, &1, &500);
+}
+
+#[test]
+fn test_installments_round_into_final_payment() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_max_cycles(&1, &Some(3));
+    client.set_installment_plan(&1, &1000);
+    assert_eq!(client.get_amount(&1), Some(333));
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.renew(&owner, &1, &1, &333, &0, &0, &true));
+    env.ledger().with_mut(|li| li.sequence_number = 200);
+    assert!(client.renew(&owner, &1, &2, &333, &0, &0, &true));
+
+    // The last installment takes the rounding remainder
+    env.ledger().with_mut(|li| li.sequence_number = 300);
+    assert_eq!(client.get_installment_due(&1), Some(334));
+    assert!(client
+        .try_renew(&owner, &1, &3, &333, &0, &0, &true)
+        .is_err());
+    assert!(client.renew(&owner, &1, &3, &334, &0, &0, &true));
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Completed);
+    assert_eq!(client.get_installment_plan(&1).unwrap().paid, 1000);
+    assert_eq!(client.get_installment_due(&1), None);
+    assert!(client.try_set_max_cycles(&1, &Some(5)).is_err());
+}
+
+#[test]
+fn test_pay_off_settles_remainder() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    client.set_max_cycles(&1, &Some(4));
+    client.set_installment_plan(&1, &1000);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.renew(&owner, &1, &1, &250, &0, &0, &true));
+
+    assert_eq!(client.pay_off(&1), 750);
+    assert_eq!(
+        event_field::<i128>(&env, &client, "installments_paid_off", "amount"),
+        750
+    );
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+    assert_eq!(token.balance(&owner), 1_000_000 - 1000);
+    assert_eq!(token.balance(&data.merchant), 1000);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Completed);
+    assert_eq!(client.get_installment_plan(&1).unwrap().paid, 1000);
+    assert_eq!(client.get_sub_economics(&1).charged, 1000);
+    assert!(client.try_pay_off(&1).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Subscription is paused")]
+fn test_pay_off_rejected_while_paused() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = scheduled_sub(&env, &client);
+    client.set_max_cycles(&1, &Some(4));
+    client.set_installment_plan(&1, &1000);
+    client.pause_sub(&owner, &1);
+
+    client.pay_off(&1);
+}
+
+#[test]
+fn test_pay_off_rejected_for_suspended_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (_, owners) = merchant_subs(&env, &client, MIN_HEALTH_ATTEMPTS as u64);
+    for (i, owner) in owners.iter().enumerate() {
+        client.renew(owner, &(i as u64 + 1), &1, &500, &3, &0, &false);
+    }
+    client.set_schedule(&1, &100, &100, &10);
+    client.set_max_cycles(&1, &Some(4));
+    client.set_installment_plan(&1, &1000);
+    env.ledger()
+        .with_mut(|li| li.sequence_number = REVERIFY_GRACE_LEDGERS + 1);
+
+    assert_eq!(
+        client.try_pay_off(&1),
+        Err(Ok(Error::MerchantSuspended.into()))
+    );
+}
+
+// ── Dynamic pricing ───────────────────────────────────────────────
+
+/// Stand-in pricing contract: each cycle costs 100 plus 10 per cycle.
+#[contract]
+struct MockPricing;
+
+#[contractimpl]
+impl MockPricing {
+    pub fn quote(_env: Env, _sub_id: u64, cycle_id: u64) -> i128 {
+        100 + 10 * cycle_id as i128
+    }
+}
+
+/// Subscription 1 with its merchant pricing through `MockPricing`.
+fn quoted_sub(env: &Env, client: &SubscriptionRenewalContractClient) -> Address {
+    let owner = new_sub(env, client, 1);
+    let pricing = env.register(MockPricing, ());
+    client.set_pricing_contract(&client.get_sub(&1).merchant, &Some(pricing));
+    owner
+}
+
+#[test]
+fn test_pricing_contract_quotes_renewal_charge() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = quoted_sub(&env, &client);
+    client.approve_renewal(&1, &1, &1000, &1000);
+    client.approve_renewal(&1, &2, &1000, &1000);
+
+    // The caller's amount gives way to the quote for each cycle
+    assert!(client.renew(&owner, &1, &1, &1, &3, &0, &true));
+    assert_eq!(client.get_sub_economics(&1).charged, 110);
+    assert_eq!(client.get_amount(&1), Some(120));
+    assert!(client.renew(&owner, &1, &2, &1, &3, &0, &true));
+    assert_eq!(client.get_sub_economics(&1).charged, 230);
+}
+
+#[test]
+fn test_quote_bounded_by_approval() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = quoted_sub(&env, &client);
+    client.approve_renewal(&1, &1, &100, &1000);
+
+    assert!(client
+        .try_renew(&owner, &1, &1, &100, &3, &0, &true)
+        .is_err());
+
+    let merchant = client.get_sub(&1).merchant;
+    client.set_pricing_contract(&merchant, &None);
+    assert_eq!(client.get_pricing_contract(&merchant), None);
+    assert!(client.renew(&owner, &1, &1, &100, &3, &0, &true));
+}
+
+#[test]
+fn test_charge_preview_before_due() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    quoted_sub(&env, &client);
+    client.set_schedule(&1, &100, &100, &10);
+    let merchant = client.get_sub(&1).merchant;
+    client.set_preview_lead(&merchant, &Some(20));
+
+    env.ledger().with_mut(|li| li.sequence_number = 79);
+    assert!(client.try_emit_charge_preview(&1).is_err());
+
+    env.ledger().with_mut(|li| li.sequence_number = 80);
+    assert_eq!(client.emit_charge_preview(&1), 110);
+    assert_eq!(
+        event_field::<u32>(&env, &client, "charge_preview", "due"),
+        100
+    );
+    // Once per cycle, and not once it is due
+    assert!(client.try_emit_charge_preview(&1).is_err());
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.set_preview_lead(&merchant, &Some(30));
+    assert!(client.try_emit_charge_preview(&1).is_err());
+}
+
+// ── Subscriber allowlist ──────────────────────────────────────────
+
+#[test]
+fn test_allowlist_gates_new_subscribers() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let merchant = client.get_template(&template_id).unwrap().merchant;
+    let token = client.get_template(&template_id).unwrap().token;
+    let vetted = Address::generate(&env);
+    let stranger = Address::generate(&env);
+
+    client.set_allowlist_mode(&merchant, &true);
+    assert!(event_field::<bool>(
+        &env,
+        &client,
+        "allowlist_mode_set",
+        "enabled"
+    ));
+    client.set_allowlisted(&merchant, &vetted, &true);
+    assert_eq!(
+        event_field::<Address>(&env, &client, "allowlist_updated", "owner"),
+        vetted
+    );
+
+    assert!(client
+        .try_init_sub(&stranger, &1, &merchant, &token, &None, &None)
+        .is_err());
+    assert!(client
+        .try_subscribe_from_template(&stranger, &1, &template_id, &None, &None)
+        .is_err());
+    client.set_max_owner_subs(&merchant, &2);
+    client.init_sub(&vetted, &1, &merchant, &token, &None, &None);
+    client.subscribe_from_template(&vetted, &2, &template_id, &None, &None);
+
+    // Other merchants are unaffected
+    client.init_sub(
+        &stranger,
+        &3,
+        &Address::generate(&env),
+        &token,
+        &None,
+        &None,
+    );
+
+    client.set_allowlist_mode(&merchant, &false);
+    client.init_sub(&stranger, &4, &merchant, &token, &None, &None);
+}
+
+#[test]
+fn test_allowlist_gates_offer_acceptance() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = offered_sub(&env, &client);
+    let merchant = client.get_sub(&1).merchant;
+    client.set_allowlist_mode(&merchant, &true);
+
+    assert!(client.try_accept_sub(&1, &1, &1000, &100).is_err());
+
+    client.set_allowlisted(&merchant, &owner, &true);
+    client.accept_sub(&1, &1, &1000, &100);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Active);
+
+    client.set_allowlisted(&merchant, &owner, &false);
+    assert!(!client.is_allowlisted(&merchant, &owner));
+}
+
+// ── Subscriptions per owner ───────────────────────────────────────
+
+#[test]
+fn test_duplicate_subscription_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let template_id = template(&env, &client);
+    let terms = client.get_template(&template_id).unwrap();
+    let owner = Address::generate(&env);
+
+    client.subscribe_from_template(&owner, &1, &template_id, &None, &None);
+    assert_eq!(client.get_owner_sub_count(&terms.merchant, &owner), 1);
+    assert_eq!(
+        client.try_subscribe_from_template(&owner, &2, &template_id, &None, &None),
+        Err(Ok(Error::TooManySubscriptions.into()))
+    );
+    assert_eq!(
+        client.try_init_sub(&owner, &2, &terms.merchant, &terms.token, &None, &None),
+        Err(Ok(Error::TooManySubscriptions.into()))
+    );
+    // An existing subscription can't be re-initialized
+    assert!(client
+        .try_init_sub(&owner, &1, &terms.merchant, &terms.token, &None, &None)
+        .is_err());
+
+    client.set_max_owner_subs(&terms.merchant, &2);
+    client.subscribe_from_template(&owner, &2, &template_id, &None, &None);
+    assert_eq!(client.get_owner_sub_count(&terms.merchant, &owner), 2);
+}
+
+#[test]
+fn test_ended_subscription_frees_its_slot() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let data = client.get_sub(&1);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&owner, &1, &1, &500, &0, &0, &false);
+    assert_eq!(client.get_sub(&1).state, SubscriptionState::Failed);
+    assert_eq!(client.get_owner_sub_count(&data.merchant, &owner), 0);
+    client.init_sub(&owner, &2, &data.merchant, &data.token, &None, &None);
+
+    // An offer only counts once accepted
+    client.offer_sub(&data.merchant, &3, &owner, &data.token, &50);
+    assert_eq!(
+        client.try_accept_sub(&3, &1, &1000, &100),
+        Err(Ok(Error::TooManySubscriptions.into()))
+    );
+}
+
+// ── Merchant health ───────────────────────────────────────────────
+
+/// Subscriptions 1..=`count` of one merchant, each with its own owner and
+/// approval 1. Returns the merchant and the owners.
+fn merchant_subs(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    count: u64,
+) -> (Address, std::vec::Vec<Address>) {
+    let merchant = Address::generate(env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(env))
+        .address();
+    let owners: std::vec::Vec<Address> = (1..=count)
+        .map(|sub_id| {
+            let owner = Address::generate(env);
+            token::StellarAssetClient::new(env, &token).mint(&owner, &1000);
+            client.init_sub(&owner, &sub_id, &merchant, &token, &None, &None);
+            client.approve_renewal(&sub_id, &1, &1000, &u32::MAX);
+            owner
+        })
+        .collect();
+    (merchant, owners)
+}
+
+#[test]
+fn test_failing_merchant_must_reverify() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (merchant, owners) = merchant_subs(&env, &client, MIN_HEALTH_ATTEMPTS as u64);
+
+    for (i, owner) in owners.iter().enumerate() {
+        client.renew(owner, &(i as u64 + 1), &1, &500, &3, &0, &false);
+    }
+    assert_eq!(
+        event_field::<u32>(&env, &client, "merchant_reverify_required", "failure_bps"),
+        10_000
+    );
+    let health = client.get_merchant_health(&merchant);
+    assert_eq!(
+        (health.attempts, health.failures),
+        (MIN_HEALTH_ATTEMPTS, MIN_HEALTH_ATTEMPTS)
+    );
+    assert_eq!(health.reverify_by, Some(REVERIFY_GRACE_LEDGERS));
+
+    // Renewals continue through the grace period, then stop
+    client.renew(&owners[0], &1, &1, &500, &3, &0, &false);
+    env.ledger()
+        .with_mut(|li| li.sequence_number = REVERIFY_GRACE_LEDGERS + 1);
+    assert_eq!(
+        client.try_renew(&owners[1], &2, &1, &500, &3, &0, &true),
+        Err(Ok(Error::MerchantSuspended.into()))
+    );
+    assert!(
+        client
+            .simulate_renew(&owners[1], &2, &1, &500, &0)
+            .merchant_suspended
+    );
+
+    client.reverify_merchant(&merchant);
+    assert_eq!(client.get_merchant_health(&merchant).attempts, 0);
+    assert!(client.renew(&owners[1], &2, &1, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_merchant_health_rolls_over_windows() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (merchant, owners) = merchant_subs(&env, &client, 10);
+
+    for (i, owner) in owners.iter().enumerate() {
+        client.renew(owner, &(i as u64 + 1), &1, &500, &3, &0, &(i >= 4));
+    }
+    let health = client.get_merchant_health(&merchant);
+    assert_eq!((health.attempts, health.failures), (10, 4));
+    assert_eq!(health.failure_bps, 4_000);
+    assert_eq!(health.reverify_by, None);
+    assert!(client.try_reverify_merchant(&merchant).is_err());
+
+    // The previous window still counts; the one before doesn't
+    env.ledger()
+        .with_mut(|li| li.sequence_number = HEALTH_WINDOW_LEDGERS);
+    assert_eq!(client.get_merchant_health(&merchant).attempts, 10);
+    env.ledger()
+        .with_mut(|li| li.sequence_number = 2 * HEALTH_WINDOW_LEDGERS);
+    assert_eq!(client.get_merchant_health(&merchant).attempts, 0);
+}
+
+#[test]
+fn test_one_subscription_cannot_sink_its_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (merchant, owners) = merchant_subs(&env, &client, 1);
+
+    for _ in 0..=MIN_HEALTH_ATTEMPTS {
+        client.renew(&owners[0], &1, &1, &500, &u32::MAX, &0, &false);
+    }
+    let health = client.get_merchant_health(&merchant);
+    assert_eq!((health.attempts, health.failures), (1, 1));
+    assert_eq!(health.reverify_by, None);
+
+    // It counts again in the next window
+    env.ledger()
+        .with_mut(|li| li.sequence_number = HEALTH_WINDOW_LEDGERS);
+    client.renew(&owners[0], &1, &1, &500, &u32::MAX, &0, &false);
+    assert_eq!(client.get_merchant_health(&merchant).failures, 2);
+}
+
+// ── Executor ──────────────────────────────────────────────────────
+
+#[test]
+fn test_executor_can_renew() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 600);
+    let executor = Address::generate(&env);
+    client.set_executor(&600, &executor);
+    client.approve_renewal(&600, &1, &1000, &100);
+
+    assert_eq!(client.get_executor(&600), Some(executor.clone()));
+    assert!(client.renew(&executor, &600, &1, &500, &3, &10, &true));
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized: caller must be owner or executor")]
+fn test_removed_executor_cannot_renew() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 601);
+    let executor = Address::generate(&env);
+    client.set_executor(&601, &executor);
+    client.remove_executor(&601);
+    client.approve_renewal(&601, &1, &1000, &100);
+
+    assert_eq!(client.get_executor(&601), None);
+    client.renew(&executor, &601, &1, &500, &3, &10, &true);
+}
+
+// ── Keepers ───────────────────────────────────────────────────────
+
+#[test]
+fn test_keeper_renews_and_builds_stats() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    scheduled_sub(&env, &client);
+    let keeper = Address::generate(&env);
+    client.register_keeper(&admin, &keeper);
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.renew(&keeper, &1, &1, &500, &3, &0, &true));
+    // The window for the cycle due at 200 has closed
+    env.ledger().with_mut(|li| li.sequence_number = 250);
+    assert!(!client.renew(&keeper, &1, &2, &500, &3, &0, &true));
+    env.ledger().with_mut(|li| li.sequence_number = 300);
+    assert!(client.renew(&keeper, &1, &2, &500, &3, &0, &true));
+
+    let stats = client.get_keeper(&keeper).unwrap();
+    assert_eq!(stats.successes, 2);
+    assert_eq!(stats.wasted, 1);
+}
+
+#[test]
+#[should_panic(expected = "Only the owner or executor can report a failed renewal")]
+fn test_keeper_cannot_report_failure() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    new_sub(&env, &client, 1);
+    let keeper = Address::generate(&env);
+    client.register_keeper(&admin, &keeper);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&keeper, &1, &1, &500, &1, &0, &false);
+}
+
+#[test]
+fn test_keeper_renews_on_default_retry_terms() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let keeper = Address::generate(&env);
+    client.register_keeper(&admin, &keeper);
+    for approval_id in 1..=3 {
+        client.approve_renewal(&1, &approval_id, &1000, &10_000);
+    }
+    client.renew(&owner, &1, &1, &500, &3, &0, &false);
+
+    // The keeper's zero cooldown is ignored
+    assert!(client
+        .try_renew(&keeper, &1, &2, &500, &3, &0, &true)
+        .is_err());
+    env.ledger().with_mut(|li| {
+        li.sequence_number = DEFAULT_RETRY_POLICY.cooldown_ledgers;
+    });
+    assert!(client.renew(&keeper, &1, &2, &500, &3, &0, &true));
+}
+
+#[test]
+fn test_keeper_fee_accrues_until_claimed() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+    client.set_payment_mode(&1, &PaymentMode::Allowance);
+    token.approve(&owner, &client.address, &10_000, &10_000);
+    client.approve_renewal(&1, &1, &1000, &100);
+    let keeper = Address::generate(&env);
+    client.register_keeper(&admin, &keeper);
+    client.set_keeper_fee(&Some(20));
+
+    assert!(client.renew(&keeper, &1, &1, &500, &3, &0, &true));
+    assert_eq!(token.balance(&data.merchant), 480);
+    assert_eq!(token.balance(&keeper), 0);
+    let rewards = client.get_keeper(&keeper).unwrap().rewards;
+    assert_eq!(rewards.get(data.token.clone()), Some(20));
+    assert_eq!(client.get_sub_economics(&1).keeper_fees, 20);
+
+    assert_eq!(
+        client.claim_keeper_rewards(&keeper, &data.token, &keeper),
+        20
+    );
+    assert_eq!(token.balance(&keeper), 20);
+    assert_eq!(token.balance(&client.address), 0);
+    assert!(client
+        .try_claim_keeper_rewards(&keeper, &data.token, &keeper)
+        .is_err());
+}
+
+#[test]
+fn test_owner_renewal_pays_no_keeper_fee() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let data = client.get_sub(&1);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.set_keeper_fee(&Some(20));
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    assert_eq!(
+        token::Client::new(&env, &data.token).balance(&data.merchant),
+        500
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized: caller must be owner or executor, or an active keeper")]
+fn test_suspended_keeper_cannot_renew() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    new_sub(&env, &client, 1);
+    let operator = Address::generate(&env);
+    let keeper = Address::generate(&env);
+    client.set_operator(&operator);
+    client.register_keeper(&admin, &keeper);
+    client.set_keeper_suspended(&operator, &keeper, &true);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert!(client.get_keeper(&keeper).unwrap().suspended);
+    client.renew(&keeper, &1, &1, &500, &3, &0, &true);
+}
+
+#[test]
+fn test_reinstated_keeper_can_renew() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    new_sub(&env, &client, 1);
+    let keeper = Address::generate(&env);
+    client.register_keeper(&admin, &keeper);
+    client.set_keeper_suspended(&admin, &keeper, &true);
+    client.set_keeper_suspended(&admin, &keeper, &false);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    assert!(client.renew(&keeper, &1, &1, &500, &3, &0, &true));
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized: caller must be admin or operator")]
+fn test_only_operator_registers_keepers() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let stranger = Address::generate(&env);
+    client.register_keeper(&stranger, &stranger);
+}
+
+// ── Retry subsidies ───────────────────────────────────────────────
+
+/// Subscription 1 whose merchant funded a 300 subsidy at 200 per retry,
+/// a registered keeper, and approvals 1..=7.
+fn subsidized_sub(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    admin: &Address,
+) -> (Address, Address) {
+    new_sub(env, client, 1);
+    let data = client.get_sub(&1);
+    token::StellarAssetClient::new(env, &data.token).mint(&data.merchant, &1_000);
+    client.fund_retry_subsidy(&data.merchant, &1, &300, &200);
+    let keeper = Address::generate(env);
+    client.register_keeper(admin, &keeper);
+    for approval_id in 1..=7 {
+        client.approve_renewal(&1, &approval_id, &1000, &10_000);
+    }
+    (keeper, data.token)
+}
+
+#[test]
+fn test_retry_subsidy_reimburses_keeper_retries() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let (keeper, token) = subsidized_sub(&env, &client, &admin);
+    let token = token::Client::new(&env, &token);
+
+    let owner = client.get_sub(&1).owner;
+    let cooldown = DEFAULT_RETRY_POLICY.cooldown_ledgers;
+
+    // The first attempt of the cycle isn't subsidized
+    assert!(client.renew(&keeper, &1, &1, &500, &5, &0, &true));
+    assert_eq!(token.balance(&keeper), 0);
+
+    client.renew(&owner, &1, &2, &500, &5, &0, &false);
+    env.ledger().with_mut(|li| li.sequence_number = cooldown);
+    assert!(client.renew(&keeper, &1, &3, &500, &5, &0, &true));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "retry_subsidy_paid", "remaining"),
+        100
+    );
+    assert_eq!(token.balance(&keeper), 200);
+
+    // What is left pays part of the next retry
+    client.renew(&owner, &1, &4, &500, &5, &0, &false);
+    env.ledger()
+        .with_mut(|li| li.sequence_number = 2 * cooldown);
+    assert!(client.renew(&keeper, &1, &5, &500, &5, &0, &true));
+    assert_eq!(token.balance(&keeper), 300);
+    assert_eq!(client.get_retry_subsidy(&1).unwrap().remaining, 0);
+
+    client.renew(&owner, &1, &6, &500, &5, &0, &false);
+    env.ledger()
+        .with_mut(|li| li.sequence_number = 3 * cooldown);
+    assert!(client.renew(&keeper, &1, &7, &500, &5, &0, &true));
+    assert_eq!(token.balance(&keeper), 300);
+}
+
+#[test]
+fn test_retry_subsidy_only_for_keepers() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    subsidized_sub(&env, &client, &admin);
+    let owner = client.get_sub(&1).owner;
+
+    client.renew(&owner, &1, &1, &500, &5, &0, &false);
+    client.renew(&owner, &1, &2, &500, &5, &0, &false);
+    assert_eq!(client.get_retry_subsidy(&1).unwrap().remaining, 300);
+}
+
+#[test]
+fn test_retry_subsidy_paid_once_per_cooldown() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let (keeper, token) = subsidized_sub(&env, &client, &admin);
+    // As the executor, the keeper may report failures itself
+    client.set_executor(&1, &keeper);
+    client.fund_retry_subsidy(&client.get_sub(&1).merchant, &1, &300, &200);
+
+    client.renew(&keeper, &1, &1, &500, &10, &0, &false);
+    env.ledger().with_mut(|li| {
+        li.sequence_number = DEFAULT_RETRY_POLICY.cooldown_ledgers;
+    });
+    for approval_id in 2..=4 {
+        client.renew(&keeper, &1, &approval_id, &500, &10, &0, &false);
+    }
+
+    assert_eq!(token::Client::new(&env, &token).balance(&keeper), 200);
+    let subsidy = client.get_retry_subsidy(&1).unwrap();
+    assert_eq!(subsidy.remaining, 400);
+    assert_eq!((subsidy.paid_cycle, subsidy.paid_retry), (0, 1));
+}
+
+#[test]
+fn test_merchant_withdraws_retry_subsidy() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let (_, token) = subsidized_sub(&env, &client, &admin);
+    let merchant = client.get_sub(&1).merchant;
+
+    assert_eq!(client.withdraw_retry_subsidy(&merchant, &1), 300);
+    assert_eq!(token::Client::new(&env, &token).balance(&merchant), 1_000);
+    assert_eq!(client.get_retry_subsidy(&1), None);
+    assert!(client
+        .try_withdraw_retry_subsidy(&Address::generate(&env), &1)
+        .is_err());
+}
+
+// ── Permissionless crank ──────────────────────────────────────────
+
+/// Priced, scheduled subscription 1 (due 100) with crank bounty 5.
+fn crankable_sub(env: &Env, client: &SubscriptionRenewalContractClient) {
+    let owner = new_sub(env, client, 1);
+    client.set_payment_mode(&1, &PaymentMode::Allowance);
+    token::Client::new(env, &client.get_sub(&1).token).approve(
+        &owner,
+        &client.address,
+        &10_000,
+        &10_000,
+    );
+    client.set_pricing(&1, &500, &1);
+    client.set_schedule(&1, &100, &100, &10);
+    for approval_id in 1..=3 {
+        client.approve_renewal(&1, &approval_id, &1000, &1000);
+    }
+    client.set_crank_bounty(&Some(5));
+}
+
+#[test]
+fn test_anyone_can_crank_for_bounty() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    let cranker = Address::generate(&env);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    assert!(client.crank(&cranker, &1, &1));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "renewal_success", "charged"),
+        500
+    );
+    assert_eq!(
+        event_field::<i128>(&env, &client, "crank_bounty_paid", "bounty"),
+        5
+    );
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 200);
+}
+
+#[test]
+fn test_crank_bounty_transferred_to_cranker() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    let recipient = Address::generate(&env);
+    client.set_fee_config(&1_000, &recipient);
+    let cranker = Address::generate(&env);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    assert!(client.crank(&cranker, &1, &1));
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+    // 10% fee first, then the bounty out of the merchant's remainder
+    assert_eq!(token.balance(&recipient), 50);
+    assert_eq!(token.balance(&cranker), 5);
+    assert_eq!(token.balance(&data.merchant), 445);
+    assert_eq!(token.balance(&client.address), 0);
+}
+
+#[test]
+fn test_undeliverable_crank_bounty_is_deferred() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    // An account without a trustline for the token can't receive it
+    let cranker = Address::try_from_val(
+        &env,
+        &ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([7; 32])))),
+    )
+    .unwrap();
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    assert!(client.crank(&cranker, &1, &1));
+    assert_eq!(
+        event_topic::<Address>(&env, &client, "payout_deferred", 1),
+        cranker
+    );
+    assert_eq!(
+        event_field::<i128>(&env, &client, "payout_deferred", "claimable"),
+        5
+    );
+    let data = client.get_sub(&1);
+    assert_eq!(client.get_claimable(&cranker, &data.token), 5);
+    assert_eq!(
+        token::Client::new(&env, &data.token).balance(&data.merchant),
+        495
+    );
+}
+
+#[test]
+#[should_panic(expected = "Cranking needs Allowance or Escrow payments")]
+fn test_crank_rejects_owner_signed_payments() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.crank(&Address::generate(&env), &1, &1);
+}
+
+#[test]
+#[should_panic(expected = "Renewal not yet due")]
+fn test_second_crank_in_cycle_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    let cranker = Address::generate(&env);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.crank(&cranker, &1, &1);
+    client.crank(&Address::generate(&env), &1, &2);
+}
+
+#[test]
+fn test_crank_again_next_cycle() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    let cranker = Address::generate(&env);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.crank(&cranker, &1, &1);
+
+    env.ledger().with_mut(|li| li.sequence_number = 200);
+    assert!(client.crank(&cranker, &1, &2));
+    assert_eq!(
+        event_field::<u32>(&env, &client, "crank_bounty_paid", "due"),
+        200
+    );
+}
+
+#[test]
+#[should_panic(expected = "Permissionless cranking disabled")]
+fn test_crank_disabled_by_default() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_bounty(&None);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.crank(&Address::generate(&env), &1, &1);
+}
+
+#[test]
+fn test_committed_crank_reveals_in_later_ledger() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_commit_reveal(&true);
+    let cranker = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    client.commit_crank(&cranker, &client.crank_commitment(&cranker, &1, &salt));
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.reveal_crank(&cranker, &1, &1, &salt));
+    assert_eq!(
+        event_field::<i128>(&env, &client, "crank_bounty_paid", "bounty"),
+        5
+    );
+}
+
+#[test]
+#[should_panic(expected = "Commitment too recent")]
+fn test_same_ledger_commit_and_reveal_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_commit_reveal(&true);
+    let sniper = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[2; 32]);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.commit_crank(&sniper, &client.crank_commitment(&sniper, &1, &salt));
+    client.reveal_crank(&sniper, &1, &1, &salt);
 }
 
-#[contractevent]
-pub struct SubscriptionRenewed {
-    pub subscriber: Address,
+#[test]
+#[should_panic(expected = "No matching commitment")]
+fn test_copied_reveal_without_commitment_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_commit_reveal(&true);
+    let cranker = Address::generate(&env);
+    let sniper = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[3; 32]);
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    client.commit_crank(&cranker, &client.crank_commitment(&cranker, &1, &salt));
+
+    // The sniper replays the revealed arguments under their own address
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.reveal_crank(&sniper, &1, &1, &salt);
+}
+
+#[test]
+fn test_first_of_competing_reveals_wins() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_commit_reveal(&true);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let salt = BytesN::from_array(&env, &[4; 32]);
+    env.ledger().with_mut(|li| li.sequence_number = 99);
+    client.commit_crank(&first, &client.crank_commitment(&first, &1, &salt));
+    client.commit_crank(&second, &client.crank_commitment(&second, &1, &salt));
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    assert!(client.reveal_crank(&first, &1, &1, &salt));
+    assert!(client.try_reveal_crank(&second, &1, &2, &salt).is_err());
+    assert_eq!(client.get_schedule(&1).unwrap().next_due, 200);
+}
+
+#[test]
+#[should_panic(expected = "Cranks must be committed first")]
+fn test_plain_crank_rejected_under_commit_reveal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_crank_commit_reveal(&true);
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+
+    client.crank(&Address::generate(&env), &1, &1);
+}
+
+// ── Fees / Config ─────────────────────────────────────────────────
+
+#[test]
+fn test_set_and_get_fee_config() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let recipient = Address::generate(&env);
+    client.set_fee_config(&500, &recipient);
+
+    let config = client.get_fee_config().unwrap();
+    assert_eq!(config.percentage, 500);
+    assert_eq!(config.recipient, recipient);
+}
+
+#[test]
+#[should_panic(expected = "Fee percentage exceeds 100%")]
+fn test_set_fee_config_invalid_percentage() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    client.set_fee_config(&10_001, &Address::generate(&env));
+}
+
+#[test]
+fn test_set_logging_contract() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let logging = Address::generate(&env);
+    client.set_logging_contract(&logging);
+    assert_eq!(client.get_logging_contract(), Some(logging));
+}
+
+// ── Economics ─────────────────────────────────────────────────────
+
+#[test]
+fn test_economics_track_charges_fees_and_refunds() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    crankable_sub(&env, &client);
+    client.set_fee_config(&1_000, &Address::generate(&env));
+
+    env.ledger().with_mut(|li| li.sequence_number = 100);
+    client.crank(&Address::generate(&env), &1, &1);
+    env.ledger().with_mut(|li| li.sequence_number = 200);
+    client.renew(&client.get_sub(&1).owner, &1, &2, &500, &0, &0, &true);
+
+    let data = client.get_sub(&1);
+    token::StellarAssetClient::new(&env, &data.token).mint(&data.merchant, &100);
+    client.refund(&1, &100, &false);
+
+    assert_eq!(
+        client.get_sub_economics(&1),
+        SubEconomics {
+            charged: 1_000,
+            credit_applied: 0,
+            protocol_fees: 100,
+            keeper_fees: 5,
+            refunded: 100,
+        }
+    );
+}
+
+#[test]
+fn test_economics_book_only_collected_fees() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_fee_config(&1_000, &Address::generate(&env));
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    // An External payment never reaches the contract, so no fee is taken
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    let economics = client.get_sub_economics(&1);
+    assert_eq!((economics.charged, economics.protocol_fees), (500, 0));
+}
+
+#[test]
+fn test_economics_skip_failed_renewals() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &false);
+    assert_eq!(client.get_sub_economics(&1), SubEconomics::default());
+}
+
+#[test]
+fn test_owner_spending_per_window_and_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = Address::generate(&env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    token::StellarAssetClient::new(&env, &token).mint(&owner, &10_000);
+    let (video, music) = (Address::generate(&env), Address::generate(&env));
+    client.set_max_owner_subs(&video, &2);
+    client.init_sub(&owner, &1, &video, &token, &None, &None);
+    client.init_sub(&owner, &2, &music, &token, &None, &None);
+    client.init_sub(&owner, &3, &video, &token, &None, &None);
+    for sub_id in 1..=3 {
+        client.approve_renewal(&sub_id, &1, &1000, &SPENDING_WINDOW_LEDGERS);
+        client.approve_renewal(&sub_id, &2, &1000, &SPENDING_WINDOW_LEDGERS);
+    }
+
+    client.renew(&owner, &1, &1, &500, &3, &0, &true);
+    client.renew(&owner, &2, &1, &200, &3, &0, &true);
+    client.renew(&owner, &3, &1, &100, &3, &0, &false);
+    client.renew(&owner, &3, &2, &100, &3, &0, &true);
+    let spending = client.get_owner_spending(&owner, &token, &None);
+    assert_eq!(spending.window, 0);
+    assert_eq!(spending.total, 800);
+    assert_eq!(spending.by_merchant.get(video.clone()), Some(600));
+    assert_eq!(spending.by_merchant.get(music), Some(200));
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number = SPENDING_WINDOW_LEDGERS);
+    client.renew(&owner, &1, &2, &500, &3, &0, &true);
+    let spending = client.get_owner_spending(&owner, &token, &None);
+    assert_eq!(spending.window, 1);
+    assert_eq!(spending.total, 500);
+    assert_eq!(spending.by_merchant.len(), 1);
+    assert_eq!(
+        client.get_owner_spending(&owner, &token, &Some(0)).total,
+        800
+    );
+    assert_eq!(
+        client
+            .get_owner_spending(&Address::generate(&env), &token, &None)
+            .total,
+        0
+    );
+}
+
+/// `count` successful renewals of 100 each, one per subscription, all for
+/// the returned merchant.
+fn settled_renewals(env: &Env, client: &SubscriptionRenewalContractClient, count: u32) -> Address {
+    let merchant = Address::generate(env);
+    let owner = Address::generate(env);
+    let token = env
+        .register_stellar_asset_contract_v2(Address::generate(env))
+        .address();
+    token::StellarAssetClient::new(env, &token).mint(&owner, &(count as i128 * 100));
+    client.set_max_owner_subs(&merchant, &count);
+    for sub_id in 1..=count as u64 {
+        client.init_sub(&owner, &sub_id, &merchant, &token, &None, &None);
+        client.approve_renewal(&sub_id, &1, &100, &SETTLEMENT_PERIOD_LEDGERS);
+        client.renew(&owner, &sub_id, &1, &100, &3, &0, &true);
+    }
+    merchant
+}
+
+#[test]
+fn test_settlement_export_reproduces_stored_hash() {
+    use soroban_sdk::xdr::{Limited, Limits, ReadXdr, ScVal, WriteXdr};
+
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let merchant = settled_renewals(&env, &client, MAX_SETTLEMENT_PAGE + 2);
+
+    let first = client.export_settlement(
+        &merchant,
+        &SettlementCursor {
+            period: 0,
+            index: 0,
+        },
+    );
+    assert_eq!(first.count, MAX_SETTLEMENT_PAGE);
+    assert_eq!(first.period.count, MAX_SETTLEMENT_PAGE + 2);
+    assert_eq!(first.next_index, Some(MAX_SETTLEMENT_PAGE));
+    let second = client.export_settlement(
+        &merchant,
+        &SettlementCursor {
+            period: 0,
+            index: MAX_SETTLEMENT_PAGE,
+        },
+    );
+    assert_eq!(second.count, 2);
+    assert_eq!(second.next_index, None);
+
+    // Rebuild the hash chain from the exported bytes alone, outside the
+    // contract's budget
+    env.cost_estimate().budget().reset_unlimited();
+    let mut hash = BytesN::from_array(&env, &[0; 32]);
+    let mut sub_ids = std::vec::Vec::new();
+    for page in [first, second] {
+        let bytes: std::vec::Vec<u8> = page.receipts.iter().collect();
+        let mut reader = Limited::new(bytes.as_slice(), Limits::none());
+        for _ in 0..page.count {
+            let val = ScVal::read_xdr(&mut reader).unwrap();
+            let receipt = SettlementReceipt::try_from_val(&env, &val).unwrap();
+            assert_eq!(receipt.charged, 100);
+            sub_ids.push(receipt.sub_id);
+            let mut preimage: Bytes = hash.into();
+            let xdr = WriteXdr::to_xdr(&val, Limits::none()).unwrap();
+            preimage.append(&Bytes::from_slice(&env, &xdr));
+            hash = env.crypto().sha256(&preimage).to_bytes();
+        }
+        assert!(reader.inner.is_empty());
+    }
+    assert_eq!(
+        sub_ids,
+        (1..=MAX_SETTLEMENT_PAGE as u64 + 2).collect::<std::vec::Vec<_>>()
+    );
+    assert_eq!(hash, client.get_settlement_period(&merchant, &0).hash);
+
+    // Later renewals fall in the next period
+    assert_eq!(client.get_settlement_period(&merchant, &1).count, 0);
+    let empty = client.export_settlement(
+        &merchant,
+        &SettlementCursor {
+            period: 1,
+            index: 0,
+        },
+    );
+    assert_eq!(
+        (empty.count, empty.receipts.len(), empty.next_index),
+        (0, 0, None)
+    );
+}
+
+#[test]
+fn test_settlement_export_page_cost() {
+    let cost = &crate::paging::SETTLEMENT_EXPORT;
+    for items in [1, MAX_SETTLEMENT_PAGE] {
+        let env = Env::default();
+        let (client, _) = setup(&env);
+        let merchant = settled_renewals(&env, &client, items);
+        client.export_settlement(
+            &merchant,
+            &SettlementCursor {
+                period: 0,
+                index: 0,
+            },
+        );
+
+        let metered = env.cost_estimate().resources();
+        assert!(
+            metered.memory_read_entries + metered.disk_read_entries
+                <= cost.fixed.entries + items * cost.item.entries
+        );
+        assert!(metered.write_entries <= cost.fixed.writes + items * cost.item.writes);
+        assert!(
+            metered.instructions as u64
+                <= cost.fixed.instructions + items as u64 * cost.item.instructions
+        );
+    }
+}
+
+// ── Compliance freeze ─────────────────────────────────────────────
+
+#[test]
+#[should_panic(expected = "Subscription is frozen")]
+fn test_frozen_sub_cannot_renew() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    client.freeze_sub(&admin, &1, &symbol_short!("legal"));
+    client.renew(&owner, &1, &1, &500, &3, &10, &true);
+}
+
+#[test]
+#[should_panic(expected = "Subscription is frozen")]
+fn test_frozen_sub_rejects_approvals() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    new_sub(&env, &client, 1);
+
+    client.freeze_sub(&admin, &1, &symbol_short!("legal"));
+    client.approve_renewal(&1, &1, &1000, &100);
+}
+
+#[test]
+fn test_freeze_preserves_state() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let officer = Address::generate(&env);
+    client.set_compliance_officer(&officer);
+
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.approve_renewal(&1, &2, &1000, &100);
+    client.renew(&owner, &1, &1, &500, &3, &10, &false);
+    let before = client.get_sub(&1);
+
+    client.freeze_sub(&officer, &1, &symbol_short!("hold"));
+    assert_eq!(client.get_freeze(&1), Some(symbol_short!("hold")));
+    assert_eq!(client.get_sub(&1), before);
+
+    client.unfreeze_sub(&officer, &1);
+    assert_eq!(client.get_freeze(&1), None);
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    assert!(client.renew(&owner, &1, &2, &500, &3, &10, &true));
+}
+
+#[test]
+#[should_panic(expected = "Unauthorized: caller must be admin or compliance officer")]
+fn test_freeze_requires_compliance_role() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.freeze_sub(&owner, &1, &symbol_short!("legal"));
 }
 
+// ── Compliance hook ───────────────────────────────────────────────
+
+/// Stand-in screening contract: allows or blocks everything and records the
+/// last request it saw.
 #[contract]
-pub struct SubscriptionRenewal;
+struct MockCompliance;
 
 #[contractimpl]
-impl SubscriptionRenewal {
+impl MockCompliance {
+    pub fn set_allow(env: Env, allow: bool) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("allow"), &allow);
+    }
 
-    pub fn create_subscription(
+    pub fn check(
         env: Env,
-        subscriber: Address,
-        plan_id: Symbol,
-        next_payment_time: u64,
-    ) {
-        subscriber.require_auth();
-
-        let subscription = Subscription {
-            subscriber: subscriber.clone(),
-            plan_id: plan_id.clone(),
-            next_payment_time,
-            active: true,
-        };
-
+        owner: Address,
+        merchant: Address,
+        token: Address,
+        amount: i128,
+    ) -> bool {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("last"), &(owner, merchant, token, amount));
         env.storage()
             .instance()
-            .set(&DataKey::Subscription(subscriber.clone()), &subscription);
+            .get(&symbol_short!("allow"))
+            .unwrap_or(false)
+    }
 
-        env.events().publish(
-            (Symbol::new(&env, "subscription_created"),),
-            SubscriptionCreated { subscriber, plan_id },
-        );
+    pub fn last(env: Env) -> Option<(Address, Address, Address, i128)> {
+        env.storage().instance().get(&symbol_short!("last"))
     }
+}
+
+fn setup_compliance(
+    env: &Env,
+    client: &SubscriptionRenewalContractClient,
+    allow: bool,
+) -> MockComplianceClient<'static> {
+    let compliance = MockComplianceClient::new(env, &env.register(MockCompliance, ()));
+    compliance.set_allow(&allow);
+    client.set_compliance_contract(&Some(compliance.address.clone()));
+    compliance
+}
+
+#[test]
+fn test_compliance_hook_allows_renewal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    let compliance = setup_compliance(&env, &client, true);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &10, &true));
+    let sub = client.get_sub(&1);
+    assert_eq!(
+        compliance.last(),
+        Some((owner, sub.merchant, sub.token, 500))
+    );
+}
+
+#[test]
+fn test_compliance_hook_rejects_renewal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    setup_compliance(&env, &client, false);
+
+    let result = client.try_renew(&owner, &1, &1, &500, &3, &10, &true);
+    assert_eq!(result, Err(Ok(Error::ComplianceRejected.into())));
+    // The rejected attempt rolled back, so the approval is still unused.
+    assert!(!client.get_approval(&1, &1).unwrap().used);
+}
+
+#[test]
+fn test_compliance_hook_can_be_cleared() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    setup_compliance(&env, &client, false);
+
+    client.set_compliance_contract(&None);
+    assert_eq!(client.get_compliance_contract(), None);
+    assert!(client.renew(&owner, &1, &1, &500, &3, &10, &true));
+}
+
+// ── Volume circuit limits ─────────────────────────────────────────
+
+fn volume_limit(max_amount: i128, window_ledgers: u32) -> Option<VolumeLimit> {
+    Some(VolumeLimit {
+        max_amount,
+        window_ledgers,
+    })
+}
+
+#[test]
+fn test_token_volume_limit_blocks_renewal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let token = client.get_sub(&1).token;
+    client.set_volume_limit(&Some(token.clone()), &volume_limit(800, 100));
+    client.approve_renewal(&1, &1, &1000, &1000);
+    client.approve_renewal(&1, &2, &1000, &1000);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    let usage = client.get_volume_usage(&Some(token)).unwrap();
+    assert_eq!(usage.total, 500);
+
+    let result = client.try_renew(&owner, &1, &2, &500, &3, &0, &true);
+    assert_eq!(result, Err(Ok(Error::VolumeLimitExceeded.into())));
+}
+
+#[test]
+fn test_global_volume_limit_spans_tokens() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let first = new_sub(&env, &client, 1);
+    let second = new_sub(&env, &client, 2);
+    assert_ne!(client.get_sub(&1).token, client.get_sub(&2).token);
+    client.set_volume_limit(&None, &volume_limit(800, 100));
+    client.approve_renewal(&1, &1, &1000, &1000);
+    client.approve_renewal(&2, &1, &1000, &1000);
+
+    assert!(client.renew(&first, &1, &1, &500, &3, &0, &true));
+    let result = client.try_renew(&second, &2, &1, &500, &3, &0, &true);
+    assert_eq!(result, Err(Ok(Error::VolumeLimitExceeded.into())));
+}
+
+#[test]
+fn test_volume_window_resets() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_volume_limit(&None, &volume_limit(500, 100));
+    client.approve_renewal(&1, &1, &1000, &1000);
+    client.approve_renewal(&1, &2, &1000, &1000);
+
+    assert!(client.renew(&owner, &1, &1, &500, &3, &0, &true));
+    env.ledger().with_mut(|li| li.sequence_number += 100);
+    assert!(client.renew(&owner, &1, &2, &500, &3, &0, &true));
+    let usage = client.get_volume_usage(&None).unwrap();
+    assert_eq!(usage.window_start, 100);
+    assert_eq!(usage.total, 500);
+}
+
+#[test]
+fn test_failed_renewal_does_not_count_volume() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.set_volume_limit(&None, &volume_limit(500, 100));
+    client.approve_renewal(&1, &1, &1000, &1000);
+
+    assert!(!client.renew(&owner, &1, &1, &500, &3, &0, &false));
+    assert_eq!(client.get_volume_usage(&None), None);
+}
+
+// ── Token rescue ──────────────────────────────────────────────────
+
+/// Register a Stellar asset and mint `amount` straight to the renewal
+/// contract, as if sent there by mistake.
+fn strand_tokens(env: &Env, client: &SubscriptionRenewalContractClient, amount: i128) -> Address {
+    let issuer = Address::generate(env);
+    let token = env.register_stellar_asset_contract_v2(issuer).address();
+    token::StellarAssetClient::new(env, &token).mint(&client.address, &amount);
+    token
+}
+
+#[test]
+fn test_rescue_tokens_after_timelock() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+
+    client.propose_rescue(&token, &to, &600);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS);
+    client.rescue_tokens(&token, &to, &600);
+
+    let balances = token::Client::new(&env, &token);
+    assert_eq!(balances.balance(&to), 600);
+    assert_eq!(balances.balance(&client.address), 400);
+    assert_eq!(client.get_pending_rescue(), None);
+}
+
+#[test]
+#[should_panic(expected = "Rescue timelock active")]
+fn test_rescue_tokens_respects_timelock() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+
+    client.propose_rescue(&token, &to, &600);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS - 1);
+    client.rescue_tokens(&token, &to, &600);
+}
+
+#[test]
+#[should_panic(expected = "Rescue does not match proposal")]
+fn test_rescue_tokens_must_match_proposal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+
+    client.propose_rescue(&token, &to, &600);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS);
+    client.rescue_tokens(&token, &to, &1000);
+}
+
+#[test]
+#[should_panic(expected = "Rescue exceeds untracked balance")]
+fn test_rescue_tokens_excludes_tracked_balance() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+    env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .set(&TrackedKey::Balance(token.clone()), &700i128);
+    });
+
+    client.propose_rescue(&token, &to, &600);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS);
+    client.rescue_tokens(&token, &to, &600);
+}
 
-    pub fn renew_subscription(env: Env, subscriber: Address) {
-        subscriber.require_auth();
+// ── Reentrancy ────────────────────────────────────────────────────
 
-        let key = DataKey::Subscription(subscriber.clone());
-        let mut subscription: Subscription = env
+/// Hook that tries to renew the calling subscription again from inside
+/// `check`, the way a malicious token or merchant callback would. The host
+/// rejects re-entry into a contract already on the call stack.
+#[contract]
+struct ReentrantHook;
+
+#[contractimpl]
+impl ReentrantHook {
+    pub fn set_target(env: Env, target: Address, sub_id: u64, approval_id: u64) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("target"), &(target, sub_id, approval_id));
+    }
+
+    pub fn check(
+        env: Env,
+        owner: Address,
+        _merchant: Address,
+        _token: Address,
+        amount: i128,
+    ) -> bool {
+        let (target, sub_id, approval_id): (Address, u64, u64) = env
             .storage()
             .instance()
-            .get(&key)
+            .get(&symbol_short!("target"))
             .unwrap();
+        SubscriptionRenewalContractClient::new(&env, &target).renew(
+            &owner,
+            &sub_id,
+            &approval_id,
+            &amount,
+            &3,
+            &0,
+            &true,
+        )
+    }
+}
+
+#[test]
+fn test_reentrant_hook_cannot_renew_twice() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.approve_renewal(&1, &2, &1000, &100);
+
+    let hook = ReentrantHookClient::new(&env, &env.register(ReentrantHook, ()));
+    hook.set_target(&client.address, &1, &2);
+    client.set_compliance_contract(&Some(hook.address.clone()));
+
+    assert!(client
+        .try_renew(&owner, &1, &1, &500, &3, &0, &true)
+        .is_err());
+    assert!(!client.get_approval(&1, &1).unwrap().used);
+    assert!(!client.get_approval(&1, &2).unwrap().used);
+    assert_eq!(client.get_sub(&1).last_attempt_ledger, 0);
+}
+
+// ── Admin audit trail ─────────────────────────────────────────────
+
+/// Decode the last `AdminAction` event as (action, admin, old, new).
+fn last_admin_action(env: &Env) -> (Symbol, Address, Bytes, Bytes) {
+    let (_, topics, data) = env.events().all().last().unwrap();
+    let name: Symbol = topics.get(0).unwrap().into_val(env);
+    assert_eq!(name, Symbol::new(env, "admin_action"));
+    let data: Map<Symbol, Val> = data.into_val(env);
+    let field = |name: &str| data.get(Symbol::new(env, name)).unwrap();
+    (
+        topics.get(1).unwrap().into_val(env),
+        field("admin").into_val(env),
+        field("old_value").into_val(env),
+        field("new_value").into_val(env),
+    )
+}
+
+fn encoded<T: IntoVal<Env, Val>>(env: &Env, value: T) -> Bytes {
+    let value: Val = value.into_val(env);
+    value.to_xdr(env)
+}
+
+#[test]
+fn test_admin_action_on_pause() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    client.set_paused(&true);
+
+    let (action, actor, old, new) = last_admin_action(&env);
+    assert_eq!(action, symbol_short!("pause"));
+    assert_eq!(actor, admin);
+    assert_eq!(old, encoded(&env, false));
+    assert_eq!(new, encoded(&env, true));
+}
+
+#[test]
+fn test_admin_action_on_fee_config() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    client.set_fee_config(&250, &first);
+    client.set_fee_config(&500, &second);
+
+    let (action, actor, old, new) = last_admin_action(&env);
+    assert_eq!(action, symbol_short!("fee_cfg"));
+    assert_eq!(actor, admin);
+    let old_config = FeeConfig {
+        percentage: 250,
+        recipient: first,
+    };
+    let new_config = FeeConfig {
+        percentage: 500,
+        recipient: second,
+    };
+    assert_eq!(old, encoded(&env, Some(old_config)));
+    assert_eq!(new, encoded(&env, Some(new_config)));
+}
+
+#[test]
+fn test_admin_action_on_logging_contract() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let logging = Address::generate(&env);
+    client.set_logging_contract(&logging);
+
+    let (action, actor, old, new) = last_admin_action(&env);
+    assert_eq!(action, symbol_short!("logging"));
+    assert_eq!(actor, admin);
+    assert_eq!(old, encoded(&env, None::<Address>));
+    assert_eq!(new, encoded(&env, Some(logging)));
+}
+
+// ── Admin dry runs ────────────────────────────────────────────────
+
+#[test]
+fn test_simulate_fee_config_leaves_config() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    client.set_fee_config(&250, &first);
+
+    let effect = client.simulate_fee_config(&500, &second);
+
+    let old_config = FeeConfig {
+        percentage: 250,
+        recipient: first,
+    };
+    let new_config = FeeConfig {
+        percentage: 500,
+        recipient: second,
+    };
+    assert_eq!(effect.action, symbol_short!("fee_cfg"));
+    assert_eq!(effect.old_value, encoded(&env, Some(old_config.clone())));
+    assert_eq!(effect.new_value, encoded(&env, Some(new_config)));
+    assert_eq!(client.get_fee_config(), Some(old_config));
+    assert!(client
+        .try_simulate_fee_config(&(MAX_FEE_BPS + 1), &Address::generate(&env))
+        .is_err());
+}
+
+#[test]
+fn test_simulate_rescue_moves_nothing() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+    client.propose_rescue(&token, &to, &600);
+    let pending = client.get_pending_rescue().unwrap();
+
+    // Still timelocked
+    assert!(client.try_simulate_rescue(&token, &to, &600).is_err());
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS);
+    let effect = client.simulate_rescue(&token, &to, &600);
+
+    assert_eq!(effect.action, symbol_short!("rescue"));
+    assert_eq!(effect.old_value, encoded(&env, Some(pending.clone())));
+    assert_eq!(effect.new_value, encoded(&env, None::<PendingRescue>));
+    assert_eq!(token::Client::new(&env, &token).balance(&to), 0);
+    assert_eq!(client.get_pending_rescue(), Some(pending));
+}
+
+// ── Withdrawal freeze ─────────────────────────────────────────────
+
+#[test]
+fn test_frozen_withdrawals_hold_payouts() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let (merchant, owners) = merchant_subs(&env, &client, 1);
+    let token = client.get_sub(&1).token;
+    client.set_payment_mode(&1, &PaymentMode::Direct);
 
-        if !subscription.active {
-            panic!("Subscription not active");
+    client.set_withdrawals_frozen(&true);
+    assert!(event_field::<bool>(
+        &env,
+        &client,
+        "withdrawals_frozen",
+        "frozen"
+    ));
+    assert!(client.is_withdrawals_frozen());
+
+    // Renewals still settle, but the merchant's share stays in the contract
+    assert!(client.renew(&owners[0], &1, &1, &500, &3, &0, &true));
+    assert_eq!(client.get_claimable(&merchant, &token), 500);
+    assert_eq!(token::Client::new(&env, &token).balance(&merchant), 0);
+    let to = Address::generate(&env);
+    assert!(client.try_claim_payout(&merchant, &token, &to).is_err());
+
+    client.set_withdrawals_frozen(&false);
+    assert_eq!(client.claim_payout(&merchant, &token, &to), 500);
+    assert_eq!(token::Client::new(&env, &token).balance(&to), 500);
+}
+
+#[test]
+#[should_panic(expected = "Withdrawals are frozen")]
+fn test_frozen_withdrawals_block_rescue() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+    let to = Address::generate(&env);
+
+    client.propose_rescue(&token, &to, &600);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += RESCUE_TIMELOCK_LEDGERS);
+    client.set_withdrawals_frozen(&true);
+    client.rescue_tokens(&token, &to, &600);
+}
+
+#[test]
+fn test_frozen_withdrawals_allow_cancellation() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let token = strand_tokens(&env, &client, 1000);
+
+    client.propose_rescue(&token, &Address::generate(&env), &600);
+    client.set_withdrawals_frozen(&true);
+    client.cancel_rescue();
+    assert_eq!(client.get_pending_rescue(), None);
+}
+
+// ── Rounding ──────────────────────────────────────────────────────
+
+#[test]
+fn test_bps_split_residue_goes_to_merchant() {
+    use crate::rounding::{split_bps, BPS_DENOMINATOR};
+
+    for amount in (-1_000..=1_000).chain([i128::MAX / BPS_DENOMINATOR, i128::MIN / BPS_DENOMINATOR])
+    {
+        for bps in 0..=MAX_FEE_BPS {
+            let (share, merchant) = split_bps(amount, bps);
+            assert_eq!(share + merchant, amount);
+            // The share never exceeds its exact value and is off by less
+            // than one unit, always in the merchant's favour
+            let exact = amount * bps as i128;
+            let residue = exact - share * BPS_DENOMINATOR;
+            assert!(residue.abs() < BPS_DENOMINATOR);
+            assert!(residue == 0 || residue.signum() == amount.signum());
         }
+    }
+}
+
+#[test]
+fn test_bps_split_edges() {
+    use crate::rounding::{bps_share, split_bps};
+
+    assert_eq!(split_bps(999, 0), (0, 999));
+    assert_eq!(split_bps(999, MAX_FEE_BPS), (999, 0));
+    assert_eq!(split_bps(999, 5_000), (499, 500));
+    assert_eq!(split_bps(1, 9_999), (0, 1));
+    assert_eq!(split_bps(-999, 5_000), (-499, -500));
+    assert_eq!(bps_share(0, 1_234), 0);
+}
+
+#[test]
+#[should_panic(expected = "Amount overflow")]
+fn test_bps_split_overflow_rejected() {
+    crate::rounding::bps_share(i128::MAX, 2);
+}
+
+#[test]
+fn test_fee_residue_paid_to_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let recipient = Address::generate(&env);
+    client.set_fee_config(&333, &recipient);
+    client.set_payment_mode(&1, &PaymentMode::Direct);
+    client.approve_renewal(&1, &1, &1000, &100);
+
+    // 3.33% of 999 is 33.2667; the fraction goes to the merchant
+    assert!(client.renew(&owner, &1, &1, &999, &3, &0, &true));
+    let data = client.get_sub(&1);
+    let token = token::Client::new(&env, &data.token);
+    assert_eq!(token.balance(&recipient), 33);
+    assert_eq!(token.balance(&data.merchant), 966);
+    assert_eq!(token.balance(&client.address), 0);
+    assert_eq!(client.get_sub_economics(&1).protocol_fees, 33);
+}
+
+#[test]
+fn test_prepay_discount_rounds_for_merchant() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    prepay_sub(&env, &client, 10_000);
+    client.set_prepay_discount(&client.get_sub(&1).merchant, &333);
+
+    // 3.33% of 3 × 333 is 33.2667; the discount rounds down
+    assert_eq!(client.prepay_cycles(&1, &3, &333), 966);
+    assert_eq!(
+        event_field::<i128>(&env, &client, "cycles_prepaid", "discount"),
+        33
+    );
+    let data = client.get_sub(&1);
+    assert_eq!(
+        token::Client::new(&env, &data.token).balance(&data.merchant),
+        966
+    );
+}
+
+// ── Token amounts ─────────────────────────────────────────────────
+
+#[contract]
+struct MockToken18;
+
+#[contractimpl]
+impl MockToken18 {
+    pub fn decimals(_env: Env) -> u32 {
+        18
+    }
+}
 
-        subscription.next_payment_time += 30 * 24 * 60 * 60;
+#[test]
+fn test_token_decimals_bound_amounts() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let stellar = env
+        .register_stellar_asset_contract_v2(Address::generate(&env))
+        .address();
+    let wide = env.register(MockToken18, ());
 
-        env.storage().instance().set(&key, &subscription);
+    assert_eq!(client.get_token_decimals(&stellar), 7);
+    assert_eq!(
+        client.get_max_amount(&stellar),
+        MAX_WHOLE_TOKENS * 10i128.pow(7)
+    );
+    assert_eq!(client.get_token_decimals(&wide), 18);
+    assert_eq!(
+        client.get_max_amount(&wide),
+        MAX_WHOLE_TOKENS * 10i128.pow(18)
+    );
+}
+
+#[test]
+#[should_panic(expected = "Amount out of range for token")]
+fn test_price_scaled_for_wrong_decimals_rejected() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+
+    // 10 tokens at 18 decimals, on a 7-decimal token
+    client.set_pricing(&1, &(10 * 10i128.pow(18)), &1);
+}
+
+#[test]
+fn test_approval_cap_bounded_by_token() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+    let max = client.get_max_amount(&client.get_sub(&1).token);
+
+    client.approve_renewal(&1, &1, &max, &100);
+    assert!(client
+        .try_approve_renewal(&1, &2, &(max + 1), &100)
+        .is_err());
+    assert_eq!(client.get_approval(&1, &2), None);
+}
+
+#[test]
+fn test_template_price_in_wide_token_accepted() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let terms = SubscriptionTemplate {
+        merchant: Address::generate(&env),
+        token: env.register(MockToken18, ()),
+        pricing: Pricing {
+            unit_price: 10 * 10i128.pow(18),
+            quantity: 1,
+        },
+        interval_ledgers: 100,
+        window_ledgers: 10,
+        trial_ledgers: 0,
+        retry: RetryPolicy {
+            max_retries: 1,
+            cooldown_ledgers: 5,
+        },
+    };
 
-        env.events().publish(
-            (Symbol::new(&env, "subscription_renewed"),),
-            SubscriptionRenewed { subscriber },
+    let template_id = client.create_template(&terms);
+    assert_eq!(client.get_template(&template_id), Some(terms));
+}
+
+// ── Snapshot fixtures ─────────────────────────────────────────────
+
+use crate::fixtures::{Fixture, Scenario, COOLDOWN, MAX_RETRIES};
+
+#[test]
+fn test_fixtures_match_rebuilt_state() {
+    for scenario in Scenario::ALL {
+        let loaded = Fixture::load(scenario);
+        let built = Fixture::build(scenario);
+
+        assert_eq!(
+            loaded.env.ledger().sequence(),
+            built.env.ledger().sequence()
+        );
+        assert_eq!(
+            loaded.client().get_sub(&loaded.sub_id),
+            built.client().get_sub(&built.sub_id)
         );
+        for approval_id in 1..=3 {
+            assert_eq!(
+                loaded.client().get_approval(&loaded.sub_id, &approval_id),
+                built.client().get_approval(&built.sub_id, &approval_id)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_mid_retry_fixture_recovers_on_success() {
+    let fx = Fixture::load(Scenario::MidRetry);
+    let client = fx.client();
+    assert_eq!(
+        client.get_sub(&fx.sub_id).state,
+        SubscriptionState::Retrying
+    );
+
+    assert!(client.renew(
+        &fx.owner(),
+        &fx.sub_id,
+        &2,
+        &500,
+        &MAX_RETRIES,
+        &COOLDOWN,
+        &true
+    ));
+    let data = client.get_sub(&fx.sub_id);
+    assert_eq!(data.state, SubscriptionState::Active);
+    assert_eq!(data.failure_count, 0);
+}
+
+#[test]
+fn test_near_cap_fixture_fails_on_next_failure() {
+    let fx = Fixture::load(Scenario::NearCap);
+    let client = fx.client();
+    assert_eq!(client.get_sub(&fx.sub_id).failure_count, MAX_RETRIES);
+
+    assert!(!client.renew(
+        &fx.owner(),
+        &fx.sub_id,
+        &3,
+        &500,
+        &MAX_RETRIES,
+        &COOLDOWN,
+        &false
+    ));
+    assert_eq!(client.get_sub(&fx.sub_id).state, SubscriptionState::Failed);
+}
+
+#[test]
+#[should_panic(expected = "Invalid or expired approval")]
+fn test_expired_approval_fixture_rejects_renewal() {
+    let fx = Fixture::load(Scenario::ExpiredApproval);
+    fx.client().renew(
+        &fx.owner(),
+        &fx.sub_id,
+        &1,
+        &500,
+        &MAX_RETRIES,
+        &COOLDOWN,
+        &true,
+    );
+}
+
+// ── Golden events ─────────────────────────────────────────────────
+
+use crate::golden::Flow;
+
+#[test]
+fn test_golden_events() {
+    for flow in Flow::ALL {
+        flow.check();
     }
+}
 
-    pub fn get_subscription(env: Env, subscriber: Address) -> Subscription {
-        env.storage()
-            .instance()
-            .get(&DataKey::Subscription(subscriber))
-            .unwrap()
+#[test]
+fn test_golden_events_are_reproducible() {
+    for flow in Flow::ALL {
+        assert_eq!(flow.record(), flow.record());
     }
 }
 
-#[cfg(test)]
-mod test;
\ No newline at end of file
+// ── Differential testing ──────────────────────────────────────────
+
+use crate::differential::{self, Op};
+
+const DIFF_SEEDS: u64 = 16;
+const DIFF_STEPS: usize = 60;
+
+#[test]
+fn test_differential_harness_is_self_consistent() {
+    for seed in 1..=DIFF_SEEDS {
+        let (env, current, previous, actors) = differential::setup(None);
+        let ops = differential::random_ops(seed, DIFF_STEPS);
+        if let Err(divergence) = differential::run(&env, &current, &previous, &actors, &ops) {
+            panic!("seed {seed}: {divergence}");
+        }
+    }
+}
+
+#[test]
+fn test_differential_detects_divergence() {
+    let (env, current, previous, actors) = differential::setup(None);
+    SubscriptionRenewalContractClient::new(&env, &previous).set_paused(&true);
+
+    let ops = [
+        Op::InitSub { sub_id: 1 },
+        Op::Approve {
+            sub_id: 1,
+            approval_id: 1,
+            max_spend: 1000,
+            expires_at: 100,
+        },
+        Op::Renew {
+            sub_id: 1,
+            approval_id: 1,
+            amount: 500,
+            max_retries: 3,
+            cooldown: 10,
+            succeed: true,
+            by_executor: false,
+        },
+    ];
+    let err = differential::run(&env, &current, &previous, &actors, &ops).unwrap_err();
+    assert!(err.starts_with("step 0"));
+}
+
+/// Compares against the released wasm named by `SYNCRO_PREVIOUS_WASM`;
+/// a no-op when the variable is unset.
+#[test]
+fn test_differential_against_previous_release() {
+    let Some(path) = std::env::var_os("SYNCRO_PREVIOUS_WASM") else {
+        return;
+    };
+    let wasm = std::fs::read(path).expect("failed to read SYNCRO_PREVIOUS_WASM");
+
+    for seed in 1..=DIFF_SEEDS {
+        let (env, current, previous, actors) = differential::setup(Some(&wasm));
+        let ops = differential::random_ops(seed, DIFF_STEPS);
+        if let Err(divergence) = differential::run(&env, &current, &previous, &actors, &ops) {
+            panic!("seed {seed}: {divergence}");
+        }
+    }
+}
+
+// ── Invariants ────────────────────────────────────────────────────
+
+#[test]
+fn test_v0_subscription_upgraded_on_read() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+    let current = client.get_sub(&1);
+
+    // Rewrite the entry in the pre-versioning layout
+    env.as_contract(&client.address, || {
+        let old = SubscriptionDataV0 {
+            owner: current.owner.clone(),
+            merchant: current.merchant.clone(),
+            token: current.token.clone(),
+            state: current.state,
+            failure_count: 0,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&1u64, &old);
+    });
+    assert_eq!(client.get_sub(&1), current);
+
+    // The next write persists the current layout
+    client.approve_renewal(&1, &1, &1000, &100);
+    client.renew(&owner, &1, &1, &500, &0, &0, &true);
+    env.as_contract(&client.address, || {
+        let stored: SubscriptionData = env.storage().persistent().get(&1u64).unwrap();
+        assert_eq!(stored.version, SUBSCRIPTION_DATA_VERSION);
+        assert_eq!(stored.renewal_seq, 1);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Invariant violated: subscription stored in an old layout")]
+fn test_invariant_current_layout() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    new_sub(&env, &client, 1);
+
+    let mut data = client.get_sub(&1);
+    data.version = 0;
+    env.as_contract(&client.address, || {
+        env.storage().persistent().set(&1u64, &data);
+        crate::invariants::check_sub(&env, 1, None);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Invariant violated: active subscription has failures")]
+fn test_invariant_active_without_failures() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+
+    env.as_contract(&client.address, || {
+        let corrupt = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            merchant: owner.clone(),
+            token: owner.clone(),
+            owner,
+            state: SubscriptionState::Active,
+            failure_count: 1,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&1u64, &corrupt);
+        crate::invariants::check_sub(&env, 1, None);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Invariant violated: subscription left FAILED state")]
+fn test_invariant_failed_is_terminal() {
+    let env = Env::default();
+    let (client, _) = setup(&env);
+    let owner = new_sub(&env, &client, 1);
+
+    env.as_contract(&client.address, || {
+        let failed = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            owner: owner.clone(),
+            merchant: owner.clone(),
+            token: owner.clone(),
+            state: SubscriptionState::Failed,
+            failure_count: 2,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        let revived = SubscriptionData {
+            version: SUBSCRIPTION_DATA_VERSION,
+            merchant: owner.clone(),
+            token: owner.clone(),
+            owner,
+            state: SubscriptionState::Retrying,
+            failure_count: 2,
+            last_attempt_ledger: 0,
+            renewal_seq: 0,
+        };
+        env.storage().persistent().set(&1u64, &revived);
+        crate::invariants::check_sub(&env, 1, Some(&failed));
+    });
+}
@@ -0,0 +1,1012 @@
+//! Types shared by the SYNCRO contracts and their off-chain clients.
+//!
+//! Subscription storage types, the typed error enum and every event the
+//! renewal contract publishes live here, so the keeper, indexer, CLI and SDK
+//! decode contract data from one definition. The crate is `no_std` and
+//! builds for wasm as well as natively.
+//!
+//! The `serde` feature (native builds only) derives `Serialize` on every
+//! type, with addresses as strkeys, symbols as strings and bytes as hex.
+//! `Deserialize` is derived only where no field is a host object: those
+//! need an `Env` to rebuild, so decode them from XDR instead.
+
+#![no_std]
+
+use soroban_sdk::{
+    contracterror, contractevent, contracttype, Address, Bytes, BytesN, Symbol, Vec,
+};
+
+#[cfg(feature = "serde")]
+mod ser;
+
+/// Layout version of `SubscriptionData` written by the renewal contract.
+pub const SUBSCRIPTION_DATA_VERSION: u32 = 1;
+
+/// Typed errors surfaced to callers.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Error {
+    /// The configured compliance contract rejected the renewal.
+    ComplianceRejected = 1,
+    /// Processing the renewal would exceed a configured volume limit.
+    VolumeLimitExceeded = 2,
+    /// The owner's wallet can't cover the part of the charge not paid from
+    /// credit.
+    InsufficientBalance = 3,
+    /// The owner has no balance entry (trustline, for Stellar assets) in the
+    /// subscription's token.
+    TrustlineMissing = 4,
+    /// The subscription used up its merchant's paused-time allowance for
+    /// the current window.
+    PauseQuotaExhausted = 5,
+    /// The owner already has as many of the merchant's subscriptions paused
+    /// as it allows.
+    TooManyPauses = 6,
+    /// The subscription's billing starts at a later ledger.
+    NotStarted = 7,
+    /// The owner already has as many active subscriptions with this
+    /// merchant as it allows.
+    TooManySubscriptions = 8,
+    /// The merchant's renewals fail too often and it didn't re-verify its
+    /// billing setup in time.
+    MerchantSuspended = 9,
+}
+
+impl Error {
+    /// Every error, in code order.
+    pub const ALL: [Error; 9] = [
+        Error::ComplianceRejected,
+        Error::VolumeLimitExceeded,
+        Error::InsufficientBalance,
+        Error::TrustlineMissing,
+        Error::PauseQuotaExhausted,
+        Error::TooManyPauses,
+        Error::NotStarted,
+        Error::TooManySubscriptions,
+        Error::MerchantSuspended,
+    ];
+
+    /// The error reported as `Error(Contract, #code)`, if known.
+    pub fn from_code(code: u32) -> Option<Error> {
+        Self::ALL.into_iter().find(|e| *e as u32 == code)
+    }
+
+    /// Stable identifier, safe to key translations on.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            Error::ComplianceRejected => "compliance_rejected",
+            Error::VolumeLimitExceeded => "volume_limit_exceeded",
+            Error::InsufficientBalance => "insufficient_balance",
+            Error::TrustlineMissing => "trustline_missing",
+            Error::PauseQuotaExhausted => "pause_quota_exhausted",
+            Error::TooManyPauses => "too_many_pauses",
+            Error::NotStarted => "not_started",
+            Error::TooManySubscriptions => "too_many_subscriptions",
+            Error::MerchantSuspended => "merchant_suspended",
+        }
+    }
+
+    /// Short English description for display.
+    pub fn message(self) -> &'static str {
+        match self {
+            Error::ComplianceRejected => "Payment blocked by compliance check",
+            Error::VolumeLimitExceeded => "Payment volume limit exceeded",
+            Error::InsufficientBalance => "Insufficient balance",
+            Error::TrustlineMissing => "No trustline for the subscription token",
+            Error::PauseQuotaExhausted => "Pause allowance used up for this year",
+            Error::TooManyPauses => "Too many subscriptions paused with this merchant",
+            Error::NotStarted => "Subscription billing has not started yet",
+            Error::TooManySubscriptions => "Already subscribed to this merchant",
+            Error::MerchantSuspended => "Merchant billing suspended until it re-verifies",
+        }
+    }
+}
+
+/// Why `renew` rejected an approval; the `reason` of `ApprovalRejected`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ApprovalRejection {
+    Expired = 1,
+    Used = 2,
+    AmountExceeded = 3,
+    NotFound = 4,
+    /// The subscription's merchant or token changed since approval.
+    TermsChanged = 5,
+}
+
+impl ApprovalRejection {
+    /// Every reason, in code order.
+    pub const ALL: [ApprovalRejection; 5] = [
+        ApprovalRejection::Expired,
+        ApprovalRejection::Used,
+        ApprovalRejection::AmountExceeded,
+        ApprovalRejection::NotFound,
+        ApprovalRejection::TermsChanged,
+    ];
+
+    /// The reason for an `ApprovalRejected.reason` code, if known.
+    pub fn from_code(code: u32) -> Option<ApprovalRejection> {
+        Self::ALL.into_iter().find(|r| *r as u32 == code)
+    }
+
+    /// Stable identifier, safe to key translations on.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            ApprovalRejection::Expired => "approval_expired",
+            ApprovalRejection::Used => "approval_used",
+            ApprovalRejection::AmountExceeded => "approval_amount_exceeded",
+            ApprovalRejection::NotFound => "approval_not_found",
+            ApprovalRejection::TermsChanged => "approval_terms_changed",
+        }
+    }
+
+    /// Short English description for display.
+    pub fn message(self) -> &'static str {
+        match self {
+            ApprovalRejection::Expired => "Approval expired",
+            ApprovalRejection::Used => "Approval already used",
+            ApprovalRejection::AmountExceeded => "Amount exceeds approved maximum",
+            ApprovalRejection::NotFound => "Approval not found",
+            ApprovalRejection::TermsChanged => "Merchant or token changed since approval",
+        }
+    }
+}
+
+/// Represents the current state of a subscription
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscriptionState {
+    Active,
+    Retrying,
+    Failed,
+    /// Offered by the merchant; becomes Active once the owner accepts.
+    PendingAcceptance,
+    /// Renewed its `max_cycles` times; it is never charged again.
+    Completed,
+}
+
+/// Renewal approval bound to subscription, amount, and expiration
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RenewalApproval {
+    pub sub_id: u64,
+    /// Payee the owner approved; the approval is void if it changes.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    /// Token the owner approved; the approval is void if it changes.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub max_spend: i128,
+    pub expires_at: u32,
+    pub used: bool,
+}
+
+/// How renewals collect the part of a charge not paid from credit.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PaymentMode {
+    /// The balance is checked; the caller settles the payment itself.
+    #[default]
+    External,
+    /// SEP-41 `transfer` from the owner, who authorizes each renewal.
+    Direct,
+    /// `transfer_from` against an allowance granted to the contract.
+    Allowance,
+    /// Only credit and prepaid cycles held by the contract.
+    Escrow,
+}
+
+/// Storage a `TtlExtended` event refers to.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TtlKeyKind {
+    /// Contract instance storage; `id` is 0.
+    Instance,
+    /// A subscription record; `id` is its subscription id.
+    Subscription,
+    /// A subscription's renewal schedule.
+    Schedule,
+}
+
+/// Core subscription data stored on-chain
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscriptionData {
+    /// Storage layout version; see `SUBSCRIPTION_DATA_VERSION`.
+    pub version: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub state: SubscriptionState,
+    pub failure_count: u32,
+    pub last_attempt_ledger: u32,
+    /// Number of successful renewals; gap-free ordering for receipts.
+    pub renewal_seq: u64,
+}
+
+/// A merchant's registered webhook signing key.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WebhookKey {
+    /// SHA-256 of the signing secret; the secret itself stays off-chain.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::bytes_n"))]
+    pub key_hash: BytesN<32>,
+    /// Starts at 1 and increases with each rotation. Deliveries carry the
+    /// version they were signed with.
+    pub version: u32,
+    /// Ledger the key was registered at.
+    pub set_at: u32,
+}
+
+/// Where a subscription's billing cycle stands for a renewal now.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CycleStatus {
+    /// No schedule; renewals are billable at any time.
+    Unscheduled,
+    /// Before `next_due`, and the caller can't renew early.
+    NotDue,
+    /// Before `next_due`, admitted as a discounted early renewal.
+    Early,
+    /// Due and inside the renewal window.
+    Due,
+    /// Past the renewal window: renewing marks the cycle missed instead of
+    /// charging it.
+    Missed,
+}
+
+/// What `simulate_renew` found for each check `renew` makes, so every
+/// client reports the same reasons a renewal would be rejected. `ok` is
+/// true when none of them would stop it.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenewDiagnostics {
+    pub protocol_paused: bool,
+    /// Paused by its owners.
+    pub sub_paused: bool,
+    /// Under a compliance freeze.
+    pub frozen: bool,
+    /// Ledger billing starts at, while it is still ahead.
+    pub starts_at: Option<u32>,
+    /// The merchant missed its re-verification deadline.
+    pub merchant_suspended: bool,
+    pub state: SubscriptionState,
+    /// The caller is the owner, the executor or an active keeper.
+    pub caller_allowed: bool,
+    pub cycle: CycleStatus,
+    /// Ledgers until a retry is allowed; 0 outside a cooldown. The owner
+    /// may retry once per cycle before it ends.
+    pub cooldown_remaining: u32,
+    /// Amount the renewal would charge, after any early discount and with
+    /// overage; 0 when a prepaid cycle covers it.
+    pub charge: i128,
+    /// `ApprovalRejection` code the approval would be rejected with; `None`
+    /// if it would be accepted or no approval is needed.
+    pub approval_rejection: Option<u32>,
+    /// The approval's `max_spend` less `charge`; `None` without one.
+    pub approval_headroom: Option<i128>,
+    /// Room left under the tighter of the token and protocol volume limits;
+    /// `None` if neither is set.
+    pub volume_headroom: Option<i128>,
+    /// Credit and the owner's wallet cover `charge`.
+    pub balance_ok: bool,
+    pub ok: bool,
+}
+
+/// Events for subscription renewal tracking
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RenewalSuccess {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub renewal_seq: u64,
+    pub unit_price: i128,
+    pub quantity: u32,
+    pub discount: i128,
+    pub overage_units: u64,
+    pub overage: i128,
+    /// Amount charged: `unit_price * quantity - discount + overage`.
+    pub charged: i128,
+    /// Part of `charged` paid from account credit rather than the wallet.
+    pub credit_applied: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenewalFailed {
+    pub sub_id: u64,
+    pub failure_count: u32,
+    pub ledger: u32,
+    /// First ledger the cooldown allows a retry at; 0 if the subscription
+    /// has FAILED and won't be retried.
+    pub next_attempt_after: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateTransition {
+    pub sub_id: u64,
+    pub new_state: SubscriptionState,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CycleMissed {
+    pub sub_id: u64,
+    pub due: u32,
+    pub missed_cycles: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CyclesPrepaid {
+    pub sub_id: u64,
+    pub cycles: u32,
+    pub charged: i128,
+    pub discount: i128,
+    pub prepaid_cycles: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrepaidCycleUsed {
+    pub sub_id: u64,
+    pub due: u32,
+    pub prepaid_cycles: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageThresholdReached {
+    pub sub_id: u64,
+    pub threshold_pct: u32,
+    pub used: u64,
+    pub budget: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PauseToggled {
+    pub paused: bool,
+}
+
+/// The admin froze or unfroze every token movement out of the contract.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WithdrawalsFrozen {
+    pub frozen: bool,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApprovalCreated {
+    pub sub_id: u64,
+    pub approval_id: u64,
+    pub max_spend: i128,
+    pub expires_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ApprovalPending {
+    pub sub_id: u64,
+    pub approval_id: u64,
+    pub max_spend: i128,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub guardian: Address,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApprovalRejected {
+    pub sub_id: u64,
+    pub approval_id: u64,
+    /// An `ApprovalRejection` code.
+    pub reason: u32,
+}
+
+/// A failed renewal handed its approval back unspent.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApprovalReleased {
+    pub sub_id: u64,
+    pub approval_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExecutorAssigned {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub executor: Address,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaymentModeSet {
+    #[topic]
+    pub sub_id: u64,
+    pub mode: PaymentMode,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutorRemoved {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscriptionFrozen {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::symbol"))]
+    pub reason: Symbol,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriptionUnfrozen {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JointOwnersSet {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::addresses"))]
+    pub owners: Vec<Address>,
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscriptionPaused {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub by: Address,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriptionResumed {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TemplateCreated {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    pub template_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscribedFromTemplate {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub sub_id: u64,
+    pub template_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscriptionOffered {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    pub expires_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfferAccepted {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfferDeclined {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlanMigrationScheduled {
+    #[topic]
+    pub sub_id: u64,
+    pub old_template_id: u64,
+    pub new_template_id: u64,
+    pub effective_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PriceChangeScheduled {
+    pub sub_id: u64,
+    pub unit_price: i128,
+    pub quantity: u32,
+    pub effective_at: u32,
+}
+
+/// Advance notice of a future-dated change to a subscription. `kind`
+/// names the change (e.g. `price`); `new_amount` is the per-cycle amount
+/// once it applies.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UpcomingChange {
+    #[topic]
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::symbol"))]
+    pub kind: Symbol,
+    pub effective_at: u32,
+    pub new_amount: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfferExpired {
+    pub sub_id: u64,
+    pub expired_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PendingApprovalExpired {
+    pub sub_id: u64,
+    pub approval_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RecoveryRequested {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub new_owner: Address,
+    pub executable_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoveryCancelled {
+    pub sub_id: u64,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnerRecovered {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub old_owner: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub new_owner: Address,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RescueProposed {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub to: Address,
+    pub amount: i128,
+    pub executable_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TokensRescued {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CreditGranted {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub granter: Address,
+    pub amount: i128,
+    pub balance: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Refunded {
+    pub sub_id: u64,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub amount: i128,
+    pub to_credit: bool,
+}
+
+/// The protocol fee on a charge was paid to the fee recipient, or held
+/// as its claimable balance when it couldn't be delivered.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProtocolFeePaid {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub recipient: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+}
+
+/// A payout reached the merchant.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PayoutSent {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+}
+
+/// The merchant, or the fee recipient or cranker in `merchant`, couldn't
+/// receive a payout; it was added to their claimable balance
+/// (`claimable`, after this payout).
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PayoutDeferred {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+    pub claimable: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PayoutClaimed {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CreditApplied {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+    pub balance: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PromoCreditGranted {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub amount: i128,
+    pub balance: i128,
+    pub expires_at: u32,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PromoCreditApplied {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+    pub balance: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PromoCreditExpired {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub amount: i128,
+}
+
+/// A permissionless crank renewed `sub_id` for the cycle due at `due`;
+/// `bounty` of the renewal amount goes to the cranker instead of the
+/// merchant.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CrankBountyPaid {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub cranker: Address,
+    pub sub_id: u64,
+    pub due: u32,
+    pub bounty: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KeeperRegistered {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub keeper: Address,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KeeperSuspended {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub keeper: Address,
+    pub suspended: bool,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub by: Address,
+}
+
+/// A keeper earned `amount` of the keeper fee for renewing `sub_id`; it is
+/// held for the keeper until claimed.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KeeperRewarded {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub keeper: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+    pub unclaimed: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KeeperRewardsClaimed {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub keeper: Address,
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub token: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FeeConfigUpdated {
+    pub percentage: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub recipient: Address,
+}
+
+/// Uniform audit record for every privileged call. `old_value` and
+/// `new_value` are the XDR-encoded `ScVal`s of the setting before and after.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AdminAction {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::symbol"))]
+    pub action: Symbol,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub admin: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::bytes"))]
+    pub old_value: Bytes,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::bytes"))]
+    pub new_value: Bytes,
+}
+
+/// The contract extended an entry's TTL; it stays live until ledger
+/// `new_live_until`.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TtlExtended {
+    pub key_kind: TtlKeyKind,
+    pub id: u64,
+    pub new_live_until: u32,
+}
+
+/// A merchant registered or rotated its webhook signing key.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WebhookKeySet {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    pub version: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::bytes_n"))]
+    pub key_hash: BytesN<32>,
+}
+
+/// The owner retried during the cooldown, using the one early retry each
+/// cycle allows.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CooldownBypassed {
+    pub sub_id: u64,
+}
+
+/// A merchant added to a subscription's retry subsidy.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetrySubsidyFunded {
+    pub sub_id: u64,
+    pub amount: i128,
+    pub per_retry: i128,
+    pub remaining: i128,
+}
+
+/// A keeper was reimbursed for a retry out of the merchant's subsidy.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RetrySubsidyPaid {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub keeper: Address,
+    pub sub_id: u64,
+    pub amount: i128,
+    pub remaining: i128,
+}
+
+/// A subscription renewed its last cycle under its term limit, or was paid
+/// off, and is now `Completed` after `cycles` renewals.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriptionCompleted {
+    pub sub_id: u64,
+    pub cycles: u32,
+}
+
+/// A term-limited subscription was put on an installment plan.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstallmentPlanSet {
+    pub sub_id: u64,
+    pub total_price: i128,
+    pub cycles: u32,
+}
+
+/// The owner paid off what remained of an installment plan.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstallmentsPaidOff {
+    pub sub_id: u64,
+    pub amount: i128,
+}
+
+/// A merchant started or stopped pricing renewals through a pricing
+/// contract.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PricingContractSet {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::ser::optional_address")
+    )]
+    pub contract: Option<Address>,
+}
+
+/// What a dynamically priced subscription's renewal due at `due` is quoted
+/// to charge, published ahead of time so the owner can still revoke.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChargePreview {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub sub_id: u64,
+    pub due: u32,
+    pub amount: i128,
+}
+
+/// A merchant restricted sign-ups to its allowlist, or lifted the
+/// restriction.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AllowlistModeSet {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    pub enabled: bool,
+}
+
+/// A merchant added `owner` to its allowlist, or removed it.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AllowlistUpdated {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub owner: Address,
+    pub allowed: bool,
+}
+
+/// The merchant's renewals failed often enough that it must re-verify its
+/// billing setup by `reverify_by`, or its renewals stop.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MerchantReverifyRequired {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+    pub failure_bps: u32,
+    pub reverify_by: u32,
+}
+
+/// A merchant re-verified its billing setup, starting its health record
+/// over.
+#[contractevent]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MerchantReverified {
+    #[topic]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::ser::address"))]
+    pub merchant: Address,
+}
+
+#[cfg(test)]
+mod test;
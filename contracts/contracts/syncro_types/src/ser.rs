@@ -0,0 +1,59 @@
+//! `serialize_with` helpers for the host types in shared structs.
+
+extern crate std;
+
+use core::fmt;
+use serde::{Serialize, Serializer};
+use soroban_sdk::{Address, Bytes, BytesN, Symbol, Vec};
+use std::string::ToString;
+
+/// Strkey: `G…` for accounts, `C…` for contracts.
+pub fn address<S: Serializer>(address: &Address, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&address.to_string())
+}
+
+pub fn optional_address<S: Serializer>(
+    address: &Option<Address>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    address.clone().map(Strkey).serialize(serializer)
+}
+
+pub fn addresses<S: Serializer>(
+    addresses: &Vec<Address>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(addresses.iter().map(Strkey))
+}
+
+pub fn symbol<S: Serializer>(symbol: &Symbol, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&symbol.to_string())
+}
+
+/// Lowercase hex.
+pub fn bytes<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&Hex(bytes))
+}
+
+pub fn bytes_n<S: Serializer, const N: usize>(
+    bytes: &BytesN<N>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&Hex(bytes.as_ref()))
+}
+
+struct Strkey(Address);
+
+impl Serialize for Strkey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        address(&self.0, serializer)
+    }
+}
+
+struct Hex<'a>(&'a Bytes);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
@@ -0,0 +1,97 @@
+use super::*;
+
+#[test]
+fn test_error_catalog_round_trips() {
+    for (i, error) in Error::ALL.into_iter().enumerate() {
+        assert_eq!(error as u32, i as u32 + 1);
+        assert_eq!(Error::from_code(error as u32), Some(error));
+        assert!(!error.message().is_empty());
+    }
+    assert_eq!(Error::from_code(0), None);
+    assert_eq!(Error::from_code(Error::ALL.len() as u32 + 1), None);
+}
+
+#[test]
+fn test_approval_rejection_catalog_round_trips() {
+    for (i, reason) in ApprovalRejection::ALL.into_iter().enumerate() {
+        assert_eq!(reason as u32, i as u32 + 1);
+        assert_eq!(ApprovalRejection::from_code(reason as u32), Some(reason));
+    }
+    assert_eq!(
+        ApprovalRejection::from_code(1).map(ApprovalRejection::message),
+        Some("Approval expired")
+    );
+}
+
+#[test]
+fn test_identifiers_are_unique() {
+    let ids = Error::ALL.into_iter().map(Error::identifier).chain(
+        ApprovalRejection::ALL
+            .into_iter()
+            .map(ApprovalRejection::identifier),
+    );
+    for (i, a) in ids.clone().enumerate() {
+        assert_eq!(ids.clone().filter(|b| *b == a).count(), 1, "{a} at {i}");
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_subscription_data_serializes_to_json() {
+    use soroban_sdk::{Env, String};
+
+    let env = Env::default();
+    let account = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF";
+    let contract = "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM";
+    let account_addr = Address::from_string(&String::from_str(&env, account));
+    let contract_addr = Address::from_string(&String::from_str(&env, contract));
+    let data = SubscriptionData {
+        version: SUBSCRIPTION_DATA_VERSION,
+        owner: account_addr.clone(),
+        merchant: account_addr,
+        token: contract_addr,
+        state: SubscriptionState::Retrying,
+        failure_count: 1,
+        last_attempt_ledger: 10,
+        renewal_seq: 3,
+    };
+
+    assert_eq!(
+        serde_json::to_value(&data).unwrap(),
+        serde_json::json!({
+            "version": 1,
+            "owner": account,
+            "merchant": account,
+            "token": contract,
+            "state": "Retrying",
+            "failure_count": 1,
+            "last_attempt_ledger": 10,
+            "renewal_seq": 3,
+        })
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_event_fields_serialize_to_json() {
+    use soroban_sdk::{Env, Symbol};
+
+    let env = Env::default();
+    let event = AdminAction {
+        action: Symbol::new(&env, "pause"),
+        admin: Address::from_string(&soroban_sdk::String::from_str(
+            &env,
+            "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF",
+        )),
+        old_value: Bytes::from_array(&env, &[0x00, 0xab]),
+        new_value: Bytes::new(&env),
+    };
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["action"], "pause");
+    assert_eq!(json["old_value"], "00ab");
+    assert_eq!(json["new_value"], "");
+
+    let transition: StateTransition =
+        serde_json::from_str(r#"{"sub_id":7,"new_state":"Failed"}"#).unwrap();
+    assert_eq!(transition.new_state, SubscriptionState::Failed);
+}
@@ -1,4 +1,7 @@
-use soroban_sdk::{testutils::Address as _, Address, BytesN, Env, String};
+use soroban_sdk::{
+    testutils::{Address as _, Events},
+    Address, BytesN, Env, IntoVal, Map, String, Symbol, Val,
+};
 use subscription_registry::{SubscriptionRegistry, SubscriptionRegistryClient};
 
 #[test]
@@ -127,11 +130,44 @@ fn test_cancel_subscription() {
         &1735689600u64,
     );
 
-    client.cancel_subscription(&subscription_id, &user);
+    client.cancel_subscription(&subscription_id, &user, &None);
 
     // Verify subscription is marked as inactive
     let metadata = client.get_subscription(&subscription_id).unwrap();
     assert!(!metadata.is_active);
+    assert_eq!(metadata.feedback_hash, None);
+}
+
+#[test]
+fn test_cancel_subscription_with_feedback_hash() {
+    // Test anchoring a hash of off-chain cancellation feedback
+    let env = Env::default();
+    let contract_id = env.register(SubscriptionRegistry, ());
+    let client = SubscriptionRegistryClient::new(&env, &contract_id);
+
+    let user = Address::generate(&env);
+    let subscription_id = client.create_subscription(
+        &user,
+        &String::from_str(&env, "netflix"),
+        &2592000u64,
+        &1599i128,
+        &1735689600u64,
+    );
+    let feedback_hash = BytesN::from_array(&env, &[7u8; 32]);
+
+    client.cancel_subscription(&subscription_id, &user, &Some(feedback_hash.clone()));
+
+    // Verify the hash is emitted and kept on the cancelled subscription
+    let (_, _, data) = env.events().all().last().unwrap();
+    let data: Map<Symbol, Val> = data.into_val(&env);
+    let emitted: Option<BytesN<32>> = data
+        .get(Symbol::new(&env, "feedback_hash"))
+        .unwrap()
+        .into_val(&env);
+    assert_eq!(emitted, Some(feedback_hash.clone()));
+    let metadata = client.get_subscription(&subscription_id).unwrap();
+    assert!(!metadata.is_active);
+    assert_eq!(metadata.feedback_hash, Some(feedback_hash));
 }
 
 #[test]
@@ -201,8 +237,8 @@ fn test_cancel_already_cancelled_subscription() {
         &1735689600u64,
     );
 
-    client.cancel_subscription(&subscription_id, &user);
-    client.cancel_subscription(&subscription_id, &user);
+    client.cancel_subscription(&subscription_id, &user, &None);
+    client.cancel_subscription(&subscription_id, &user, &None);
 }
 
 #[test]
@@ -222,7 +258,7 @@ fn test_update_cancelled_subscription() {
         &1735689600u64,
     );
 
-    client.cancel_subscription(&subscription_id, &user);
+    client.cancel_subscription(&subscription_id, &user, &None);
     client.update_subscription(
         &subscription_id,
         &user,
@@ -0,0 +1,170 @@
+//! End-to-end harness for the Synchro contracts.
+//!
+//! Unlike the `Env`-based unit tests, everything here goes through real
+//! transaction submission: contracts are deployed and invoked with the
+//! Stellar CLI against a live RPC endpoint (a quickstart container in
+//! `tests/`), so fees, auth signing and TTLs behave as they do on a network.
+
+mod quickstart;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub use quickstart::Quickstart;
+
+/// Passphrase of the quickstart `--local` network.
+pub const LOCAL_PASSPHRASE: &str = "Standalone Network ; February 2017";
+
+/// Wasm target used by soroban-sdk 23.
+pub const WASM_TARGET: &str = "wasm32v1-none";
+
+/// The contracts workspace root.
+pub fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("e2e lives inside the workspace")
+        .to_path_buf()
+}
+
+pub fn wasm_path(name: &str) -> PathBuf {
+    workspace_root()
+        .join("target")
+        .join(WASM_TARGET)
+        .join("release")
+        .join(format!("{name}.wasm"))
+}
+
+/// Build release wasm for the given cargo packages.
+pub fn build_wasm(packages: &[&str]) -> Result<(), String> {
+    let mut args = vec!["build", "--target", WASM_TARGET, "--release"];
+    for package in packages {
+        args.push("-p");
+        args.push(package);
+    }
+    run(&workspace_root(), "cargo", &args).map(|_| ())
+}
+
+/// Stellar CLI bound to one network.
+pub struct Cli {
+    rpc_url: String,
+    passphrase: String,
+}
+
+impl Cli {
+    pub fn new(rpc_url: impl Into<String>, passphrase: impl Into<String>) -> Self {
+        Self {
+            rpc_url: rpc_url.into(),
+            passphrase: passphrase.into(),
+        }
+    }
+
+    fn network_args(&self) -> [&str; 4] {
+        [
+            "--rpc-url",
+            &self.rpc_url,
+            "--network-passphrase",
+            &self.passphrase,
+        ]
+    }
+
+    fn stellar(&self, args: &[&str]) -> Result<String, String> {
+        run(&workspace_root(), "stellar", args)
+    }
+
+    /// Create a friendbot-funded identity and return its address.
+    pub fn funded_identity(&self, name: &str) -> Result<String, String> {
+        let mut args = vec!["keys", "generate", name, "--fund", "--overwrite"];
+        args.extend(self.network_args());
+        self.stellar(&args)?;
+        self.stellar(&["keys", "address", name])
+    }
+
+    pub fn deploy(&self, wasm: &Path, source: &str) -> Result<String, String> {
+        let wasm = wasm.display().to_string();
+        let mut args = vec![
+            "contract",
+            "deploy",
+            "--wasm",
+            &wasm,
+            "--source-account",
+            source,
+        ];
+        args.extend(self.network_args());
+        self.stellar(&args)
+    }
+
+    /// Submit `call` (function name followed by `--arg value` pairs) to a
+    /// contract, signed by `source`. Returns the CLI's JSON-ish output.
+    pub fn invoke(&self, id: &str, source: &str, call: &[&str]) -> Result<String, String> {
+        let mut args = vec!["contract", "invoke", "--id", id, "--source-account", source];
+        args.extend(self.network_args());
+        args.push("--");
+        args.extend(call);
+        self.stellar(&args)
+    }
+
+    /// Contract id of the native asset's Stellar Asset Contract.
+    pub fn native_asset(&self) -> Result<String, String> {
+        let mut args = vec!["contract", "id", "asset", "--asset", "native"];
+        args.extend(self.network_args());
+        self.stellar(&args)
+    }
+
+    /// Native balance (in stroops) of `address`.
+    pub fn native_balance(&self, address: &str, source: &str) -> Result<i128, String> {
+        let sac = self.native_asset()?;
+        let out = self.invoke(&sac, source, &["balance", "--id", address])?;
+        parse_i128(&out)
+    }
+
+    /// Extend the TTL of a contract's instance entry and return the new
+    /// live-until ledger.
+    pub fn extend_instance(&self, id: &str, source: &str, ledgers: u32) -> Result<u32, String> {
+        let ledgers = ledgers.to_string();
+        let mut args = vec![
+            "contract",
+            "extend",
+            "--id",
+            id,
+            "--ledgers-to-extend",
+            &ledgers,
+            "--durability",
+            "persistent",
+            "--ttl-ledger-only",
+            "--source-account",
+            source,
+        ];
+        args.extend(self.network_args());
+        let out = self.stellar(&args)?;
+        out.parse()
+            .map_err(|_| format!("unexpected extend output `{out}`"))
+    }
+}
+
+/// CLI output for i128 values is a quoted decimal string.
+pub fn parse_i128(out: &str) -> Result<i128, String> {
+    out.trim()
+        .trim_matches('"')
+        .parse()
+        .map_err(|_| format!("not an i128: `{out}`"))
+}
+
+pub(crate) fn run(cwd: &Path, program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| format!("failed to spawn `{program}`: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{program} {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,74 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::{run, workspace_root};
+
+const IMAGE: &str = "stellar/quickstart:latest";
+const READY_LINE: &str = "soroban rpc: up and ready";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A throwaway `stellar/quickstart --local` node, removed on drop.
+pub struct Quickstart {
+    id: String,
+    pub rpc_url: String,
+}
+
+impl Quickstart {
+    /// Start the container on a random host port and block until the RPC
+    /// server reports ready.
+    pub fn start() -> Result<Self, String> {
+        let root = workspace_root();
+        let id = run(
+            &root,
+            "docker",
+            &[
+                "run",
+                "-d",
+                "--rm",
+                "-p",
+                "127.0.0.1::8000",
+                IMAGE,
+                "--local",
+                "--enable-soroban-rpc",
+            ],
+        )?;
+        // From here on the guard owns the container, even if startup fails.
+        let mut node = Self {
+            id,
+            rpc_url: String::new(),
+        };
+
+        let mapping = run(&root, "docker", &["port", &node.id, "8000/tcp"])?;
+        node.rpc_url = format!("http://{}/rpc", parse_port_mapping(&mapping)?);
+
+        let started = Instant::now();
+        loop {
+            let logs = run(&root, "docker", &["logs", &node.id])?;
+            if logs.contains(READY_LINE) {
+                return Ok(node);
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err("quickstart did not become ready in time".into());
+            }
+            sleep(Duration::from_secs(2));
+        }
+    }
+}
+
+impl Drop for Quickstart {
+    fn drop(&mut self) {
+        let _ = run(&workspace_root(), "docker", &["rm", "-f", &self.id]);
+    }
+}
+
+/// `docker port` prints one `host:port` mapping per line; take the first.
+pub(crate) fn parse_port_mapping(out: &str) -> Result<String, String> {
+    out.lines()
+        .map(str::trim)
+        .find(|line| {
+            line.rsplit_once(':')
+                .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+        })
+        .map(str::to_string)
+        .ok_or_else(|| format!("unexpected `docker port` output `{out}`"))
+}
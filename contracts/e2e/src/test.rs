@@ -0,0 +1,28 @@
+use super::*;
+use crate::quickstart::parse_port_mapping;
+
+#[test]
+fn test_parse_i128() {
+    assert_eq!(parse_i128("\"10000000\""), Ok(10_000_000));
+    assert_eq!(parse_i128("-5\n"), Ok(-5));
+    assert!(parse_i128("true").is_err());
+}
+
+#[test]
+fn test_parse_port_mapping() {
+    assert_eq!(
+        parse_port_mapping("127.0.0.1:49153\n"),
+        Ok("127.0.0.1:49153".to_string())
+    );
+    assert_eq!(
+        parse_port_mapping("0.0.0.0:8000\n[::]:8000"),
+        Ok("0.0.0.0:8000".to_string())
+    );
+    assert!(parse_port_mapping("").is_err());
+}
+
+#[test]
+fn test_wasm_path() {
+    let path = wasm_path("subscription_renewal");
+    assert!(path.ends_with("target/wasm32v1-none/release/subscription_renewal.wasm"));
+}
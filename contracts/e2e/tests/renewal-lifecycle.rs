@@ -0,0 +1,143 @@
+//! Requires Docker and the `stellar` CLI on PATH. Run with
+//! `cargo test -p e2e -- --ignored`.
+
+use e2e::{build_wasm, wasm_path, Cli, Quickstart, LOCAL_PASSPHRASE};
+
+fn start_quickstart() -> (Quickstart, Cli) {
+    let node = Quickstart::start().expect("failed to start stellar/quickstart");
+    let cli = Cli::new(node.rpc_url.clone(), LOCAL_PASSPHRASE);
+    (node, cli)
+}
+
+/// Deploy and initialize the renewal contract; returns (contract id, owner address).
+fn deploy_renewal(cli: &Cli) -> (String, String) {
+    build_wasm(&["subscription_renewal"]).unwrap();
+
+    let admin = cli.funded_identity("e2e-admin").unwrap();
+    let owner = cli.funded_identity("e2e-owner").unwrap();
+    let merchant = cli.funded_identity("e2e-merchant").unwrap();
+    let token = cli.native_asset().unwrap();
+
+    let id = cli
+        .deploy(&wasm_path("subscription_renewal"), "e2e-admin")
+        .unwrap();
+    cli.invoke(&id, "e2e-admin", &["init", "--admin", &admin])
+        .unwrap();
+    cli.invoke(
+        &id,
+        "e2e-owner",
+        &[
+            "init_sub",
+            "--info",
+            &owner,
+            "--sub_id",
+            "1",
+            "--merchant",
+            &merchant,
+            "--token",
+            &token,
+        ],
+    )
+    .unwrap();
+    (id, owner)
+}
+
+fn approve(cli: &Cli, id: &str, approval_id: &str) {
+    cli.invoke(
+        id,
+        "e2e-owner",
+        &[
+            "approve_renewal",
+            "--sub_id",
+            "1",
+            "--approval_id",
+            approval_id,
+            "--max_spend",
+            "1000",
+            "--expires_at",
+            "1000000",
+        ],
+    )
+    .unwrap();
+}
+
+fn renew(
+    cli: &Cli,
+    id: &str,
+    owner: &str,
+    approval_id: &str,
+    succeed: bool,
+) -> Result<String, String> {
+    let mut call = vec![
+        "renew",
+        "--caller",
+        owner,
+        "--sub_id",
+        "1",
+        "--approval_id",
+        approval_id,
+        "--amount",
+        "500",
+        "--max_retries",
+        "0",
+        "--cooldown_ledgers",
+        "0",
+    ];
+    if succeed {
+        call.push("--succeed");
+    }
+    cli.invoke(id, "e2e-owner", &call)
+}
+
+#[test]
+#[ignore = "requires docker and the stellar CLI"]
+fn test_init_approve_renew_charges_fees() {
+    let (_node, cli) = start_quickstart();
+    let (id, owner) = deploy_renewal(&cli);
+
+    approve(&cli, &id, "1");
+    let before = cli.native_balance(&owner, "e2e-owner").unwrap();
+    assert_eq!(renew(&cli, &id, &owner, "1", true).unwrap(), "true");
+    let after = cli.native_balance(&owner, "e2e-owner").unwrap();
+
+    // The owner signed and paid for the renewal transaction.
+    assert!(after < before);
+
+    let sub = cli
+        .invoke(&id, "e2e-owner", &["get_sub", "--sub_id", "1"])
+        .unwrap();
+    assert!(sub.contains("\"Active\""));
+
+    // Single-use approval is rejected on-chain, not just in the host.
+    assert!(renew(&cli, &id, &owner, "1", true).is_err());
+}
+
+#[test]
+#[ignore = "requires docker and the stellar CLI"]
+fn test_failed_renewal_is_persisted() {
+    let (_node, cli) = start_quickstart();
+    let (id, owner) = deploy_renewal(&cli);
+
+    approve(&cli, &id, "1");
+    assert_eq!(renew(&cli, &id, &owner, "1", false).unwrap(), "false");
+
+    let sub = cli
+        .invoke(&id, "e2e-owner", &["get_sub", "--sub_id", "1"])
+        .unwrap();
+    assert!(sub.contains("\"Failed\""));
+
+    approve(&cli, &id, "2");
+    assert!(renew(&cli, &id, &owner, "2", true).is_err());
+}
+
+#[test]
+#[ignore = "requires docker and the stellar CLI"]
+fn test_instance_ttl_extension() {
+    let (_node, cli) = start_quickstart();
+    let (id, _) = deploy_renewal(&cli);
+
+    let first = cli.extend_instance(&id, "e2e-admin", 10_000).unwrap();
+    let second = cli.extend_instance(&id, "e2e-admin", 20_000).unwrap();
+    assert!(first >= 10_000);
+    assert!(second > first);
+}
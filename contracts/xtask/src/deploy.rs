@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use crate::manifest::Manifest;
+use crate::network::Profile;
+use crate::runner::Runner;
+
+/// Wasm target used by soroban-sdk 23.
+pub const WASM_TARGET: &str = "wasm32v1-none";
+
+/// Deployable contracts as (wasm/manifest name, cargo package).
+pub const CONTRACTS: &[(&str, &str)] = &[
+    ("subscription_registry", "subscription-registry"),
+    ("subscription_logging", "subscription_logging"),
+    ("subscription_renewal", "subscription_renewal"),
+    ("payment_channel", "payment_channel"),
+];
+
+/// Drives the build → deploy → init → wire sequence.
+pub struct Deployer<'a, R: Runner> {
+    runner: &'a mut R,
+    root: &'a Path,
+}
+
+impl<'a, R: Runner> Deployer<'a, R> {
+    pub fn new(runner: &'a mut R, root: &'a Path) -> Self {
+        Self { runner, root }
+    }
+
+    /// Build release wasm for every deployable contract.
+    pub fn build(&mut self) -> Result<(), String> {
+        let mut args = strings(&["build", "--target", WASM_TARGET, "--release"]);
+        for (_, package) in CONTRACTS {
+            args.push("-p".into());
+            args.push((*package).into());
+        }
+        self.runner.run("cargo", &args)?;
+        Ok(())
+    }
+
+    pub fn wasm_path(&self, name: &str) -> PathBuf {
+        self.root
+            .join("target")
+            .join(WASM_TARGET)
+            .join("release")
+            .join(format!("{name}.wasm"))
+    }
+
+    /// Build and deploy every contract, initialize the renewal contract with
+    /// `source` as admin, and point it at the logging contract.
+    pub fn deploy(&mut self, profile: &Profile, source: &str) -> Result<Manifest, String> {
+        self.build()?;
+
+        let admin = self
+            .runner
+            .run("stellar", &strings(&["keys", "address", source]))?;
+
+        let mut manifest = Manifest::new(profile.network, admin.clone());
+        for (name, _) in CONTRACTS {
+            let wasm = self.wasm_path(name).display().to_string();
+            let mut args = strings(&["contract", "deploy", "--wasm", &wasm]);
+            args.extend(network_args(profile, source));
+            let id = self.runner.run("stellar", &args)?;
+            validate_contract_id(name, &id)?;
+            manifest.contracts.push(((*name).into(), id));
+        }
+
+        let renewal = manifest.contract("subscription_renewal")?.to_string();
+        let logging = manifest.contract("subscription_logging")?.to_string();
+
+        self.invoke(profile, source, &renewal, &["init", "--admin", &admin])?;
+        self.invoke(
+            profile,
+            source,
+            &renewal,
+            &["set_logging_contract", "--address", &logging],
+        )?;
+
+        Ok(manifest)
+    }
+
+    fn invoke(
+        &mut self,
+        profile: &Profile,
+        source: &str,
+        contract_id: &str,
+        call: &[&str],
+    ) -> Result<String, String> {
+        let mut args = strings(&["contract", "invoke", "--id", contract_id]);
+        args.extend(network_args(profile, source));
+        args.push("--".into());
+        args.extend(strings(call));
+        self.runner.run("stellar", &args)
+    }
+}
+
+fn network_args(profile: &Profile, source: &str) -> Vec<String> {
+    strings(&[
+        "--source-account",
+        source,
+        "--rpc-url",
+        &profile.rpc_url,
+        "--network-passphrase",
+        profile.passphrase,
+    ])
+}
+
+/// Contract strkeys are 56 characters starting with `C`.
+pub fn validate_contract_id(name: &str, id: &str) -> Result<(), String> {
+    if id.len() == 56 && id.starts_with('C') && id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "deploy of {name} returned an invalid contract id `{id}`"
+        ))
+    }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| (*s).to_string()).collect()
+}
@@ -0,0 +1,202 @@
+//! Human-readable differences between two states of the contracts, e.g.
+//! before and after a migration or upgrade.
+//!
+//! A state is a ledger snapshot: a file written by `stellar snapshot
+//! create`, a full test snapshot (whose `ledger` field is one), or `live`,
+//! fetched from the network on the spot. Contract storage is flattened into
+//! one line per value, so a changed subscription shows up as the fields
+//! that changed rather than the whole record.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use soroban_ledger_snapshot::LedgerSnapshot;
+use stellar_xdr::curr::{LedgerEntryData, ScVal};
+
+use crate::network::Profile;
+use crate::runner::Runner;
+
+/// Where a state comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    File(PathBuf),
+    Live,
+}
+
+impl Source {
+    pub fn parse(arg: &str) -> Self {
+        match arg {
+            "live" => Source::Live,
+            path => Source::File(path.into()),
+        }
+    }
+}
+
+/// Flattened contract storage, keyed by (contract, section, entry). The
+/// sections are `config` (instance storage), `subscriptions` (records
+/// keyed by subscription id) and `storage` (everything else).
+pub type State = BTreeMap<(String, &'static str, String), String>;
+
+/// Snapshot the current state of `contract` with the Stellar CLI and
+/// return the path it was written to.
+pub fn fetch_live<R: Runner>(
+    runner: &mut R,
+    root: &Path,
+    profile: &Profile,
+    contract: &str,
+) -> Result<PathBuf, String> {
+    let dir = root.join("target").join("xtask");
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    let path = dir.join(format!("{}-{contract}.json", profile.network));
+
+    let args: Vec<String> = [
+        "snapshot",
+        "create",
+        "--address",
+        contract,
+        "--output",
+        "json",
+        "--out",
+        &path.display().to_string(),
+        "--rpc-url",
+        &profile.rpc_url,
+        "--network-passphrase",
+        profile.passphrase,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    runner.run("stellar", &args)?;
+    Ok(path)
+}
+
+/// Read a ledger snapshot, or the `ledger` of a full test snapshot.
+pub fn load(path: &Path) -> Result<LedgerSnapshot, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut json: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    if let Some(ledger) = json.get_mut("ledger") {
+        json = ledger.take();
+    }
+    serde_json::from_value(json)
+        .map_err(|e| format!("{}: not a ledger snapshot: {e}", path.display()))
+}
+
+/// Flatten the contract storage in `snapshot`, keeping only `contract`
+/// when given.
+pub fn state(snapshot: &LedgerSnapshot, contract: Option<&str>) -> State {
+    let mut state = State::new();
+    for (_, (entry, _)) in &snapshot.ledger_entries {
+        let LedgerEntryData::ContractData(data) = &entry.data else {
+            continue;
+        };
+        let address = data.contract.to_string();
+        if contract.is_some_and(|c| c != address) {
+            continue;
+        }
+
+        match (&data.key, &data.val) {
+            (ScVal::LedgerKeyContractInstance, ScVal::ContractInstance(instance)) => {
+                for item in instance.storage.iter().flat_map(|s| s.iter()) {
+                    insert(&mut state, &address, "config", render(&item.key), &item.val);
+                }
+            }
+            (ScVal::LedgerKeyNonce(_), _) => {}
+            (ScVal::U64(sub_id), val) => {
+                insert(
+                    &mut state,
+                    &address,
+                    "subscriptions",
+                    sub_id.to_string(),
+                    val,
+                );
+            }
+            (key, val) => insert(&mut state, &address, "storage", render(key), val),
+        }
+    }
+    state
+}
+
+/// Structs are split into one entry per field.
+fn insert(state: &mut State, contract: &str, section: &'static str, label: String, val: &ScVal) {
+    if let ScVal::Map(Some(fields)) = val {
+        if !fields.is_empty() && fields.iter().all(|f| matches!(f.key, ScVal::Symbol(_))) {
+            for field in fields.iter() {
+                let label = format!("{label}.{}", render(&field.key));
+                state.insert((contract.to_string(), section, label), render(&field.val));
+            }
+            return;
+        }
+    }
+    state.insert((contract.to_string(), section, label), render(val));
+}
+
+/// Report of what changed from `before` to `after`, grouped by contract
+/// and section: `+` added, `-` removed, `~` changed. Empty if nothing did.
+pub fn diff(before: &State, after: &State) -> String {
+    let mut keys: Vec<_> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut out = String::new();
+    let mut group = None;
+    for key in keys {
+        let line = match (before.get(key), after.get(key)) {
+            (Some(a), Some(b)) if a == b => continue,
+            (Some(a), Some(b)) => format!("  ~ {}: {a} -> {b}\n", key.2),
+            (None, Some(b)) => format!("  + {}: {b}\n", key.2),
+            (Some(a), None) => format!("  - {}: {a}\n", key.2),
+            (None, None) => unreachable!(),
+        };
+        if group != Some((&key.0, key.1)) {
+            group = Some((&key.0, key.1));
+            out.push_str(&format!("{} {}\n", key.0, key.1));
+        }
+        out.push_str(&line);
+    }
+    out
+}
+
+/// Compact rendering of a value: enum variants as `Name(args)`, structs as
+/// `{field: value}`, addresses as strkeys.
+pub fn render(val: &ScVal) -> String {
+    match val {
+        ScVal::Bool(b) => b.to_string(),
+        ScVal::Void => "()".into(),
+        ScVal::U32(n) => n.to_string(),
+        ScVal::I32(n) => n.to_string(),
+        ScVal::U64(n) => n.to_string(),
+        ScVal::I64(n) => n.to_string(),
+        ScVal::Timepoint(t) => t.0.to_string(),
+        ScVal::Duration(d) => d.0.to_string(),
+        ScVal::U128(p) => (((p.hi as u128) << 64) | p.lo as u128).to_string(),
+        ScVal::I128(p) => (((p.hi as i128) << 64) | p.lo as i128).to_string(),
+        ScVal::Symbol(s) => String::from_utf8_lossy(s.as_slice()).into_owned(),
+        ScVal::String(s) => format!("{:?}", String::from_utf8_lossy(s.as_slice())),
+        ScVal::Bytes(b) => b.iter().map(|byte| format!("{byte:02x}")).collect(),
+        ScVal::Address(a) => a.to_string(),
+        ScVal::Vec(Some(items)) => match items.split_first() {
+            Some((ScVal::Symbol(name), [])) => render(&ScVal::Symbol(name.clone())),
+            Some((ScVal::Symbol(name), args)) => format!(
+                "{}({})",
+                render(&ScVal::Symbol(name.clone())),
+                join(args.iter().map(render))
+            ),
+            _ => format!("[{}]", join(items.iter().map(render))),
+        },
+        ScVal::Map(Some(entries)) => format!(
+            "{{{}}}",
+            join(
+                entries
+                    .iter()
+                    .map(|e| format!("{}: {}", render(&e.key), render(&e.val)))
+            )
+        ),
+        other => format!("{other:?}"),
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,204 @@
+//! Workspace automation for the Synchro contracts.
+//!
+//! ```text
+//! cargo xtask build
+//! cargo xtask deploy <local|testnet|mainnet> --source <identity> [--confirm-mainnet]
+//! cargo xtask diff --before <snapshot|live> --after <snapshot|live> [--contract <id>] [--network <network>]
+//! ```
+//!
+//! `deploy` builds every contract, deploys them with the Stellar CLI, runs
+//! `init` on the renewal contract with the source identity as admin, wires
+//! the logging contract into the renewal contract, and writes
+//! `deployments/<network>.json`.
+//!
+//! `diff` prints what changed in contract storage between two ledger
+//! snapshots; `live` snapshots `--contract` on `--network` first.
+
+mod deploy;
+mod diff;
+mod manifest;
+mod network;
+mod runner;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use deploy::Deployer;
+use diff::Source;
+use network::Network;
+use runner::SystemRunner;
+
+const USAGE: &str = "usage:
+  cargo xtask build
+  cargo xtask deploy <local|testnet|mainnet> --source <identity> [--confirm-mainnet]
+  cargo xtask diff --before <snapshot|live> --after <snapshot|live> [--contract <id>] [--network <network>]
+
+environment:
+  SYNCRO_DEPLOY_SOURCE     default for --source
+  SYNCRO_MAINNET_RPC_URL   RPC endpoint used for mainnet deployments";
+
+/// Parsed command line.
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Build,
+    Deploy {
+        network: Network,
+        source: String,
+        confirm_mainnet: bool,
+    },
+    Diff {
+        before: Source,
+        after: Source,
+        contract: Option<String>,
+        network: Option<Network>,
+    },
+}
+
+fn parse_args(args: &[String], default_source: Option<String>) -> Result<Command, String> {
+    let (cmd, rest) = args.split_first().ok_or("missing command")?;
+    match cmd.as_str() {
+        "build" => {
+            if !rest.is_empty() {
+                return Err(format!("unexpected argument `{}`", rest[0]));
+            }
+            Ok(Command::Build)
+        }
+        "deploy" => {
+            let mut network = None;
+            let mut source = default_source;
+            let mut confirm_mainnet = false;
+            let mut iter = rest.iter();
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "--source" => {
+                        source = Some(iter.next().ok_or("--source requires a value")?.clone());
+                    }
+                    "--confirm-mainnet" => confirm_mainnet = true,
+                    other if network.is_none() && !other.starts_with("--") => {
+                        network = Some(other.parse::<Network>()?);
+                    }
+                    other => return Err(format!("unexpected argument `{other}`")),
+                }
+            }
+            Ok(Command::Deploy {
+                network: network.ok_or("missing network")?,
+                source: source.ok_or("missing --source (or SYNCRO_DEPLOY_SOURCE)")?,
+                confirm_mainnet,
+            })
+        }
+        "diff" => {
+            let (mut before, mut after, mut contract, mut network) = (None, None, None, None);
+            let mut iter = rest.iter();
+            while let Some(arg) = iter.next() {
+                let mut value = || iter.next().ok_or(format!("{arg} requires a value"));
+                match arg.as_str() {
+                    "--before" => before = Some(Source::parse(value()?)),
+                    "--after" => after = Some(Source::parse(value()?)),
+                    "--contract" => contract = Some(value()?.clone()),
+                    "--network" => network = Some(value()?.parse::<Network>()?),
+                    other => return Err(format!("unexpected argument `{other}`")),
+                }
+            }
+            let (before, after) = (
+                before.ok_or("missing --before")?,
+                after.ok_or("missing --after")?,
+            );
+            if (before == Source::Live || after == Source::Live)
+                && (contract.is_none() || network.is_none())
+            {
+                return Err("`live` requires --contract and --network".into());
+            }
+            Ok(Command::Diff {
+                before,
+                after,
+                contract,
+                network,
+            })
+        }
+        other => Err(format!("unknown command `{other}`")),
+    }
+}
+
+/// The workspace root, i.e. the parent of this crate's directory.
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives inside the workspace")
+        .to_path_buf()
+}
+
+fn run(command: Command) -> Result<(), String> {
+    let root = workspace_root();
+    let mut runner = SystemRunner::new(&root);
+
+    match command {
+        Command::Build => {
+            Deployer::new(&mut runner, &root).build()?;
+        }
+        Command::Deploy {
+            network,
+            source,
+            confirm_mainnet,
+        } => {
+            if network == Network::Mainnet && !confirm_mainnet {
+                return Err("refusing to deploy to mainnet without --confirm-mainnet".into());
+            }
+            let profile = network.profile(std::env::var("SYNCRO_MAINNET_RPC_URL").ok())?;
+            let manifest = Deployer::new(&mut runner, &root).deploy(&profile, &source)?;
+
+            let path = manifest::path_for(&root, network);
+            manifest.write(&path)?;
+            println!("wrote {}", path.display());
+        }
+        Command::Diff {
+            before,
+            after,
+            contract,
+            network,
+        } => {
+            let mut state = |source: Source| -> Result<diff::State, String> {
+                let path = match source {
+                    Source::File(path) => path,
+                    Source::Live => {
+                        let (Some(network), Some(contract)) = (network, contract.as_deref()) else {
+                            unreachable!("checked when parsing");
+                        };
+                        let profile =
+                            network.profile(std::env::var("SYNCRO_MAINNET_RPC_URL").ok())?;
+                        diff::fetch_live(&mut runner, &root, &profile, contract)?
+                    }
+                };
+                Ok(diff::state(&diff::load(&path)?, contract.as_deref()))
+            };
+            let (before, after) = (state(before)?, state(after)?);
+
+            match diff::diff(&before, &after) {
+                report if report.is_empty() => println!("no differences"),
+                report => print!("{report}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args, std::env::var("SYNCRO_DEPLOY_SOURCE").ok()) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::network::Network;
+
+/// Record of a deployment, written to `deployments/<network>.json`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub network: Network,
+    pub admin: String,
+    /// (contract name, contract id) in deployment order.
+    pub contracts: Vec<(String, String)>,
+}
+
+impl Manifest {
+    pub fn new(network: Network, admin: String) -> Self {
+        Self {
+            network,
+            admin,
+            contracts: Vec::new(),
+        }
+    }
+
+    pub fn contract(&self, name: &str) -> Result<&str, String> {
+        self.contracts
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, id)| id.as_str())
+            .ok_or_else(|| format!("{name} missing from manifest"))
+    }
+
+    /// Deterministic JSON rendering (stable key order, trailing newline).
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        out.push_str(&format!(
+            "  \"network\": {},\n",
+            quote(self.network.as_str())
+        ));
+        out.push_str(&format!("  \"admin\": {},\n", quote(&self.admin)));
+        out.push_str("  \"contracts\": {");
+        for (i, (name, id)) in self.contracts.iter().enumerate() {
+            let sep = if i == 0 { "\n" } else { ",\n" };
+            out.push_str(&format!("{sep}    {}: {}", quote(name), quote(id)));
+        }
+        if !self.contracts.is_empty() {
+            out.push_str("\n  ");
+        }
+        out.push_str("}\n}\n");
+        out
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        }
+        fs::write(path, self.to_json()).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+pub fn path_for(root: &Path, network: Network) -> PathBuf {
+    root.join("deployments").join(format!("{network}.json"))
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
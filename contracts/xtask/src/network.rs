@@ -0,0 +1,71 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Deployment target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Local,
+    Testnet,
+    Mainnet,
+}
+
+/// Connection settings passed to every Stellar CLI invocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub network: Network,
+    pub rpc_url: String,
+    pub passphrase: &'static str,
+}
+
+impl Network {
+    /// Resolve the connection profile. Mainnet has no public default RPC,
+    /// so its endpoint must be supplied by the operator.
+    pub fn profile(self, mainnet_rpc_url: Option<String>) -> Result<Profile, String> {
+        let (rpc_url, passphrase) = match self {
+            Network::Local => (
+                "http://localhost:8000/rpc".to_string(),
+                "Standalone Network ; February 2017",
+            ),
+            Network::Testnet => (
+                "https://soroban-testnet.stellar.org".to_string(),
+                "Test SDF Network ; September 2015",
+            ),
+            Network::Mainnet => (
+                mainnet_rpc_url.ok_or("SYNCRO_MAINNET_RPC_URL must be set for mainnet")?,
+                "Public Global Stellar Network ; September 2015",
+            ),
+        };
+        Ok(Profile {
+            network: self,
+            rpc_url,
+            passphrase,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Network::Local => "local",
+            Network::Testnet => "testnet",
+            Network::Mainnet => "mainnet",
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Network::Local),
+            "testnet" => Ok(Network::Testnet),
+            "mainnet" => Ok(Network::Mainnet),
+            other => Err(format!("unknown network `{other}`")),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Executes external programs. Abstracted so the deployment sequence can be
+/// exercised in tests without a toolchain or network.
+pub trait Runner {
+    /// Run `program` with `args` and return its trimmed stdout.
+    fn run(&mut self, program: &str, args: &[String]) -> Result<String, String>;
+}
+
+/// Runs commands for real, from the workspace root.
+pub struct SystemRunner {
+    cwd: PathBuf,
+}
+
+impl SystemRunner {
+    pub fn new(cwd: &Path) -> Self {
+        Self {
+            cwd: cwd.to_path_buf(),
+        }
+    }
+}
+
+impl Runner for SystemRunner {
+    fn run(&mut self, program: &str, args: &[String]) -> Result<String, String> {
+        println!("$ {} {}", program, args.join(" "));
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(&self.cwd)
+            .output()
+            .map_err(|e| format!("failed to spawn `{program}`: {e}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "`{program}` exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
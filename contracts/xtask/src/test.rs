@@ -0,0 +1,284 @@
+use super::*;
+use crate::deploy::{validate_contract_id, CONTRACTS, WASM_TARGET};
+use crate::diff::{self, Source};
+use crate::manifest::Manifest;
+use crate::runner::Runner;
+
+const ADMIN: &str = "GADMINADMINADMINADMINADMINADMINADMINADMINADMINADMINADMIN";
+
+/// Records every invocation and answers deploys with fake contract ids.
+#[derive(Default)]
+struct FakeRunner {
+    calls: Vec<(String, Vec<String>)>,
+    deploys: usize,
+}
+
+impl Runner for FakeRunner {
+    fn run(&mut self, program: &str, args: &[String]) -> Result<String, String> {
+        self.calls.push((program.to_string(), args.to_vec()));
+        match args.first().map(String::as_str) {
+            Some("keys") => Ok(ADMIN.to_string()),
+            Some("contract") if args[1] == "deploy" => {
+                self.deploys += 1;
+                Ok(format!("C{:055}", self.deploys))
+            }
+            _ => Ok(String::new()),
+        }
+    }
+}
+
+fn args(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_parse_build() {
+    assert_eq!(parse_args(&args(&["build"]), None), Ok(Command::Build));
+    assert!(parse_args(&args(&["build", "extra"]), None).is_err());
+}
+
+#[test]
+fn test_parse_deploy() {
+    let cmd = parse_args(&args(&["deploy", "testnet", "--source", "alice"]), None).unwrap();
+    assert_eq!(
+        cmd,
+        Command::Deploy {
+            network: Network::Testnet,
+            source: "alice".into(),
+            confirm_mainnet: false,
+        }
+    );
+}
+
+#[test]
+fn test_parse_deploy_uses_default_source() {
+    let cmd = parse_args(&args(&["deploy", "local"]), Some("ci".into())).unwrap();
+    assert_eq!(
+        cmd,
+        Command::Deploy {
+            network: Network::Local,
+            source: "ci".into(),
+            confirm_mainnet: false,
+        }
+    );
+}
+
+#[test]
+fn test_parse_deploy_errors() {
+    assert!(parse_args(&args(&["deploy"]), Some("ci".into())).is_err());
+    assert!(parse_args(&args(&["deploy", "devnet", "--source", "a"]), None).is_err());
+    assert!(parse_args(&args(&["deploy", "local"]), None).is_err());
+    assert!(parse_args(&args(&["deploy", "local", "--source"]), None).is_err());
+    assert!(parse_args(&args(&["frobnicate"]), None).is_err());
+    assert!(parse_args(&[], None).is_err());
+}
+
+#[test]
+fn test_mainnet_requires_rpc_url() {
+    assert!(Network::Mainnet.profile(None).is_err());
+    let profile = Network::Mainnet
+        .profile(Some("https://rpc.example".into()))
+        .unwrap();
+    assert_eq!(profile.rpc_url, "https://rpc.example");
+    assert!(Network::Testnet.profile(None).is_ok());
+}
+
+#[test]
+fn test_mainnet_deploy_requires_confirmation() {
+    let err = run(Command::Deploy {
+        network: Network::Mainnet,
+        source: "alice".into(),
+        confirm_mainnet: false,
+    })
+    .unwrap_err();
+    assert!(err.contains("--confirm-mainnet"));
+}
+
+#[test]
+fn test_build_compiles_all_contracts_for_wasm() {
+    let mut runner = FakeRunner::default();
+    let root = Path::new("/ws");
+    Deployer::new(&mut runner, root).build().unwrap();
+
+    let (program, build_args) = &runner.calls[0];
+    assert_eq!(program, "cargo");
+    assert!(build_args.contains(&WASM_TARGET.to_string()));
+    for (_, package) in CONTRACTS {
+        assert!(build_args.contains(&package.to_string()));
+    }
+}
+
+#[test]
+fn test_deploy_sequence_and_wiring() {
+    let mut runner = FakeRunner::default();
+    let root = Path::new("/ws");
+    let profile = Network::Local.profile(None).unwrap();
+    let manifest = Deployer::new(&mut runner, root)
+        .deploy(&profile, "alice")
+        .unwrap();
+
+    assert_eq!(manifest.admin, ADMIN);
+    assert_eq!(manifest.contracts.len(), CONTRACTS.len());
+
+    let renewal = manifest.contract("subscription_renewal").unwrap();
+    let logging = manifest.contract("subscription_logging").unwrap();
+
+    // build, keys address, one deploy per contract, init, wiring
+    assert_eq!(runner.calls.len(), 2 + CONTRACTS.len() + 2);
+
+    let init = &runner.calls[runner.calls.len() - 2].1;
+    assert!(init.contains(&renewal.to_string()));
+    assert_eq!(
+        init[init.len() - 3..],
+        args(&["init", "--admin", ADMIN])[..]
+    );
+
+    let wire = &runner.calls[runner.calls.len() - 1].1;
+    assert_eq!(
+        wire[wire.len() - 3..],
+        args(&["set_logging_contract", "--address", logging])[..]
+    );
+    assert!(wire.contains(&profile.rpc_url));
+}
+
+#[test]
+fn test_deploy_rejects_bad_contract_id() {
+    struct BadDeploy;
+    impl Runner for BadDeploy {
+        fn run(&mut self, _: &str, args: &[String]) -> Result<String, String> {
+            match args.first().map(String::as_str) {
+                Some("contract") => Ok("error: something went wrong".into()),
+                _ => Ok(ADMIN.into()),
+            }
+        }
+    }
+
+    let profile = Network::Local.profile(None).unwrap();
+    let err = Deployer::new(&mut BadDeploy, Path::new("/ws"))
+        .deploy(&profile, "alice")
+        .unwrap_err();
+    assert!(err.contains("invalid contract id"));
+}
+
+#[test]
+fn test_validate_contract_id() {
+    assert!(validate_contract_id("x", &format!("C{:055}", 1)).is_ok());
+    assert!(validate_contract_id("x", &format!("G{:055}", 1)).is_err());
+    assert!(validate_contract_id("x", "C123").is_err());
+}
+
+#[test]
+fn test_manifest_json_is_deterministic() {
+    let mut manifest = Manifest::new(Network::Testnet, "GABC".into());
+    manifest.contracts.push(("a".into(), "CA".into()));
+    manifest.contracts.push(("b".into(), "CB".into()));
+
+    let expected = "{\n  \"network\": \"testnet\",\n  \"admin\": \"GABC\",\n  \"contracts\": {\n    \"a\": \"CA\",\n    \"b\": \"CB\"\n  }\n}\n";
+    assert_eq!(manifest.to_json(), expected);
+    assert_eq!(
+        manifest::path_for(Path::new("/ws"), Network::Testnet),
+        Path::new("/ws/deployments/testnet.json")
+    );
+}
+
+#[test]
+fn test_parse_diff() {
+    let cmd = parse_args(
+        &args(&["diff", "--before", "a.json", "--after", "b.json"]),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        cmd,
+        Command::Diff {
+            before: Source::File("a.json".into()),
+            after: Source::File("b.json".into()),
+            contract: None,
+            network: None,
+        }
+    );
+
+    let cmd = parse_args(
+        &args(&[
+            "diff",
+            "--before",
+            "a.json",
+            "--after",
+            "live",
+            "--contract",
+            "CX",
+            "--network",
+            "testnet",
+        ]),
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        cmd,
+        Command::Diff {
+            after: Source::Live,
+            network: Some(Network::Testnet),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_parse_diff_errors() {
+    assert!(parse_args(&args(&["diff", "--before", "a.json"]), None).is_err());
+    assert!(parse_args(&args(&["diff", "--before", "a.json", "--after"]), None).is_err());
+    // Fetching live state needs to know what and where
+    assert!(parse_args(
+        &args(&["diff", "--before", "a.json", "--after", "live"]),
+        None
+    )
+    .is_err());
+}
+
+#[test]
+fn test_diff_of_fixture_snapshots() {
+    let fixtures = workspace_root().join("contracts/subscription_renewal/test_fixtures");
+    let state = |name: &str| diff::state(&diff::load(&fixtures.join(name)).unwrap(), None);
+    let (mid_retry, near_cap) = (state("mid_retry.json"), state("near_cap.json"));
+
+    // NearCap is MidRetry plus one more failure
+    let report = diff::diff(&mid_retry, &near_cap);
+    let contract = "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM";
+    assert!(report.contains(&format!(
+        "{contract} subscriptions\n  ~ 1.failure_count: 1 -> 2\n"
+    )));
+    assert!(!report.contains(" config\n"));
+    assert_eq!(diff::diff(&near_cap, &near_cap), "");
+}
+
+#[test]
+fn test_diff_reports_added_and_removed_entries() {
+    let key = |label: &str| ("C1".to_string(), "config", label.to_string());
+    let before = diff::State::from([(key("Admin"), "GA".into()), (key("Paused"), "true".into())]);
+    let after = diff::State::from([
+        (key("Admin"), "GA".into()),
+        (key("FeeConfig.percentage"), "100".into()),
+    ]);
+
+    assert_eq!(
+        diff::diff(&before, &after),
+        "C1 config\n  + FeeConfig.percentage: 100\n  - Paused: true\n"
+    );
+}
+
+#[test]
+fn test_fetch_live_snapshots_contract() {
+    let mut runner = FakeRunner::default();
+    let root = std::env::temp_dir().join("syncro-xtask-test");
+    let profile = Network::Testnet.profile(None).unwrap();
+    let path = diff::fetch_live(&mut runner, &root, &profile, "CX").unwrap();
+
+    assert_eq!(path, root.join("target/xtask/testnet-CX.json"));
+    let (program, snapshot_args) = &runner.calls[0];
+    assert_eq!(program, "stellar");
+    assert_eq!(
+        snapshot_args[..4],
+        args(&["snapshot", "create", "--address", "CX"])[..]
+    );
+    assert!(snapshot_args.contains(&profile.rpc_url));
+}
@@ -0,0 +1,51 @@
+import { describe, it, expect } from "@jest/globals";
+import {
+  assertSaneAmount,
+  fromBaseUnits,
+  maxAmount,
+  toBaseUnits,
+} from "./amounts.js";
+
+describe("token amounts", () => {
+  describe("toBaseUnits", () => {
+    it("should scale by the token's decimals", () => {
+      expect(toBaseUnits("12.5", 7)).toBe(125_000_000n);
+      expect(toBaseUnits("12.5", 18)).toBe(12_500_000_000_000_000_000n);
+      expect(toBaseUnits("3", 0)).toBe(3n);
+      expect(toBaseUnits("1.10000000", 7)).toBe(11_000_000n);
+    });
+
+    it("should reject amounts finer than the token supports", () => {
+      expect(() => toBaseUnits("0.00000001", 7)).toThrow(
+        "Token amount 0.00000001 has more than 7 decimal places",
+      );
+    });
+
+    it("should reject malformed amounts and decimals", () => {
+      expect(() => toBaseUnits("1e5", 7)).toThrow("Invalid token amount: 1e5");
+      expect(() => toBaseUnits("1", -1)).toThrow("Invalid token decimals: -1");
+    });
+  });
+
+  describe("fromBaseUnits", () => {
+    it("should format without trailing zeros", () => {
+      expect(fromBaseUnits(125_000_000n, 7)).toBe("12.5");
+      expect(fromBaseUnits(1n, 7)).toBe("0.0000001");
+      expect(fromBaseUnits(10n ** 18n, 18)).toBe("1");
+      expect(fromBaseUnits(-1n, 7)).toBe("-0.0000001");
+    });
+  });
+
+  describe("assertSaneAmount", () => {
+    it("should accept amounts up to the contract's bound", () => {
+      expect(maxAmount(7)).toBe(10n ** 16n);
+      expect(() => assertSaneAmount(maxAmount(7), 7)).not.toThrow();
+    });
+
+    it("should reject an 18-decimal amount sent for a 7-decimal token", () => {
+      expect(() => assertSaneAmount(toBaseUnits("10", 18), 7)).toThrow(
+        "out of range for a token with 7 decimals",
+      );
+    });
+  });
+});
@@ -0,0 +1,70 @@
+/**
+ * Token Amount Helpers
+ * Converts between human-readable token amounts and on-chain base units
+ * using each token's `decimals()`, mirroring the contract's bounds check.
+ */
+
+/** Most whole tokens a configured price, cap or limit may be (matches the contract). */
+export const MAX_WHOLE_TOKENS = 1_000_000_000n;
+
+function assertDecimals(decimals: number): void {
+  if (!Number.isInteger(decimals) || decimals < 0 || decimals > 38) {
+    throw new Error(`Invalid token decimals: ${decimals}`);
+  }
+}
+
+/**
+ * Converts a decimal string such as `"12.5"` to base units for a token with
+ * `decimals` decimals. Throws rather than rounding when the amount has more
+ * fractional digits than the token supports.
+ */
+export function toBaseUnits(amount: string, decimals: number): bigint {
+  assertDecimals(decimals);
+  const match = /^(-?)(\d+)(?:\.(\d+))?$/.exec(amount.trim());
+  if (!match) {
+    throw new Error(`Invalid token amount: ${amount}`);
+  }
+  const [, sign, whole = "0", fraction = ""] = match;
+  if (fraction.replace(/0+$/, "").length > decimals) {
+    throw new Error(
+      `Token amount ${amount} has more than ${decimals} decimal places`,
+    );
+  }
+  const units =
+    BigInt(whole) * 10n ** BigInt(decimals) +
+    BigInt(fraction.slice(0, decimals).padEnd(decimals, "0") || "0");
+  return sign === "-" ? -units : units;
+}
+
+/** Formats base units of a token with `decimals` decimals as a decimal string. */
+export function fromBaseUnits(units: bigint, decimals: number): string {
+  assertDecimals(decimals);
+  const negative = units < 0n;
+  const abs = negative ? -units : units;
+  const scale = 10n ** BigInt(decimals);
+  const whole = (abs / scale).toString();
+  const fraction = (abs % scale)
+    .toString()
+    .padStart(decimals, "0")
+    .replace(/0+$/, "");
+  return `${negative ? "-" : ""}${whole}${fraction ? `.${fraction}` : ""}`;
+}
+
+/** Largest amount, in base units, the contract accepts for a price or cap in this token. */
+export function maxAmount(decimals: number): bigint {
+  assertDecimals(decimals);
+  return MAX_WHOLE_TOKENS * 10n ** BigInt(decimals);
+}
+
+/**
+ * Throws if `units` is outside what the contract accepts for a token with
+ * `decimals` decimals, e.g. an amount scaled for 18 decimals sent for a
+ * 7-decimal token.
+ */
+export function assertSaneAmount(units: bigint, decimals: number): void {
+  if (units < 0n || units > maxAmount(decimals)) {
+    throw new Error(
+      `Token amount ${units} is out of range for a token with ${decimals} decimals`,
+    );
+  }
+}
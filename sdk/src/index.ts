@@ -327,3 +327,10 @@ export function init(config: SyncroSDKInitConfig): SyncroSDK {
 
 export default SyncroSDK;
 export type { GiftCardEvent, GiftCardEventType } from "./types";
+export {
+  MAX_WHOLE_TOKENS,
+  assertSaneAmount,
+  fromBaseUnits,
+  maxAmount,
+  toBaseUnits,
+} from "./amounts";